@@ -5,6 +5,7 @@ use std::time::Duration;
 use bevy::prelude::*;
 use mpm2d::core::{ParticleRemap, cleanup_grid_cells, zero_grid};
 use mpm2d::core::{clear_particle_remap_system, remove_failed_particles_system};
+use mpm2d::math::{Real, Vector};
 use mpm2d::solver::{grid_to_particle, grid_update, particle_to_grid};
 use mpm2d::{GRAVITY, GRID_RESOLUTION, MaterialType, MpmState, Particle, SolverParams};
 
@@ -12,12 +13,15 @@ use mpm2d::{GRAVITY, GRID_RESOLUTION, MaterialType, MpmState, Particle, SolverPa
 struct TrackingAllocator;
 
 static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+/// Every byte ever allocated, never decremented.
+static ALLOCATED_TOTAL: AtomicUsize = AtomicUsize::new(0);
 
 unsafe impl GlobalAlloc for TrackingAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let ret = unsafe { System.alloc(layout) };
         if !ret.is_null() {
             ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+            ALLOCATED_TOTAL.fetch_add(layout.size(), Ordering::SeqCst);
         }
         ret
     }
@@ -41,7 +45,7 @@ fn memory_benchmark_system(mut state: ResMut<MpmState>, mut frame_count: Local<u
     if *frame_count == 1 {
         for x in 0..50 {
             for y in 0..100 {
-                let position = Vec2::new(x as f32 + 55.0, y as f32 + 20.0);
+                let position = Vector::new(x as Real + 55.0, y as Real + 20.0);
                 let mut particle = Particle::zeroed(MaterialType::water());
                 particle.position = position;
                 state.add_particle(particle);
@@ -64,6 +68,17 @@ fn memory_benchmark_system(mut state: ResMut<MpmState>, mut frame_count: Local<u
         );
         println!("Estimated sparse overhead: HashMap buckets, keys, etc.");
 
+        // The solver has been rebinning this particle count for ten frames,
+        // so its buffers should all be sized already
+        let before = ALLOCATED_TOTAL.load(Ordering::SeqCst);
+        state.rebuild_particle_bins();
+        let rebin_bytes = ALLOCATED_TOTAL.load(Ordering::SeqCst) - before;
+        println!("Allocated by a steady-state rebin: {} bytes", rebin_bytes);
+        println!(
+            "Spatial index footprint: {} KB",
+            state.particle_set().index_heap_bytes() / 1024
+        );
+
         std::process::exit(0);
     }
 }
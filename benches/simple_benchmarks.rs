@@ -1,10 +1,22 @@
+use indexmap::IndexMap;
+use mpm2d::core::{update_particles_health, update_particles_health_serial};
+use mpm2d::geometry::{PackedCellBuildHasher, pack_coords};
+use mpm2d::math::{Real, Vector};
+use mpm2d::solver::{
+    transfer_grid_to_particles, transfer_grid_to_particles_serial, transfer_particles_to_grid,
+    transfer_particles_to_grid_serial,
+};
+use mpm2d::{
+    GRAVITY, GRID_RESOLUTION, Grid, GridBackend, MaterialType, MpmState, Particle, SolverParams,
+};
 /// Simple custom benchmarking without criterion
 /// Avoids Windows MSVC linker issues with rayon/criterion
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
 use std::time::Instant;
-use bevy::prelude::*;
-use mpm2d::{MpmState, SolverParams, Particle, MaterialType, GRAVITY};
 
-fn time_it<F: FnMut()>(name: &str, iterations: usize, mut f: F) {
+/// Prints and returns the average milliseconds per iteration.
+fn time_it<F: FnMut()>(name: &str, iterations: usize, mut f: F) -> f64 {
     // Warmup
     for _ in 0..5 {
         f();
@@ -18,10 +30,39 @@ fn time_it<F: FnMut()>(name: &str, iterations: usize, mut f: F) {
 
     let avg_ms = elapsed.as_secs_f64() * 1000.0 / iterations as f64;
     println!("{}: {:.3}ms avg ({} iterations)", name, avg_ms, iterations);
+    avg_ms
+}
+
+/// The P2G access pattern on its own: a 3x3 stencil scatter per particle
+/// into a map keyed by packed cell.
+fn scatter_stencils<S: BuildHasher + Default>(particles: &[Particle]) -> Real {
+    let mut cells: IndexMap<u64, Real, S> = IndexMap::default();
+    for particle in particles {
+        let (ix, iy) = (particle.position.x as i32, particle.position.y as i32);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                *cells.entry(pack_coords(ix + dx, iy + dy)).or_default() += particle.mass;
+            }
+        }
+    }
+    cells.values().sum()
+}
+
+/// Four particles per cell over a square covering `fill` of the domain.
+fn create_filled_particles(fill: Real) -> Vec<Particle> {
+    let side = ((GRID_RESOLUTION - 8) as Real * fill.sqrt()) as usize * 2;
+    let mut particles = Vec::new();
+    for x in 0..side {
+        for y in 0..side {
+            let position = Vector::new(x as Real * 0.5 + 4.25, y as Real * 0.5 + 4.25);
+            particles.push(Particle::new(position, MaterialType::water()));
+        }
+    }
+    particles
 }
 
 fn create_test_particles(count: usize) -> Vec<Particle> {
-    let side = (count as f32).sqrt() as usize;
+    let side = (count as Real).sqrt() as usize;
     let mut particles = Vec::new();
 
     for x in 0..side {
@@ -29,10 +70,10 @@ fn create_test_particles(count: usize) -> Vec<Particle> {
             if particles.len() >= count {
                 break;
             }
-            let position = Vec2::new(x as f32 + 16.0, y as f32 + 32.0);
+            let position = Vector::new(x as Real + 16.0, y as Real + 32.0);
             let mut particle = Particle::zeroed(MaterialType::water());
             particle.position = position;
-            particle.velocity = Vec2::new(1.0, -2.0);
+            particle.velocity = Vector::new(1.0, -2.0);
             particles.push(particle);
         }
     }
@@ -52,10 +93,37 @@ fn main() {
             state.add_particle(p);
         }
 
+        time_it(&format!("rebuild_bins (n={})", count), 20, || {
+            state.rebuild_particle_bins();
+        });
+    }
+
+    println!("\n--- Rebinning: full vs incremental ---");
+    for &fill in &[0.1, 0.4] {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        let particles = create_filled_particles(fill);
+        let count = particles.len();
+        for p in particles {
+            state.add_particle(p);
+        }
+        state.rebuild_particle_bins();
+
+        // A slow drift, as between frames of a settled pool
+        let nudge = |state: &mut MpmState| {
+            for particle in state.particles_mut() {
+                particle.position.y -= 0.002;
+            }
+        };
+        time_it(&format!("rebuild_bins full (n={})", count), 20, || {
+            nudge(&mut state);
+            state.particle_set_mut().invalidate_spatial_index();
+            state.rebuild_particle_bins();
+        });
         time_it(
-            &format!("rebuild_bins (n={})", count),
+            &format!("rebuild_bins incremental (n={})", count),
             20,
             || {
+                nudge(&mut state);
                 state.rebuild_particle_bins();
             },
         );
@@ -70,13 +138,9 @@ fn main() {
         }
         state.rebuild_particle_bins();
 
-        time_it(
-            &format!("zero_grid (n={})", count),
-            50,
-            || {
-                state.zero_grid();
-            },
-        );
+        time_it(&format!("zero_grid (n={})", count), 50, || {
+            state.zero_grid();
+        });
     }
 
     println!("\n--- Combined Operations ---");
@@ -87,15 +151,129 @@ fn main() {
             state.add_particle(p);
         }
 
+        time_it(&format!("bin+zero (n={})", count), 10, || {
+            state.rebuild_particle_bins();
+            state.zero_grid();
+        });
+    }
+
+    println!("\n--- G2P: serial vs parallel ---");
+    for &count in &[5000, 20000] {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for p in create_test_particles(count) {
+            state.add_particle(p);
+        }
+        transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+
+        // Tiny dt keeps the particles in their cells between iterations
+        time_it(&format!("g2p serial (n={})", count), 50, || {
+            transfer_grid_to_particles_serial(&mut state, 1e-6);
+        });
+        time_it(&format!("g2p parallel (n={})", count), 50, || {
+            transfer_grid_to_particles(&mut state, 1e-6);
+        });
+    }
+
+    println!("\n--- Health check: serial vs parallel ---");
+    {
+        let count = 50_000;
+        let mut particles: Vec<Particle> = (0..count)
+            .map(|i| {
+                let position = Vector::new((i % 250) as Real * 0.5, (i / 250) as Real * 0.5);
+                Particle::new(position, MaterialType::water())
+            })
+            .collect();
+        time_it(&format!("health serial (n={})", count), 50, || {
+            update_particles_health_serial(&mut particles, 1.0e6);
+        });
+        time_it(&format!("health parallel (n={})", count), 50, || {
+            update_particles_health(&mut particles, 1.0e6);
+        });
+    }
+
+    println!("\n--- P2G: serial vs parallel ---");
+    for &count in &[5000, 20000] {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for p in create_test_particles(count) {
+            state.add_particle(p);
+        }
+
+        time_it(&format!("p2g serial (n={})", count), 50, || {
+            state.zero_grid();
+            transfer_particles_to_grid_serial(&mut state, 1.0 / 60.0);
+        });
+        time_it(&format!("p2g parallel (n={})", count), 50, || {
+            state.zero_grid();
+            transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+        });
+    }
+
+    println!("\n--- SpGrid hasher: SipHash vs packed-cell ---");
+    for &count in &[5000, 20000] {
+        let particles = create_test_particles(count);
         time_it(
-            &format!("bin+zero (n={})", count),
-            10,
+            &format!("stencil scatter SipHash (n={})", count),
+            50,
             || {
-                state.rebuild_particle_bins();
-                state.zero_grid();
+                std::hint::black_box(scatter_stencils::<RandomState>(&particles));
+            },
+        );
+        time_it(
+            &format!("stencil scatter packed-cell (n={})", count),
+            50,
+            || {
+                std::hint::black_box(scatter_stencils::<PackedCellBuildHasher>(&particles));
             },
         );
     }
 
+    println!("\n--- Grid sweep: insertion vs Morton order ---");
+    for &count in &[5000, 20000] {
+        let mut averages = Vec::new();
+        for morton in [false, true] {
+            let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+            // Reverse insertion scatters the nodes relative to their neighbours
+            for p in create_test_particles(count).into_iter().rev() {
+                state.add_particle(p);
+            }
+            state.grid_mut().set_morton_order(morton);
+            transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+            state.cleanup_grid();
+
+            let order = if morton { "morton" } else { "insertion" };
+            averages.push(time_it(
+                &format!("integrate grid {} (n={})", order, count),
+                100,
+                || {
+                    state.integrate_grid_velocities(1e-6);
+                },
+            ));
+        }
+        println!(
+            "morton speedup (n={}): {:.2}x",
+            count,
+            averages[0] / averages[1]
+        );
+    }
+
+    println!("\n--- Grid backend: sparse vs dense by fill fraction ---");
+    for &fill in &[0.05, 0.25, 0.5, 0.9] {
+        for backend in [GridBackend::Sparse, GridBackend::Dense] {
+            let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+            *state.grid_mut() = Grid::new().with_backend(backend);
+            for p in create_filled_particles(fill) {
+                state.add_particle(p);
+            }
+
+            time_it(&format!("{:?} step (fill={})", backend, fill), 20, || {
+                state.zero_grid();
+                transfer_particles_to_grid(&mut state, 1e-6);
+                state.cleanup_grid();
+                state.integrate_grid_velocities(1e-6);
+                transfer_grid_to_particles(&mut state, 1e-6);
+            });
+        }
+    }
+
     println!("\n=== Benchmark Complete ===\n");
 }
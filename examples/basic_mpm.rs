@@ -1,18 +1,16 @@
 // Minimal MLS-MPM example using the new resource-driven solver state.
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
 use mpm2d::core::{
-    GridInterpolation, MpmState, ParticleRemap, cleanup_grid_cells, remove_failed_particles_system,
-    zero_grid,
+    Grid, GridInterpolation, MpmState, ParticleRemap, cleanup_grid_cells,
+    clear_particle_remap_system, remove_failed_particles_system, zero_grid,
 };
-use mpm2d::solver::{
-    grid_to_particle as solver_grid_to_particle, grid_update,
-    particle_to_grid as solver_particle_to_grid,
-};
-use mpm2d::{FluidParams, GRAVITY, MaterialType, Particle, SolverParams};
-use mpm2d::math::{to_bevy_vec2, from_bevy_vec2};
+use mpm2d::math::Real;
+use mpm2d::solver::{SolverTimings, grid_to_particle, grid_update, particle_to_grid};
+use mpm2d::visuals::{ParticleVisualPlugin, spawn_visual_particle};
+use mpm2d::{FluidParams, GRAVITY, GridConfig, MaterialType, Particle, SolverParams};
 use nalgebra::Vector2;
 use rand::Rng;
 
@@ -21,39 +19,29 @@ const CLUSTER_WIDTH: u32 = 42;
 const CLUSTER_HEIGHT: u32 = 84;
 const WATER_PARAMS: FluidParams = FluidParams::water();
 
-#[derive(Component)]
-struct ParticleVisual {
-    index: usize,
-}
-
-#[derive(Resource, Default, Clone, Copy)]
-struct ExampleTimings {
-    p2g_ms: f32,
-    g2p_ms: f32,
-}
-
-fn sim_to_world(position: Vec2) -> Vec3 {
-    Vec3::new((position.x - 64.0) * 4.0, (position.y - 64.0) * 4.0, 0.0)
-}
-
-fn world_to_sim(position: Vec2) -> Vec2 {
-    Vec2::new(position.x / 4.0 + 64.0, position.y / 4.0 + 64.0)
-}
-
 fn spawn_particle_entity(
     commands: &mut Commands,
     meshes: &mut Assets<Mesh>,
     materials: &mut Assets<ColorMaterial>,
-    index: usize,
-    position: Vec2,
+    state: &mut MpmState,
+    particle: Particle,
     color: Color,
 ) {
-    commands.spawn((
-        ParticleVisual { index },
-        Mesh2d(meshes.add(Circle::new(1.0))),
-        MeshMaterial2d(materials.add(color)),
-        Transform::from_translation(sim_to_world(position)),
-    ));
+    let position = state.sim_to_world(particle.position).extend(0.0);
+    let Some((_, entity)) = spawn_visual_particle(
+        commands,
+        meshes,
+        materials,
+        state,
+        particle,
+        Circle::new(1.0),
+        color,
+    ) else {
+        return;
+    };
+    commands
+        .entity(entity)
+        .insert(Transform::from_translation(position));
 }
 
 fn init_particles(
@@ -69,20 +57,18 @@ fn init_particles(
             for y in 0..CLUSTER_HEIGHT {
                 let mut particle = Particle::zeroed(MaterialType::fluid(WATER_PARAMS));
                 particle.position = Vector2::new(
-                    origin.x + x as f32 / 4.0,
-                    origin.y + y as f32 / 4.0,
+                    origin.x as Real + x as Real / 4.0,
+                    origin.y as Real + y as Real / 4.0,
                 );
                 particle.velocity =
                     Vector2::new(rand.random_range(-1.0..=1.0), rand.random_range(-1.0..=1.0));
 
-                let position = to_bevy_vec2(&particle.position);
-                let index = state.add_particle(particle);
                 spawn_particle_entity(
                     &mut commands,
                     &mut meshes,
                     &mut materials,
-                    index,
-                    position,
+                    &mut state,
+                    particle,
                     Color::hsl(210.0, 0.7, 0.3 + cluster_index as f32 * 0.05),
                 );
             }
@@ -90,6 +76,7 @@ fn init_particles(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn controls(
     mut commands: Commands,
     mut camera_query: Query<(&mut Camera, &mut Transform, &mut Projection)>,
@@ -136,15 +123,13 @@ fn controls(
         let mut particle = Particle::zeroed(MaterialType::fluid(WATER_PARAMS));
         particle.position = position;
         particle.velocity = velocity;
-        let position = to_bevy_vec2(&particle.position);
-        let index = state.add_particle(particle);
 
         spawn_particle_entity(
             &mut commands,
             &mut meshes,
             &mut materials,
-            index,
-            position,
+            &mut state,
+            particle,
             Color::hsl(0.0, 1.0, 0.5),
         );
     }
@@ -160,60 +145,18 @@ fn controls(
     }
 }
 
-fn update_particle_transforms(
-    state: Res<MpmState>,
-    mut query: Query<(&ParticleVisual, &mut Transform)>,
-) {
-    let particles = state.particles();
-    for (visual, mut transform) in query.iter_mut() {
-        if let Some(particle) = particles.get(visual.index) {
-            transform.translation = sim_to_world(to_bevy_vec2(&particle.position));
-        }
-    }
-}
-
-fn apply_particle_remap(
-    mut commands: Commands,
-    remap: Res<ParticleRemap>,
-    mut visuals: Query<(Entity, &mut ParticleVisual)>,
-) {
-    if remap.map.is_empty() {
-        return;
-    }
-
-    let map_len = remap.map.len();
-    for (entity, mut visual) in visuals.iter_mut() {
-        let old_index = visual.index;
-        if old_index >= map_len {
-            continue;
-        }
-
-        match remap.map[old_index] {
-            Some(new_index) => visual.index = new_index,
-            None => {
-                commands.entity(entity).despawn();
-            }
-        }
-    }
-}
-
-fn clear_particle_remap(mut remap: ResMut<ParticleRemap>) {
-    if !remap.map.is_empty() {
-        remap.map.clear();
-    }
-}
-
-fn log_particle_debug(state: Res<MpmState>, timings: Res<ExampleTimings>, mut frame: Local<u32>) {
+fn log_particle_debug(state: Res<MpmState>, timings: Res<SolverTimings>, mut frame: Local<u32>) {
     const SAMPLE_PERIOD: u32 = 30;
     const SAMPLE_COUNT: usize = 3;
 
-    if *frame % SAMPLE_PERIOD == 0 {
+    if (*frame).is_multiple_of(SAMPLE_PERIOD) {
         let mut lines = Vec::new();
         let grid = state.grid();
         let particles = state.particles();
+        let cell_width = grid.cell_width();
 
         for (idx, particle) in particles.iter().enumerate().take(SAMPLE_COUNT) {
-            let interp = GridInterpolation::compute_for_particle(particle.position);
+            let interp = GridInterpolation::compute_for_particle(particle.position, cell_width);
             let mut density = 0.0;
             for (coord, weight, _) in interp.iter_neighbors() {
                 if let Some(cell) = grid.get_cell_coord(coord) {
@@ -234,7 +177,8 @@ fn log_particle_debug(state: Res<MpmState>, timings: Res<ExampleTimings>, mut fr
         if !lines.is_empty() {
             lines.push(format!(
                 "timings: p2g={:.3}ms g2p={:.3}ms",
-                timings.p2g_ms, timings.g2p_ms
+                timings.p2g_ms(),
+                timings.g2p_ms()
             ));
             println!("[frame {:04}] {}", *frame, lines.join(" | "));
         }
@@ -243,26 +187,6 @@ fn log_particle_debug(state: Res<MpmState>, timings: Res<ExampleTimings>, mut fr
     *frame = frame.wrapping_add(1);
 }
 
-fn profile_particle_to_grid(
-    time: Res<Time>,
-    state: ResMut<MpmState>,
-    mut timings: ResMut<ExampleTimings>,
-) {
-    let start = Instant::now();
-    solver_particle_to_grid(time, state);
-    timings.p2g_ms = start.elapsed().as_secs_f32() * 1000.0;
-}
-
-fn profile_grid_to_particle(
-    time: Res<Time>,
-    state: ResMut<MpmState>,
-    mut timings: ResMut<ExampleTimings>,
-) {
-    let start = Instant::now();
-    solver_grid_to_particle(time, state);
-    timings.g2p_ms = start.elapsed().as_secs_f32() * 1000.0;
-}
-
 fn apply_cursor_force(
     mut state: ResMut<MpmState>,
     windows: Query<&Window>,
@@ -282,25 +206,25 @@ fn apply_cursor_force(
         return;
     };
 
-    let sim_pos = from_bevy_vec2(world_to_sim(world_pos));
+    let sim_pos = state.world_to_sim(world_pos);
     let radius = 12.0;
     let strength = 180.0;
-    let dt = time.delta_secs();
+    let dt = time.delta_secs() as Real;
 
     let normal = Vector2::new(0.0, 1.0);
+    let nearby = state.particle_set().query_radius(sim_pos, radius);
     let particles = state.particles_mut();
-    for particle in particles.iter_mut() {
+    for index in nearby {
+        let particle = &mut particles[index];
         let offset = particle.position - sim_pos;
         let distance = offset.norm();
-        if distance < radius {
-            let direction = if distance > 1.0e-4 {
-                offset / distance
-            } else {
-                normal
-            };
-            let falloff = (1.0 - distance / radius).powi(2);
-            particle.velocity += direction * strength * falloff * dt;
-        }
+        let direction = if distance > 1.0e-4 {
+            offset / distance
+        } else {
+            normal
+        };
+        let falloff = (1.0 - distance / radius).powi(2);
+        particle.velocity += direction * strength * falloff * dt;
     }
 }
 
@@ -308,27 +232,35 @@ pub struct MpmPlugin;
 
 impl Plugin for MpmPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(MpmState::new(SolverParams::default(), GRAVITY));
+        // Centre the 128-cell domain on the world origin, 4 world units per cell
+        let grid = GridConfig::default()
+            .with_origin(Vector2::new(-256.0, -256.0))
+            .with_scale(4.0);
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        *state.grid_mut() = Grid::from_config(&grid);
+        app.insert_resource(state);
         app.insert_resource(ParticleRemap::default());
-        app.insert_resource(ExampleTimings::default());
+        app.insert_resource(SolverTimings::default());
         app.insert_resource(Time::<Fixed>::from_duration(Duration::from_secs_f64(
             1.0 / 60.0,
         )));
+        // Adopts the visuals spawned below and keeps them following their particles
+        app.add_plugins(
+            ParticleVisualPlugin::new(Circle::new(1.0), Color::WHITE).in_schedule(FixedUpdate),
+        );
         app.add_systems(Startup, init_particles);
         app.add_systems(
             FixedUpdate,
             (
                 apply_cursor_force,
                 zero_grid,
-                profile_particle_to_grid,
+                particle_to_grid,
                 cleanup_grid_cells,
                 grid_update,
                 log_particle_debug,
-                profile_grid_to_particle,
+                grid_to_particle,
                 remove_failed_particles_system,
-                apply_particle_remap,
-                clear_particle_remap,
-                update_particle_transforms,
+                clear_particle_remap_system,
                 controls,
             )
                 .chain(),
@@ -364,7 +296,7 @@ fn setup_diagnostics(mut commands: Commands) {
 fn update_diagnostics(
     diagnostics: Res<DiagnosticsStore>,
     state: Res<MpmState>,
-    timings: Res<ExampleTimings>,
+    timings: Res<SolverTimings>,
     mut query: Query<&mut Text, With<DiagnosticsText>>,
 ) {
     let particle_count = state.particle_count();
@@ -382,7 +314,11 @@ fn update_diagnostics(
 
         text.0 = format!(
             "FPS: {:.1}\nFrame: {:.2}ms\nParticles: {}\nP2G: {:.3} ms\nG2P: {:.3} ms",
-            fps, frame_time, particle_count, timings.p2g_ms, timings.g2p_ms,
+            fps,
+            frame_time,
+            particle_count,
+            timings.p2g_ms(),
+            timings.g2p_ms(),
         );
     }
 }
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use mpm2d::core::{ParticleRemap, cleanup_grid_cells, zero_grid};
 use mpm2d::core::{clear_particle_remap_system, remove_failed_particles_system};
+use mpm2d::math::{Real, Vector};
 use mpm2d::solver::{grid_to_particle, grid_update, particle_to_grid};
 use mpm2d::{GRAVITY, GRID_RESOLUTION, MaterialType, MpmState, Particle, SolverParams};
 use std::time::Duration;
@@ -9,7 +10,7 @@ fn create_particles(mut state: ResMut<MpmState>) {
     println!("Creating 5000 particles...");
     for x in 0..50 {
         for y in 0..100 {
-            let position = Vec2::new(x as f32 + 55.0, y as f32 + 20.0);
+            let position = Vector::new(x as Real + 55.0, y as Real + 20.0);
             let mut particle = Particle::zeroed(MaterialType::water());
             particle.position = position;
             state.add_particle(particle);
@@ -20,7 +21,7 @@ fn create_particles(mut state: ResMut<MpmState>) {
 fn memory_tracker(state: Res<MpmState>, mut frame_count: Local<u32>) {
     *frame_count += 1;
 
-    if *frame_count % 60 == 0 {
+    if (*frame_count).is_multiple_of(60) {
         let grid = state.grid();
         let active_cells = grid.active_cell_count();
         let total_cells = GRID_RESOLUTION * GRID_RESOLUTION;
@@ -0,0 +1,55 @@
+use bevy::prelude::Resource;
+
+use crate::core::GRID_RESOLUTION;
+use crate::math::{Real, Vector, zero_vector};
+
+/// Size and placement of the simulation grid, inserted by `MpmPlugin`.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct GridConfig {
+    /// Nodes per side of the square domain.
+    pub resolution: usize,
+    pub cell_width: Real,
+    /// World-space position of node (0, 0).
+    pub origin: Vector,
+    /// World units per simulation unit. Particles, colliders and the kernel
+    /// all work in simulation units; only `MpmState::world_to_sim` and
+    /// `MpmState::sim_to_world` apply this.
+    pub scale: Real,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            resolution: GRID_RESOLUTION,
+            cell_width: 1.0,
+            origin: zero_vector(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl GridConfig {
+    pub fn with_resolution(mut self, resolution: usize) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn with_cell_width(mut self, cell_width: Real) -> Self {
+        self.cell_width = cell_width;
+        self
+    }
+
+    pub fn with_origin(mut self, origin: Vector) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Real) -> Self {
+        self.scale = scale;
+        self
+    }
+}
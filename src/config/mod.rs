@@ -3,7 +3,13 @@
 //! Constants and solver settings.
 
 pub mod constants;
+pub mod determinism;
+pub mod grid_config;
+pub mod mpm_config;
 pub mod solver_params;
 
 pub use constants::*;
+pub use determinism::*;
+pub use grid_config::*;
+pub use mpm_config::*;
 pub use solver_params::*;
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::core::{BoundaryConfig, BoundaryHandling, Grid, GridBackend, MpmState};
+use crate::math::{Real, Vector};
+
+use super::constants::GRAVITY;
+use super::determinism::{DeterministicConfig, SimRng};
+use super::grid_config::GridConfig;
+use super::solver_params::SolverParams;
+
+/// Reasons an `MpmConfig` is rejected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MpmConfigError {
+    /// The grid cell width must be finite and strictly positive.
+    InvalidCellWidth(Real),
+    /// The grid resolution must be a positive multiple of 8, the tiling P2G
+    /// splits the grid into for parallel scattering.
+    InvalidResolution(usize),
+    /// The world scale must be finite and strictly positive.
+    InvalidScale(Real),
+    /// Gravity must be finite.
+    NonFiniteGravity,
+    /// Wall friction must be non-negative (infinite is allowed and sticks).
+    InvalidFriction(Real),
+    /// Wall restitution must lie in `[0, 1]`.
+    InvalidRestitution(Real),
+    /// A fixed timestep of zero would never advance the simulation.
+    ZeroTimestep,
+}
+
+impl fmt::Display for MpmConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCellWidth(width) => {
+                write!(f, "cell width must be finite and positive, got {width}")
+            }
+            Self::InvalidResolution(resolution) => {
+                write!(
+                    f,
+                    "grid resolution must be a positive multiple of 8, got {resolution}"
+                )
+            }
+            Self::InvalidScale(scale) => {
+                write!(f, "world scale must be finite and positive, got {scale}")
+            }
+            Self::NonFiniteGravity => write!(f, "gravity must be finite"),
+            Self::InvalidFriction(friction) => {
+                write!(f, "wall friction must be non-negative, got {friction}")
+            }
+            Self::InvalidRestitution(restitution) => {
+                write!(
+                    f,
+                    "wall restitution must be between 0 and 1, got {restitution}"
+                )
+            }
+            Self::ZeroTimestep => write!(f, "fixed timestep must be greater than zero"),
+        }
+    }
+}
+
+impl std::error::Error for MpmConfigError {}
+
+/// Everything needed to set up a simulation, consumed by `MpmPlugin::from_config`.
+#[derive(Clone)]
+pub struct MpmConfig {
+    pub solver_params: SolverParams,
+    pub gravity: Vector,
+    pub boundary: BoundaryConfig,
+    pub grid: GridConfig,
+    pub grid_backend: GridBackend,
+    /// Run the solver in `FixedUpdate` at this rate; `None` steps it once per
+    /// frame in `Update` with the frame delta.
+    pub timestep: Option<Duration>,
+    /// Seed for `SimRng`; `None` seeds it from the system entropy source.
+    pub deterministic: Option<DeterministicConfig>,
+}
+
+impl Default for MpmConfig {
+    fn default() -> Self {
+        Self {
+            solver_params: SolverParams::default(),
+            gravity: GRAVITY,
+            boundary: BoundaryConfig::default(),
+            grid: GridConfig::default(),
+            grid_backend: GridBackend::Sparse,
+            timestep: None,
+            deterministic: None,
+        }
+    }
+}
+
+impl MpmConfig {
+    pub fn with_solver_params(mut self, solver_params: SolverParams) -> Self {
+        self.solver_params = solver_params;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vector) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    /// Per-edge boundary, or one `BoundaryHandling` for all four edges.
+    pub fn with_boundary(mut self, boundary: impl Into<BoundaryConfig>) -> Self {
+        self.boundary = boundary.into();
+        self
+    }
+
+    pub fn with_grid(mut self, grid: GridConfig) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    pub fn with_cell_width(mut self, cell_width: Real) -> Self {
+        self.grid.cell_width = cell_width;
+        self
+    }
+
+    pub fn with_resolution(mut self, resolution: usize) -> Self {
+        self.grid.resolution = resolution;
+        self
+    }
+
+    /// Dense storage pays off for scenes that fill most of the domain
+    pub fn with_grid_backend(mut self, backend: GridBackend) -> Self {
+        self.grid_backend = backend;
+        self
+    }
+
+    /// Step the solver at a fixed rate instead of once per frame
+    pub fn with_fixed_timestep(mut self, timestep: Duration) -> Self {
+        self.timestep = Some(timestep);
+        self
+    }
+
+    /// Draw every random number from a generator seeded with `seed`, so runs
+    /// repeat exactly (see `config::determinism`)
+    pub fn with_deterministic(mut self, seed: u64) -> Self {
+        self.deterministic = Some(DeterministicConfig::new(seed));
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), MpmConfigError> {
+        let cell_width = self.grid.cell_width;
+        if !cell_width.is_finite() || cell_width <= 0.0 {
+            return Err(MpmConfigError::InvalidCellWidth(cell_width));
+        }
+        let resolution = self.grid.resolution;
+        if resolution == 0 || !resolution.is_multiple_of(8) {
+            return Err(MpmConfigError::InvalidResolution(resolution));
+        }
+        let scale = self.grid.scale;
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(MpmConfigError::InvalidScale(scale));
+        }
+        if !self.gravity.iter().all(|v| v.is_finite()) {
+            return Err(MpmConfigError::NonFiniteGravity);
+        }
+        for edge in self.boundary.edges() {
+            if let BoundaryHandling::Friction(friction) = edge
+                && (friction.is_nan() || friction < 0.0)
+            {
+                return Err(MpmConfigError::InvalidFriction(friction));
+            }
+        }
+        let restitution = self.boundary.restitution;
+        if !(0.0..=1.0).contains(&restitution) {
+            return Err(MpmConfigError::InvalidRestitution(restitution));
+        }
+        if self.timestep == Some(Duration::ZERO) {
+            return Err(MpmConfigError::ZeroTimestep);
+        }
+        Ok(())
+    }
+
+    /// The simulation's random number generator, seeded as configured.
+    pub fn build_rng(&self) -> SimRng {
+        self.deterministic
+            .map(|deterministic| deterministic.rng())
+            .unwrap_or_default()
+    }
+
+    /// Empty simulation state matching this configuration.
+    pub fn build_state(&self) -> MpmState {
+        let mut state = MpmState::new(self.solver_params.clone(), self.gravity);
+        *state.grid_mut() = Grid::from_config(&self.grid).with_backend(self.grid_backend);
+        state.set_boundary_mode(self.boundary);
+        state
+    }
+}
@@ -1,17 +1,178 @@
 use bevy::prelude::*;
 
+use crate::core::KernelKind;
+use crate::math::Real;
+
+/// Time integration scheme used when advecting particles after G2P.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum Integrator {
+    /// Grid velocities take an explicit force step and particles advect with
+    /// the freshly resampled velocity (the original update).
+    #[default]
+    ExplicitEuler,
+    /// Verlet in its drift-kick-drift form: particles drift half a step
+    /// before P2G, so forces are taken mid-step, and the other half with the
+    /// resampled velocity after G2P. Second order where `ExplicitEuler` is
+    /// first, so an oscillation keeps its energy far more closely, and
+    /// positions and velocities describe the same instant between steps.
+    VelocityVerlet,
+}
+
+/// How particle velocity fields are carried through the transfers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum TransferMode {
+    /// Particles scatter only their velocity, dropping the affine part, and
+    /// G2P hands them no affine field back (they keep the velocity gradient
+    /// for deformation): rotation and shear are smoothed away quickly, but
+    /// nothing can blow up. Handy for telling whether an instability comes
+    /// from the transfers.
+    Pic,
+    /// Particles also scatter their affine velocity field `C`, preserving
+    /// angular momentum (the original transfer).
+    #[default]
+    Apic,
+}
+
+/// How `Particle::is_static` particles take part in the transfers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum StaticParticleHandling {
+    /// Static particles are transferred like any other particle, so their
+    /// (zero) velocity is averaged into the nodes they share with fluid.
+    #[default]
+    Blend,
+    /// Static particles act as a one-way boundary: they add mass to the
+    /// density estimate but no momentum, and fluid velocity on the nodes they
+    /// touch is projected away from them.
+    Boundary,
+}
+
+/// What `MpmState::add_particle` does once `SolverParams::max_particles` is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum CapacityHandling {
+    /// New particles are refused.
+    #[default]
+    Reject,
+    /// The oldest particle (largest `Particle::age`) is overwritten in place
+    /// and listed in `MpmState::recycled_particles` until the frame ends.
+    RecycleOldest,
+}
+
 /// Solver parameters for controlling MPM simulation behavior
 #[derive(Resource, Clone)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct SolverParams {
     /// Enable volume preservation for incompressible materials (like water)
     /// When true, applies density correction to maintain volume conservation
     pub preserve_fluid_volume: bool,
 
     /// Strength of volume preservation correction (0.0 = disabled, 1.0 = strong)
-    pub volume_correction_strength: f32,
+    pub volume_correction_strength: Real,
 
     /// Dynamic viscosity for fluid materials
-    pub dynamic_viscosity: f32,
+    pub dynamic_viscosity: Real,
+
+    /// Surface-tension strength pulling free fluid surfaces smooth (0.0 = off)
+    pub surface_tension_coeff: Real,
+
+    /// Heat diffusivity between grid nodes, in cells squared per second
+    pub thermal_diffusivity: Real,
+
+    /// Upward acceleration per degree above `ambient_temperature`
+    pub buoyancy: Real,
+
+    /// Temperature at which material neither rises nor sinks
+    pub ambient_temperature: Real,
+
+    /// Share of each particle's colour replaced by the grid's every step,
+    /// blending dyes where they meet; `None` leaves colours off the grid
+    pub colour_diffusion: Option<Real>,
+
+    /// Strength of the vorticity confinement spinning swirls back up
+    /// (0.0 = off)
+    pub vorticity_confinement: Real,
+
+    /// Strength of the pull holding under-dense regions to their clump,
+    /// scaled per particle by `Particle::cohesion_energy` (0.0 = off)
+    pub cohesion_strength: Real,
+
+    /// Particle advection scheme
+    pub integrator: Integrator,
+
+    /// Velocity transfer scheme
+    pub transfer_mode: TransferMode,
+
+    /// B-spline spreading particles over the grid
+    pub kernel: KernelKind,
+
+    /// APIC `D^-1` used in place of the one `kernel` implies, for
+    /// experimenting with the affine scale (`None` = derived, see
+    /// `KernelKind::inv_d`)
+    pub affine_inv_d: Option<Real>,
+
+    /// Treatment of static (obstacle) particles
+    pub static_particles: StaticParticleHandling,
+
+    /// Upper bound on the particle count (`None` = unbounded)
+    pub max_particles: Option<usize>,
+
+    /// Behaviour of insertions once `max_particles` is reached
+    pub at_capacity: CapacityHandling,
+
+    /// Solver passes per tick, each advancing `1 / substeps` of the frame time
+    pub substeps: u32,
+
+    /// Share of FLIP in the G2P velocity (0.0 = pure APIC/PIC, damped and
+    /// stable; near 1.0 = lively, splashy FLIP)
+    pub flip_ratio: Real,
+
+    /// Pressure-projection sweeps making the fluid grid velocity
+    /// divergence-free each step (0 = off, leaving volume to the EOS)
+    pub projection_iterations: u32,
+
+    /// Largest change in gravity per second while it eases towards
+    /// `MpmState::set_gravity_target` (0.0 = jump straight there)
+    pub gravity_slew_rate: Real,
+
+    /// Fraction of grid velocity removed per second, a global energy sink
+    /// for calming energetic scenes (0.0 = off)
+    pub linear_damping: Real,
+
+    /// Range solid deformation-gradient singular values are clamped into
+    /// after every G2P, keeping landing solids well conditioned (`None` = off)
+    pub singular_value_range: Option<(Real, Real)>,
+
+    /// Deformation-gradient condition number beyond which a particle is
+    /// failed and removed
+    pub condition_threshold: Real,
+
+    /// Speed (and velocity-gradient norm) below which a particle counts as
+    /// idle; idle for `sleep_steps` steps it goes to sleep (0.0 = never)
+    pub sleep_threshold: Real,
+
+    /// Consecutive idle steps before a particle sleeps
+    pub sleep_steps: u32,
+
+    /// Grid speed on any stencil node that wakes a sleeping particle
+    pub wake_threshold: Real,
 }
 
 impl Default for SolverParams {
@@ -20,6 +181,30 @@ impl Default for SolverParams {
             preserve_fluid_volume: false, // EOS handles volume naturally
             volume_correction_strength: 0.0,
             dynamic_viscosity: 0.001,
+            surface_tension_coeff: 0.0,
+            thermal_diffusivity: 0.0,
+            buoyancy: 0.0,
+            ambient_temperature: 0.0,
+            colour_diffusion: None,
+            vorticity_confinement: 0.0,
+            cohesion_strength: 0.0,
+            integrator: Integrator::ExplicitEuler,
+            transfer_mode: TransferMode::Apic,
+            kernel: KernelKind::Quadratic,
+            affine_inv_d: None,
+            static_particles: StaticParticleHandling::Blend,
+            max_particles: None,
+            at_capacity: CapacityHandling::Reject,
+            substeps: 1,
+            flip_ratio: 0.0,
+            projection_iterations: 0,
+            gravity_slew_rate: 0.0,
+            linear_damping: 0.0,
+            singular_value_range: None,
+            condition_threshold: 1.0e6,
+            sleep_threshold: 0.0,
+            sleep_steps: 30,
+            wake_threshold: 1.0,
         }
     }
 }
@@ -30,7 +215,7 @@ impl SolverParams {
         Self {
             preserve_fluid_volume: true,
             volume_correction_strength: 0.5,
-            dynamic_viscosity: 0.001,
+            ..Self::default()
         }
     }
 
@@ -39,13 +224,153 @@ impl SolverParams {
         Self {
             preserve_fluid_volume: false,
             volume_correction_strength: 0.0,
-            dynamic_viscosity: 0.001,
+            ..Self::default()
         }
     }
 
     /// Set volume preservation strength (0.0 to 1.0)
-    pub fn with_correction_strength(mut self, strength: f32) -> Self {
+    pub fn with_correction_strength(mut self, strength: Real) -> Self {
         self.volume_correction_strength = strength.clamp(0.0, 1.0);
         self
     }
+
+    /// Select how static particles interact with the rest of the scene
+    pub fn with_static_particles(mut self, handling: StaticParticleHandling) -> Self {
+        self.static_particles = handling;
+        self
+    }
+
+    /// Cap the particle count, choosing what happens to insertions beyond it
+    pub fn with_max_particles(mut self, max: usize, at_capacity: CapacityHandling) -> Self {
+        self.max_particles = Some(max);
+        self.at_capacity = at_capacity;
+        self
+    }
+
+    /// Set the surface-tension coefficient
+    pub fn with_surface_tension(mut self, coefficient: Real) -> Self {
+        self.surface_tension_coeff = coefficient.max(0.0);
+        self
+    }
+
+    /// Set the heat diffusivity
+    pub fn with_thermal_diffusivity(mut self, diffusivity: Real) -> Self {
+        self.thermal_diffusivity = diffusivity.max(0.0);
+        self
+    }
+
+    /// Let temperatures away from `ambient` push material up or down
+    pub fn with_buoyancy(mut self, buoyancy: Real, ambient: Real) -> Self {
+        self.buoyancy = buoyancy;
+        self.ambient_temperature = ambient;
+        self
+    }
+
+    /// Whether particle temperatures go through the grid at all
+    pub fn transfers_heat(&self) -> bool {
+        self.thermal_diffusivity > 0.0 || self.buoyancy != 0.0
+    }
+
+    /// Carry particle colours through the grid, blending `diffusion` of the
+    /// grid's colour into each particle per step (0.0 = advect only)
+    pub fn with_colour_diffusion(mut self, diffusion: Real) -> Self {
+        self.colour_diffusion = Some(diffusion.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Spin swirls back up with vorticity confinement of strength `epsilon`
+    /// (0.0 = off)
+    pub fn with_vorticity_confinement(mut self, epsilon: Real) -> Self {
+        self.vorticity_confinement = epsilon.max(0.0);
+        self
+    }
+
+    /// Set the cohesion strength
+    pub fn with_cohesion(mut self, strength: Real) -> Self {
+        self.cohesion_strength = strength.max(0.0);
+        self
+    }
+
+    /// Select the particle advection scheme
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Select the velocity transfer scheme
+    pub fn with_transfer_mode(mut self, transfer_mode: TransferMode) -> Self {
+        self.transfer_mode = transfer_mode;
+        self
+    }
+
+    /// Select the transfer kernel
+    pub fn with_kernel(mut self, kernel: KernelKind) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// Override the APIC `D^-1` the kernel implies
+    pub fn with_affine_inv_d(mut self, inv_d: Real) -> Self {
+        self.affine_inv_d = Some(inv_d);
+        self
+    }
+
+    /// APIC `D^-1` for node distances measured in units where a cell is
+    /// `cell_width` across: the override if set, else the kernel's.
+    pub fn inv_d(&self, cell_width: Real) -> Real {
+        self.affine_inv_d
+            .unwrap_or_else(|| self.kernel.inv_d(cell_width))
+    }
+
+    /// Blend FLIP into the G2P velocity (0.0 to 1.0)
+    pub fn with_flip_ratio(mut self, ratio: Real) -> Self {
+        self.flip_ratio = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Project fluid grid velocities divergence-free with this many sweeps
+    pub fn with_projection_iterations(mut self, iterations: u32) -> Self {
+        self.projection_iterations = iterations;
+        self
+    }
+
+    /// Limit how fast gravity eases towards its target, per second
+    /// (0.0 = jump straight there)
+    pub fn with_gravity_slew_rate(mut self, rate: Real) -> Self {
+        self.gravity_slew_rate = rate.max(0.0);
+        self
+    }
+
+    /// Damp grid velocities by this fraction per second
+    pub fn with_linear_damping(mut self, damping: Real) -> Self {
+        self.linear_damping = damping.max(0.0);
+        self
+    }
+
+    /// Clamp solid deformation-gradient singular values into `[min, max]`
+    pub fn with_singular_value_clamp(mut self, min: Real, max: Real) -> Self {
+        self.singular_value_range = Some((min, max));
+        self
+    }
+
+    /// Fail particles whose deformation condition number exceeds `threshold`
+    pub fn with_condition_threshold(mut self, threshold: Real) -> Self {
+        self.condition_threshold = threshold;
+        self
+    }
+
+    /// Let particles idle below `threshold` for `steps` steps sleep until the
+    /// grid around them moves faster than `wake_threshold`
+    pub fn with_sleeping(mut self, threshold: Real, steps: u32, wake_threshold: Real) -> Self {
+        self.sleep_threshold = threshold.max(0.0);
+        self.sleep_steps = steps;
+        self.wake_threshold = wake_threshold;
+        self
+    }
+
+    /// Split every tick into this many solver passes (at least one)
+    pub fn with_substeps(mut self, substeps: u32) -> Self {
+        self.substeps = substeps.max(1);
+        self
+    }
 }
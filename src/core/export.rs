@@ -0,0 +1,260 @@
+//! Plain-text particle dumps (CSV, VTK) for analysis outside the simulation.
+
+use std::io::{self, Write};
+
+use crate::materials::{MaterialModel, utils};
+use crate::math::Real;
+
+use super::mpm_state::MpmState;
+use super::particle::Particle;
+
+const CSV_HEADER: &str =
+    "position_x,position_y,velocity_x,velocity_y,mass,jacobian,condition_number,material_name";
+
+/// VTK type of the float arrays, matching `Real` so nothing is written at
+/// more precision than the file claims.
+#[cfg(not(feature = "f64"))]
+const VTU_FLOAT: &str = "Float32";
+#[cfg(feature = "f64")]
+const VTU_FLOAT: &str = "Float64";
+
+impl MpmState {
+    /// Writes one CSV row per particle, after a header naming the columns:
+    /// position, velocity, mass, `det(F)`, condition number and material
+    /// name. Rows are written straight to `writer`; wrap files in a
+    /// `BufWriter`.
+    pub fn export_particles_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{CSV_HEADER}")?;
+        for particle in self.particles() {
+            write!(
+                writer,
+                "{},{},{},{},{},{},{},",
+                particle.position.x,
+                particle.position.y,
+                particle.velocity.x,
+                particle.velocity.y,
+                particle.mass,
+                particle.jacobian(),
+                particle.condition_number,
+            )?;
+            write_csv_text(&mut writer, particle.material_type.material_name())?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the particles as a VTK unstructured grid (`.vtu`) of vertex
+    /// cells, for ParaView and friends. Points get `z = 0`; each carries its
+    /// speed, pressure and `det(F)`. Pressure is `utils::pressure` of the
+    /// material stress at the density the particle's deformation implies.
+    pub fn export_vtu<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let particles = self.particles();
+        let count = particles.len();
+        writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+        writeln!(
+            writer,
+            r#"<VTKFile type="UnstructuredGrid" version="0.1" byte_order="LittleEndian">"#
+        )?;
+        writeln!(writer, "  <UnstructuredGrid>")?;
+        writeln!(
+            writer,
+            r#"    <Piece NumberOfPoints="{count}" NumberOfCells="{count}">"#
+        )?;
+
+        writeln!(writer, r#"      <PointData Scalars="pressure">"#)?;
+        let speed = |particle: &Particle| particle.velocity.norm();
+        let pressure = |particle: &Particle| {
+            let density = particle.material_rest_density() / particle.jacobian().abs();
+            let params = self.solver_params_for(&particle.material_type);
+            let stress = particle
+                .material_type
+                .compute_stress(particle, density, params);
+            utils::pressure(stress)
+        };
+        write_vtu_scalars(&mut writer, "velocity_magnitude", particles, speed)?;
+        write_vtu_scalars(&mut writer, "pressure", particles, pressure)?;
+        write_vtu_scalars(&mut writer, "jacobian", particles, Particle::jacobian)?;
+        writeln!(writer, "      </PointData>")?;
+
+        writeln!(writer, "      <Points>")?;
+        writeln!(
+            writer,
+            r#"        <DataArray type="{VTU_FLOAT}" NumberOfComponents="3" format="ascii">"#
+        )?;
+        for particle in particles {
+            writeln!(
+                writer,
+                "          {} {} 0",
+                particle.position.x, particle.position.y
+            )?;
+        }
+        writeln!(writer, "        </DataArray>")?;
+        writeln!(writer, "      </Points>")?;
+
+        // One VTK_VERTEX (type 1) cell per point
+        writeln!(writer, "      <Cells>")?;
+        write_vtu_integers(&mut writer, "connectivity", "Int64", 0..count)?;
+        write_vtu_integers(&mut writer, "offsets", "Int64", 1..=count)?;
+        write_vtu_integers(&mut writer, "types", "UInt8", std::iter::repeat_n(1, count))?;
+        writeln!(writer, "      </Cells>")?;
+
+        writeln!(writer, "    </Piece>")?;
+        writeln!(writer, "  </UnstructuredGrid>")?;
+        writeln!(writer, "</VTKFile>")
+    }
+}
+
+fn write_vtu_scalars<W: Write>(
+    writer: &mut W,
+    name: &str,
+    particles: &[Particle],
+    value: impl Fn(&Particle) -> Real,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        r#"        <DataArray type="{VTU_FLOAT}" Name="{name}" format="ascii">"#
+    )?;
+    for particle in particles {
+        writeln!(writer, "          {}", value(particle))?;
+    }
+    writeln!(writer, "        </DataArray>")
+}
+
+fn write_vtu_integers<W: Write>(
+    writer: &mut W,
+    name: &str,
+    kind: &str,
+    values: impl Iterator<Item = usize>,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        r#"        <DataArray type="{kind}" Name="{name}" format="ascii">"#
+    )?;
+    for value in values {
+        writeln!(writer, "          {value}")?;
+    }
+    writeln!(writer, "        </DataArray>")
+}
+
+/// Writes `text` as a CSV field, quoted only when it needs to be.
+fn write_csv_text<W: Write>(writer: &mut W, text: &str) -> io::Result<()> {
+    if !text.contains([',', '"', '\n', '\r']) {
+        return writer.write_all(text.as_bytes());
+    }
+    write!(writer, "\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::core::Particle;
+    use crate::materials::{ElasticParams, MaterialType};
+    use crate::math::{Real, Vector};
+
+    #[test]
+    fn csv_has_a_header_and_one_row_per_particle() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        let rubber = MaterialType::elastic(ElasticParams::new("rubber, soft", 50.0, 0.3));
+        for i in 0..25 {
+            let material = if i % 5 == 0 {
+                rubber.clone()
+            } else {
+                MaterialType::water()
+            };
+            let position = Vector::new(20.0 + i as Real, 30.5);
+            state.add_particle(Particle::new(position, material));
+        }
+
+        let mut csv = Vec::new();
+        state.export_particles_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), state.particle_count());
+
+        for (row, particle) in rows.iter().zip(state.particles()) {
+            // The material name is the last column and the only quoted one
+            let (numbers, name) = row.split_at(row.find(",water").or(row.find(",\"")).unwrap());
+            let values: Vec<Real> = numbers.split(',').map(|v| v.parse().unwrap()).collect();
+            assert_eq!(values.len(), 7);
+            assert_eq!(values[0], particle.position.x);
+            assert_eq!(values[5], particle.jacobian());
+            let expected = match particle.material_type.material_name() {
+                "water" => ",water",
+                _ => ",\"rubber, soft\"",
+            };
+            assert_eq!(name, expected);
+        }
+    }
+
+    /// Checks that every tag is closed in order and returns the tag names
+    /// seen, opening tags only.
+    fn well_formed_tags(xml: &str) -> Vec<String> {
+        let mut open = Vec::new();
+        let mut seen = Vec::new();
+        for tag in xml
+            .split('<')
+            .skip(1)
+            .map(|rest| &rest[..rest.find('>').unwrap()])
+        {
+            if tag.starts_with('?') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop().as_deref(), Some(name), "unbalanced </{name}>");
+                continue;
+            }
+            let name = tag.split_whitespace().next().unwrap().trim_end_matches('/');
+            seen.push(name.to_string());
+            if !tag.ends_with('/') {
+                open.push(name.to_string());
+            }
+        }
+        assert!(open.is_empty(), "unclosed {open:?}");
+        seen
+    }
+
+    #[test]
+    fn vtu_is_well_formed_with_one_point_per_particle() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for i in 0..40 {
+            let position = Vector::new(10.0 + (i % 8) as Real, 12.0 + (i / 8) as Real);
+            let particle = Particle::new(position, MaterialType::water());
+            state.add_particle(particle.with_velocity(Vector::new(3.0, -4.0)));
+        }
+
+        let mut vtu = Vec::new();
+        state.export_vtu(&mut vtu).unwrap();
+        let vtu = String::from_utf8(vtu).unwrap();
+        let tags = well_formed_tags(&vtu);
+        assert_eq!(tags.iter().filter(|tag| *tag == "DataArray").count(), 7);
+        assert!(vtu.contains(r#"NumberOfPoints="40""#));
+        let float_arrays = format!(r#"type="{VTU_FLOAT}""#);
+        assert_eq!(vtu.matches(&float_arrays).count(), 4);
+
+        let points = vtu
+            .split("<Points>")
+            .nth(1)
+            .unwrap()
+            .split("</Points>")
+            .next()
+            .unwrap();
+        let body = &points[points.find('>').unwrap() + 1..points.find("</DataArray>").unwrap()];
+        let point_rows: Vec<&str> = body
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        assert_eq!(point_rows.len(), state.particle_count());
+        assert!(
+            point_rows
+                .iter()
+                .all(|row| row.split_whitespace().count() == 3)
+        );
+        assert!(
+            vtu.contains("\n          5\n"),
+            "speeds are written per point"
+        );
+    }
+}
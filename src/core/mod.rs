@@ -1,19 +1,32 @@
+mod export;
 pub mod grid;
 pub mod kernel;
 pub mod mpm_state;
 pub mod particle;
 pub mod particle_set;
+pub mod rewind;
+#[cfg(feature = "serde-serialize")]
+pub mod snapshot;
 
 pub use grid::{
-    BoundaryHandling, GRID_RESOLUTION, Grid, GridInterpolation, GridNode, KERNEL_SIZE,
-    NEIGHBOR_COUNT, apply_boundary_conditions,
+    BoundaryConfig, BoundaryHandling, GRID_RESOLUTION, Grid, GridBackend, GridInterpolation,
+    GridNode, KERNEL_SIZE, MAX_KERNEL_SIZE, MAX_NEIGHBOR_COUNT, NEIGHBOR_COUNT, add_rebound,
+    apply_boundary_conditions, project_friction, project_slip, project_slip_moving, project_stick,
+    wrap_grid_coord, wrap_grid_coord_on,
+};
+pub use kernel::{
+    KernelKind, cell_colour, cell_from_position, inv_d, node_center, populate_transfer_cache,
 };
-pub use kernel::{cell_colour, cell_from_position, inv_d, populate_transfer_cache};
 pub use mpm_state::{
-    MpmState, ParticleRemap, cleanup_grid_cells, clear_particle_remap_system,
-    remove_failed_particles_system, zero_grid,
+    BatchInsertion, MpmState, ParticleFailed, ParticleRemap, StressParams, cleanup_grid_cells,
+    clear_particle_remap_system, remove_failed_particles_system, zero_grid,
 };
 pub use particle::{
-    Particle, ParticleContact, ParticleFracture, ParticlePlasticityState, update_particles_health,
+    FailureReason, PARALLEL_HEALTH_MIN_PARTICLES, Particle, ParticleBuilder, ParticleContact,
+    ParticleFracture, ParticlePlasticityState, update_particles_health,
+    update_particles_health_serial,
 };
 pub use particle_set::{PackedCell, ParticleBin, ParticleSet, ParticleTransferCache};
+pub use rewind::{RewindBuffer, RewindFrame, record_rewind};
+#[cfg(feature = "serde-serialize")]
+pub use snapshot::SnapshotError;
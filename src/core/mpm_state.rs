@@ -1,27 +1,114 @@
+use std::collections::HashMap;
 use std::ops::Range;
 
 use bevy::prelude::*;
 
-use crate::config::SolverParams;
-use crate::math::{Real, Vector};
+use crate::config::{CapacityHandling, SolverParams, StaticParticleHandling};
+use crate::geometry::sp_grid::unpack_to_ivec;
+use crate::materials::MaterialType;
+use crate::math::{Real, Vector, from_bevy_vec2, to_bevy_scalar, to_bevy_vec2, zero_vector};
+use crate::solver::P2gScratch;
 
-use super::grid::{BoundaryHandling, Grid, apply_boundary_conditions};
-use super::particle::Particle;
+use super::grid::{
+    BoundaryConfig, Grid, GridInterpolation, GridNode, apply_boundary_conditions,
+    wrap_grid_coord_on,
+};
+use super::particle::{FailureReason, Particle};
 use super::particle_set::{PackedCell, ParticleBin, ParticleSet, ParticleTransferCache};
 
+/// Where each particle index from the start of the frame ended up after this
+/// frame's removals (`None` = removed). Empty when nothing was removed.
 #[derive(Resource, Default)]
 pub struct ParticleRemap {
     pub map: Vec<Option<usize>>,
 }
 
+impl ParticleRemap {
+    /// Folds the `mapping` of one more removal pass into the frame's remap.
+    ///
+    /// `mapping` is indexed by the particle order just before that pass, so
+    /// several removal systems can run in one frame and `map` still goes
+    /// from the original indices to the final ones.
+    pub fn compose(&mut self, mapping: Vec<Option<usize>>) {
+        if mapping.is_empty() {
+            return;
+        }
+        if self.map.is_empty() {
+            self.map = mapping;
+            return;
+        }
+        for entry in self.map.iter_mut() {
+            *entry = entry.and_then(|index| mapping[index]);
+        }
+    }
+}
+
+/// Sent by `remove_failed_particles_system` for each particle it removes.
+/// `index` is the particle's index before the removal.
+#[derive(Message, Clone, Copy, Debug, PartialEq)]
+pub struct ParticleFailed {
+    pub index: usize,
+    pub position: Vector,
+    pub reason: FailureReason,
+}
+
+/// Indices `MpmState::add_particles` gave a batch, in batch order: the
+/// appended block first, then the particles it recycled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchInsertion {
+    pub appended: Range<usize>,
+    /// Old particles overwritten past the cap, oldest first.
+    pub recycled: Vec<usize>,
+}
+
+impl BatchInsertion {
+    /// How many particles of the batch were placed.
+    pub fn len(&self) -> usize {
+        self.appended.len() + self.recycled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.appended.clone().chain(self.recycled.iter().copied())
+    }
+}
+
+/// The solver parameters and the per-material ones that override them for
+/// stress, borrowed from `MpmState`.
+#[derive(Clone, Copy)]
+pub struct StressParams<'a> {
+    pub solver: &'a SolverParams,
+    pub materials: &'a HashMap<&'static str, SolverParams>,
+}
+
+impl<'a> StressParams<'a> {
+    /// Parameters stress is computed with for `material`.
+    pub fn for_material(&self, material: &MaterialType) -> &'a SolverParams {
+        self.materials
+            .get(material.material_name())
+            .unwrap_or(self.solver)
+    }
+}
+
 /// Aggregate simulation state for the solver.
 #[derive(Resource)]
 pub struct MpmState {
     particle_set: ParticleSet,
     grid: Grid,
     solver_params: SolverParams,
+    /// Stress parameters for particular materials, by material name.
+    material_params: HashMap<&'static str, SolverParams>,
     gravity: Vector,
-    boundary: BoundaryHandling,
+    /// Where `gravity` is easing towards, until it gets there.
+    gravity_target: Option<Vector>,
+    boundary: BoundaryConfig,
+    /// Buffers P2G reuses from one substep to the next.
+    p2g_scratch: P2gScratch,
+    /// Indices overwritten past the cap since `clear_recycled_particles`.
+    recycled: Vec<usize>,
 }
 
 impl MpmState {
@@ -30,11 +117,19 @@ impl MpmState {
             particle_set: ParticleSet::new(),
             grid: Grid::new(),
             solver_params,
+            material_params: HashMap::new(),
             gravity,
-            boundary: BoundaryHandling::Slip,
+            gravity_target: None,
+            boundary: BoundaryConfig::default(),
+            p2g_scratch: P2gScratch::default(),
+            recycled: Vec::new(),
         }
     }
 
+    pub(crate) fn p2g_scratch_mut(&mut self) -> &mut P2gScratch {
+        &mut self.p2g_scratch
+    }
+
     pub fn particle_set(&self) -> &ParticleSet {
         &self.particle_set
     }
@@ -67,6 +162,34 @@ impl MpmState {
         self.particle_set.particles_mut_and_cache()
     }
 
+    /// The particles to write, next to the parameters to compute their
+    /// stress with.
+    pub fn particles_mut_and_params(&mut self) -> (&mut [Particle], StressParams<'_>) {
+        let params = StressParams {
+            solver: &self.solver_params,
+            materials: &self.material_params,
+        };
+        (self.particle_set.particles_mut(), params)
+    }
+
+    /// `grid_mut_and_particles_cache` plus the parameters to compute stress
+    /// with.
+    pub fn grid_mut_particles_cache_and_params(
+        &mut self,
+    ) -> (
+        &mut Grid,
+        &[Particle],
+        &[ParticleTransferCache],
+        StressParams<'_>,
+    ) {
+        let (particles, cache) = self.particle_set.particles_and_cache();
+        let params = StressParams {
+            solver: &self.solver_params,
+            materials: &self.material_params,
+        };
+        (&mut self.grid, particles, cache, params)
+    }
+
     pub fn grid_mut_and_particles_cache(
         &mut self,
     ) -> (&mut Grid, &[Particle], &[ParticleTransferCache]) {
@@ -89,12 +212,28 @@ impl MpmState {
         }
     }
 
+    /// Read-only grid next to the mutable particles. The grid is a separate
+    /// field, so this is a plain split borrow and stays sound when G2P reads
+    /// it from many threads at once.
     pub fn grid_and_particles_mut_cache(
         &mut self,
     ) -> (&Grid, &mut [Particle], &[ParticleTransferCache]) {
-        let grid_ptr = &self.grid as *const Grid;
         let (particles, cache) = self.particle_set.particles_mut_and_cache();
-        unsafe { (&*grid_ptr, particles, cache) }
+        (&self.grid, particles, cache)
+    }
+
+    /// `grid_and_particles_mut_cache` plus the velocity buffers to read and
+    /// write, when the particle set is double-buffered.
+    pub fn grid_and_particles_mut_velocities(
+        &mut self,
+    ) -> (
+        &Grid,
+        &mut [Particle],
+        &[ParticleTransferCache],
+        Option<(&[Vector], &mut [Vector])>,
+    ) {
+        let (particles, cache, velocities) = self.particle_set.particles_mut_cache_and_velocities();
+        (&self.grid, particles, cache, velocities)
     }
 
     pub fn particle_count(&self) -> usize {
@@ -105,17 +244,134 @@ impl MpmState {
         self.particle_set.particles()
     }
 
+    /// Summed mass of every particle, including failed ones not yet removed.
+    pub fn total_particle_mass(&self) -> Real {
+        self.particles().iter().map(|particle| particle.mass).sum()
+    }
+
     pub fn particles_mut(&mut self) -> &mut [Particle] {
         self.particle_set.particles_mut()
     }
 
-    pub fn add_particle(&mut self, particle: Particle) -> usize {
-        self.particle_set.push(particle)
+    /// Grid cell the particle at `index` was binned into.
+    ///
+    /// Only meaningful after `rebuild_particle_bins`; returns `None` for
+    /// unknown indices and for failed or out-of-domain particles.
+    pub fn particle_cell(&self, index: usize) -> Option<IVec2> {
+        let particle = self.particle_set.get(index)?;
+        if particle.failed || particle.grid_index == u64::MAX {
+            return None;
+        }
+        Some(unpack_to_ivec(particle.grid_index))
+    }
+
+    /// Inserts `particle` and returns its index.
+    ///
+    /// Once `SolverParams::max_particles` is reached the insertion is refused
+    /// (`None`), or with `CapacityHandling::RecycleOldest` the oldest particle
+    /// is overwritten and its index returned.
+    pub fn add_particle(&mut self, particle: Particle) -> Option<usize> {
+        let Some(max) = self.solver_params.max_particles else {
+            return Some(self.particle_set.push(particle));
+        };
+        if self.particle_set.len() < max {
+            return Some(self.particle_set.push(particle));
+        }
+
+        match self.solver_params.at_capacity {
+            CapacityHandling::Reject => None,
+            CapacityHandling::RecycleOldest => {
+                let oldest = self.particle_set.recycle_oldest(particle)?;
+                self.recycled.push(oldest);
+                Some(oldest)
+            }
+        }
+    }
+
+    /// Indices that now hold a different particle because the cap recycled
+    /// them, since `clear_particle_remap_system` last ran. They follow this
+    /// frame's removals, so they are current indices.
+    pub fn recycled_particles(&self) -> &[usize] {
+        &self.recycled
     }
 
+    pub fn clear_recycled_particles(&mut self) {
+        self.recycled.clear();
+    }
+
+    /// Points `recycled` at where a removal pass moved its particles.
+    fn remap_recycled(&mut self, mapping: &[Option<usize>]) {
+        self.recycled.retain_mut(|index| match mapping[*index] {
+            Some(new_index) => {
+                *index = new_index;
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Whether `SolverParams::max_particles` is reached, so the next
+    /// insertion goes through `SolverParams::at_capacity`.
+    pub fn at_capacity(&self) -> bool {
+        self.solver_params
+            .max_particles
+            .is_some_and(|max| self.particle_set.len() >= max)
+    }
+
+    /// Inserts `particles` and returns the indices they were given.
+    ///
+    /// What fits under `SolverParams::max_particles` is appended as one
+    /// contiguous block. The rest follows `SolverParams::at_capacity` like
+    /// `add_particle` does: with `CapacityHandling::Reject` it is dropped,
+    /// with `CapacityHandling::RecycleOldest` it overwrites the oldest
+    /// particles that were there before the batch, and anything past those
+    /// is dropped.
+    pub fn add_particles(&mut self, mut particles: Vec<Particle>) -> BatchInsertion {
+        let Some(max) = self.solver_params.max_particles else {
+            let count = particles.len();
+            let start = self.particle_set.insert_batch(particles);
+            return BatchInsertion {
+                appended: start..start + count,
+                recycled: Vec::new(),
+            };
+        };
+
+        let room = max.saturating_sub(self.particle_set.len());
+        let overflow = particles.split_off(particles.len().min(room));
+        let recycled: Vec<usize> = match self.solver_params.at_capacity {
+            CapacityHandling::Reject => Vec::new(),
+            // Before appending, so only particles from before the batch go
+            CapacityHandling::RecycleOldest => overflow
+                .into_iter()
+                .take(self.particle_set.len())
+                .filter_map(|particle| self.particle_set.recycle_oldest(particle))
+                .collect(),
+        };
+        self.recycled.extend_from_slice(&recycled);
+        let count = particles.len();
+        let start = self.particle_set.insert_batch(particles);
+        BatchInsertion {
+            appended: start..start + count,
+            recycled,
+        }
+    }
+
+    /// Re-bins particles by their current cell. P2G calls this at the start
+    /// of every substep, since particles move between substeps.
     pub fn rebuild_particle_bins(&mut self) {
         let cell_width = self.grid.cell_width();
-        self.particle_set.rebuild_bins(cell_width);
+        let periodic = self.boundary.periodic_axes();
+        let resolution = self.grid.resolution();
+        let kernel = self.solver_params.kernel;
+        self.particle_set
+            .rebuild_bins(cell_width, periodic, resolution, kernel);
+    }
+
+    /// The APIC `D^-1` P2G, G2P and the constraint iterations share, in the
+    /// units of the transfer cache's node distances: simulation units, where
+    /// a cell is `cell_width` across.
+    pub fn transfer_inv_d(&self) -> Real {
+        self.solver_params.inv_d(self.grid.cell_width())
     }
 
     pub fn grid(&self) -> &Grid {
@@ -126,6 +382,75 @@ impl MpmState {
         &mut self.grid
     }
 
+    /// Simulation-space position of a world-space point, per the grid's
+    /// origin and scale.
+    pub fn world_to_sim(&self, world: Vec2) -> Vector {
+        (from_bevy_vec2(world) - self.grid.origin()) / self.grid.scale()
+    }
+
+    /// World-space position of a simulation-space point.
+    pub fn sim_to_world(&self, sim: Vector) -> Vec2 {
+        to_bevy_vec2(&(self.grid.origin() + sim * self.grid.scale()))
+    }
+
+    /// Grid velocity at `position`, interpolated with the solver's kernel
+    /// and weighted by node mass. Zero where no node nearby holds mass.
+    pub fn sample_velocity(&self, position: Vector) -> Vector {
+        let mut mass = 0.0;
+        let mut momentum = zero_vector();
+        for (node, weight) in self.sample_nodes(position) {
+            mass += node.mass * weight;
+            momentum += node.velocity * (node.mass * weight);
+        }
+        if mass > 0.0 {
+            momentum / mass
+        } else {
+            zero_vector()
+        }
+    }
+
+    /// Grid mass per cell at `position`, accumulated the same way P2G
+    /// measures a particle's density. Zero where no mass exists.
+    pub fn sample_density(&self, position: Vector) -> Real {
+        self.sample_nodes(position)
+            .map(|(node, weight)| node.mass * weight)
+            .sum()
+    }
+
+    /// Blended dye colour at `position`, weighted by node mass like
+    /// `sample_velocity`. `None` where no node nearby holds mass, or when
+    /// `SolverParams::colour_diffusion` leaves colours off the grid.
+    pub fn sample_colour(&self, position: Vector) -> Option<Color> {
+        self.solver_params.colour_diffusion?;
+        let mut mass = 0.0;
+        let mut colour = [0.0; 4];
+        for (node, weight) in self.sample_nodes(position) {
+            mass += node.mass * weight;
+            for (channel, value) in colour.iter_mut().zip(node.colour) {
+                *channel += value * (node.mass * weight);
+            }
+        }
+        if mass <= 0.0 {
+            return None;
+        }
+        let [red, green, blue, alpha] = colour.map(|channel| to_bevy_scalar(channel / mass));
+        Some(Color::linear_rgba(red, green, blue, alpha))
+    }
+
+    /// Active nodes in the kernel stencil around `position`, with weights.
+    fn sample_nodes(&self, position: Vector) -> impl Iterator<Item = (&GridNode, Real)> + '_ {
+        let kernel = self.solver_params.kernel;
+        let interpolation =
+            GridInterpolation::compute_for_particle_with(position, kernel, self.grid.cell_width());
+        let periodic = self.boundary.periodic_axes();
+        let resolution = self.grid.resolution();
+        (0..interpolation.len).filter_map(move |idx| {
+            let coord = wrap_grid_coord_on(interpolation.neighbor_coord(idx), periodic, resolution);
+            let node = self.grid.get_cell_coord(coord)?;
+            Some((node, interpolation.weight_for_neighbor(idx)))
+        })
+    }
+
     pub fn solver_params(&self) -> &SolverParams {
         &self.solver_params
     }
@@ -134,20 +459,89 @@ impl MpmState {
         &mut self.solver_params
     }
 
+    /// Computes stress for every particle of `material` (matched by name)
+    /// with `params` in place of the global `SolverParams`, so two fluids can
+    /// differ in viscosity or volume preservation. Only what the material
+    /// models read is taken from `params`; the solver itself (transfer mode,
+    /// kernel, substeps and so on) stays global.
+    pub fn set_material_params(&mut self, material: &MaterialType, params: SolverParams) {
+        self.material_params
+            .insert(material.material_name(), params);
+    }
+
+    /// Puts `material` back on the global `SolverParams`.
+    pub fn clear_material_params(&mut self, material: &MaterialType) {
+        self.material_params.remove(material.material_name());
+    }
+
+    pub fn material_params(&self) -> &HashMap<&'static str, SolverParams> {
+        &self.material_params
+    }
+
+    /// Parameters stress is computed with for `material`.
+    pub fn solver_params_for(&self, material: &MaterialType) -> &SolverParams {
+        self.stress_params().for_material(material)
+    }
+
+    pub fn stress_params(&self) -> StressParams<'_> {
+        StressParams {
+            solver: &self.solver_params,
+            materials: &self.material_params,
+        }
+    }
+
     pub fn gravity(&self) -> Vector {
         self.gravity
     }
 
+    /// Jumps straight to `gravity`, dropping any target still being eased
+    /// towards.
     pub fn set_gravity(&mut self, gravity: Vector) {
         self.gravity = gravity;
+        self.gravity_target = None;
     }
 
-    pub fn boundary_mode(&self) -> BoundaryHandling {
+    /// Eases gravity towards `target` at `SolverParams::gravity_slew_rate`,
+    /// one step of `ease_gravity` at a time, so tilting the world doesn't
+    /// jolt the material.
+    pub fn set_gravity_target(&mut self, target: Vector) {
+        self.gravity_target = Some(target);
+    }
+
+    /// Eases towards gravity of `magnitude` pointing `radians`
+    /// counter-clockwise from the +x axis (`-PI / 2` is straight down).
+    pub fn set_gravity_from_angle(&mut self, radians: Real, magnitude: Real) {
+        let (sin, cos) = radians.sin_cos();
+        self.set_gravity_target(Vector::new(cos, sin) * magnitude);
+    }
+
+    pub fn gravity_target(&self) -> Option<Vector> {
+        self.gravity_target
+    }
+
+    /// Moves gravity up to `gravity_slew_rate * dt` towards its target,
+    /// stopping exactly on it.
+    pub fn ease_gravity(&mut self, dt: Real) {
+        let Some(target) = self.gravity_target else {
+            return;
+        };
+        let remaining = target - self.gravity;
+        let max_step = self.solver_params.gravity_slew_rate * dt;
+        if max_step <= 0.0 || remaining.norm() <= max_step {
+            self.gravity = target;
+            self.gravity_target = None;
+        } else {
+            self.gravity += remaining.normalize() * max_step;
+        }
+    }
+
+    pub fn boundary_mode(&self) -> BoundaryConfig {
         self.boundary
     }
 
-    pub fn set_boundary_mode(&mut self, boundary: BoundaryHandling) {
-        self.boundary = boundary;
+    /// Sets the per-edge boundary, or one `BoundaryHandling` for all edges.
+    pub fn set_boundary_mode(&mut self, boundary: impl Into<BoundaryConfig>) {
+        self.boundary = boundary.into();
     }
 
     pub fn zero_grid(&mut self) {
@@ -160,15 +554,104 @@ impl MpmState {
 
     pub fn integrate_grid_velocities(&mut self, dt: Real) {
         let gravity_step = self.gravity * dt;
+        let damping = (1.0 - self.solver_params.linear_damping * dt).max(0.0);
+        let static_boundary =
+            self.solver_params.static_particles == StaticParticleHandling::Boundary;
+        if self.solver_params.surface_tension_coeff > 0.0 {
+            self.grid
+                .apply_surface_tension(self.solver_params.surface_tension_coeff, dt);
+        }
+        if self.solver_params.thermal_diffusivity > 0.0 {
+            self.grid
+                .diffuse_temperature(self.solver_params.thermal_diffusivity, dt);
+        }
+        // Buoyancy acts against gravity, or up the y axis without any
+        let up = self
+            .gravity
+            .try_normalize(Real::EPSILON)
+            .map_or(Vector::y(), |down| -down);
+        let buoyancy_step = up * (self.solver_params.buoyancy * dt);
+        let ambient = self.solver_params.ambient_temperature;
+        if self.solver_params.cohesion_strength > 0.0 {
+            self.grid
+                .apply_cohesion(self.solver_params.cohesion_strength, dt);
+        }
+        if self.solver_params.vorticity_confinement > 0.0 {
+            self.grid
+                .apply_vorticity_confinement(self.solver_params.vorticity_confinement, dt);
+        }
+        let resolution = self.grid.resolution();
         for (coords, node) in self.grid.iter_active_cells_mut() {
             if node.mass > 0.0 {
                 // Velocity is already computed in P2G, just add gravity
                 node.velocity += gravity_step;
+                node.velocity += buoyancy_step * (node.temperature - ambient);
+                node.velocity *= damping;
+
+                if static_boundary {
+                    node.project_from_static();
+                }
 
                 let coord = IVec2::new(coords.0, coords.1);
-                apply_boundary_conditions(node, coord, self.boundary);
+                apply_boundary_conditions(node, coord, &self.boundary, resolution);
             }
         }
+        // Last, so the walls and body forces are already in what it corrects
+        if self.solver_params.projection_iterations > 0 {
+            self.grid
+                .project_divergence_free(self.solver_params.projection_iterations, &self.boundary);
+        }
+    }
+
+    /// Removes the particle at `index` straight away, whether or not it
+    /// failed, and returns the mapping to compose into `ParticleRemap` (empty
+    /// when there is no such particle). The others keep their order.
+    pub fn remove_particle(&mut self, index: usize) -> Vec<Option<usize>> {
+        let mapping = self.particle_set.remove(index);
+        if mapping.is_empty() {
+            return mapping;
+        }
+
+        self.remap_recycled(&mapping);
+        self.rebuild_particle_bins();
+        mapping
+    }
+
+    /// Removes the particles at `indices` straight away, like
+    /// `remove_particle` does for one.
+    pub fn remove_particles(&mut self, indices: &[usize]) -> Vec<Option<usize>> {
+        let mapping = self.particle_set.remove_many(indices);
+        if mapping.is_empty() {
+            return mapping;
+        }
+
+        self.remap_recycled(&mapping);
+        self.rebuild_particle_bins();
+        mapping
+    }
+
+    /// Clears every particle and grid node, keeping the parameters, gravity
+    /// and boundary. Returns the mapping to compose into `ParticleRemap`,
+    /// with every old index removed.
+    pub fn reset(&mut self) -> Vec<Option<usize>> {
+        let mapping = vec![None; self.particle_count()];
+        self.particle_set.clear();
+        self.recycled.clear();
+        self.grid.clear();
+        mapping
+    }
+
+    /// What `remove_failed_particles` is about to take out.
+    pub fn failed_particles(&self) -> impl Iterator<Item = ParticleFailed> + '_ {
+        self.particles()
+            .iter()
+            .enumerate()
+            .filter(|(_, particle)| particle.failed)
+            .map(|(index, particle)| ParticleFailed {
+                index,
+                position: particle.position,
+                reason: particle.failure_reason,
+            })
     }
 
     pub fn remove_failed_particles(&mut self) -> Vec<Option<usize>> {
@@ -177,6 +660,7 @@ impl MpmState {
             return mapping;
         }
 
+        self.remap_recycled(&mapping);
         self.rebuild_particle_bins();
         mapping
     }
@@ -190,15 +674,628 @@ pub fn cleanup_grid_cells(mut state: ResMut<MpmState>) {
     state.cleanup_grid();
 }
 
+/// Removes failed particles, composing the `ParticleRemap` and sending a
+/// `ParticleFailed` for each when those messages are registered.
 pub fn remove_failed_particles_system(
     mut state: ResMut<MpmState>,
     mut remap: ResMut<ParticleRemap>,
+    failures: Option<ResMut<Messages<ParticleFailed>>>,
 ) {
-    remap.map = state.remove_failed_particles();
+    if let Some(mut failures) = failures {
+        failures.write_batch(state.failed_particles());
+    }
+    let mapping = state.remove_failed_particles();
+    remap.compose(mapping);
 }
 
-pub fn clear_particle_remap_system(mut remap: ResMut<ParticleRemap>) {
+/// Ends the frame's record of removed and recycled particles.
+pub fn clear_particle_remap_system(mut remap: ResMut<ParticleRemap>, mut state: ResMut<MpmState>) {
     if !remap.map.is_empty() {
         remap.map.clear();
     }
+    if !state.recycled_particles().is_empty() {
+        state.clear_recycled_particles();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EOS_POWER, EOS_STIFFNESS, GRAVITY, GridConfig, REST_DENSITY};
+    use crate::core::{GRID_RESOLUTION, KernelKind};
+    use crate::materials::FluidParams;
+    use crate::test_support::step_serial;
+
+    fn water_at(x: Real, y: Real) -> Particle {
+        Particle::new(Vector::new(x, y), MaterialType::water())
+    }
+
+    #[test]
+    fn particle_cell_reports_the_binned_cell() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particle(water_at(10.2, 20.4));
+        let failed = state.add_particle(water_at(30.0, 30.0)).unwrap();
+        state.particles_mut()[failed].failed = true;
+        state.rebuild_particle_bins();
+
+        assert_eq!(state.particle_cell(0), Some(IVec2::new(10, 20)));
+        assert_eq!(state.particle_cell(failed), None);
+        assert_eq!(state.particle_cell(2), None);
+
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        *state.grid_mut() = Grid::with_cell_width(0.5);
+        state.add_particle(water_at(5.1, 3.6));
+        state.rebuild_particle_bins();
+        assert_eq!(state.particle_cell(0), Some(IVec2::new(10, 7)));
+    }
+
+    #[test]
+    fn incremental_rebinning_matches_a_full_rebuild() {
+        let rebuild = |set: &mut ParticleSet| {
+            set.rebuild_bins(1.0, BVec2::FALSE, GRID_RESOLUTION, KernelKind::Quadratic);
+        };
+        let mut set = ParticleSet::new();
+        for j in 0..20 {
+            for i in 0..20 {
+                set.push(water_at(40.25 + i as Real * 0.5, 30.25 + j as Real * 0.5));
+            }
+        }
+        rebuild(&mut set);
+
+        let assert_rebuilds_match = |mut set: ParticleSet| {
+            let mut full = set.clone();
+            full.invalidate_spatial_index();
+            rebuild(&mut full);
+            rebuild(&mut set);
+            assert_eq!(set.cell_regions(), full.cell_regions());
+            assert_eq!(set.particle_order(), full.particle_order());
+            let bin_indices =
+                |set: &ParticleSet| set.bins().iter().map(|bin| bin.indices).collect::<Vec<_>>();
+            assert_eq!(bin_indices(&set), bin_indices(&full));
+            set
+        };
+
+        // A few particles hop cells while the rest jiggle inside theirs
+        for (index, particle) in set.particles_mut().iter_mut().enumerate() {
+            let step = if index % 20 == 7 { 1.3 } else { 0.01 };
+            particle.position += Vector::new(step, -step);
+        }
+        let mut set = assert_rebuilds_match(set);
+
+        // Too many movers fall back to sorting from scratch
+        for particle in set.particles_mut() {
+            particle.position.x += 0.6;
+        }
+        assert_rebuilds_match(set);
+    }
+
+    #[test]
+    fn steady_rebinning_reuses_its_buffers() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..20 {
+            for i in 0..20 {
+                state.add_particle(water_at(40.25 + i as Real * 0.5, 30.25 + j as Real * 0.5));
+            }
+        }
+
+        let mut footprints = Vec::new();
+        for frame in 0..4 {
+            // Alternate hops, so every frame some particles change cell
+            let hop = if frame % 2 == 0 { 1.0 } else { -1.0 };
+            for (index, particle) in state.particles_mut().iter_mut().enumerate() {
+                if index % 20 == 3 {
+                    particle.position.x += hop;
+                }
+            }
+            state.rebuild_particle_bins();
+            footprints.push(state.particle_set().index_heap_bytes());
+        }
+        assert_eq!(footprints[2], footprints[1]);
+        assert_eq!(footprints[3], footprints[2]);
+    }
+
+    #[test]
+    fn insertions_stop_at_the_particle_cap() {
+        let params = SolverParams::default().with_max_particles(3, CapacityHandling::Reject);
+        let mut state = MpmState::new(params, GRAVITY);
+        let batch = (0..5).map(|i| water_at(10.0 + i as Real, 10.0)).collect();
+        let inserted = state.add_particles(batch);
+        assert_eq!(inserted.appended, 0..3);
+        assert!(inserted.recycled.is_empty());
+        assert_eq!(state.particle_count(), 3);
+        assert_eq!(state.add_particle(water_at(20.0, 20.0)), None);
+    }
+
+    #[test]
+    fn a_batch_gets_the_contiguous_indices_after_the_existing_particles() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particle(water_at(5.0, 5.0));
+        let batch: Vec<_> = (0..4).map(|i| water_at(10.0 + i as Real, 10.0)).collect();
+
+        let indices = state.add_particles(batch.clone());
+        assert_eq!(indices.len(), batch.len());
+        assert_eq!(indices.appended, 1..5);
+        for (index, particle) in indices.iter().zip(&batch) {
+            assert_eq!(state.particles()[index].position, particle.position);
+        }
+        assert_eq!(state.add_particles(Vec::new()).appended, 5..5);
+    }
+
+    #[test]
+    fn recycling_overwrites_the_oldest_particle_at_the_cap() {
+        let params = SolverParams::default().with_max_particles(3, CapacityHandling::RecycleOldest);
+        let mut state = MpmState::new(params, GRAVITY);
+        state.add_particles((0..3).map(|i| water_at(10.0 + i as Real, 10.0)).collect());
+        for (particle, age) in state.particles_mut().iter_mut().zip([1.0, 3.0, 2.0]) {
+            particle.age = age;
+        }
+        state.particle_set_mut().refresh_age_order();
+
+        assert_eq!(state.add_particle(water_at(40.0, 40.0)), Some(1));
+        assert_eq!(state.particle_count(), 3);
+        assert_eq!(state.particles()[1].position, Vector::new(40.0, 40.0));
+        assert_eq!(state.add_particle(water_at(50.0, 50.0)), Some(2));
+        // The fresh particles queue up behind the last original one
+        assert_eq!(state.particle_set().oldest(), Some(0));
+        assert_eq!(state.recycled_particles(), [1, 2]);
+
+        // Removals carry the recycled indices along
+        state.remove_particle(1);
+        assert_eq!(state.recycled_particles(), [1]);
+        assert_eq!(state.add_particle(water_at(60.0, 60.0)), Some(2));
+        assert_eq!(state.add_particle(water_at(70.0, 70.0)), Some(0));
+    }
+
+    #[test]
+    fn a_batch_past_the_cap_follows_the_capacity_policy() {
+        let batch_at = |x: Real| (0..3).map(|i| water_at(x + i as Real, 10.0)).collect();
+        for at_capacity in [CapacityHandling::Reject, CapacityHandling::RecycleOldest] {
+            let params = SolverParams::default().with_max_particles(4, at_capacity);
+            let mut state = MpmState::new(params, GRAVITY);
+            state.add_particles(batch_at(10.0));
+            for (particle, age) in state.particles_mut().iter_mut().zip([1.0, 3.0, 2.0]) {
+                particle.age = age;
+            }
+            state.particle_set_mut().refresh_age_order();
+
+            let inserted = state.add_particles(batch_at(40.0));
+            assert_eq!(inserted.appended, 3..4);
+            assert_eq!(state.particle_count(), 4);
+            let positions: Vec<Real> = state.particles().iter().map(|p| p.position.x).collect();
+            match at_capacity {
+                CapacityHandling::Reject => {
+                    assert!(inserted.recycled.is_empty());
+                    assert_eq!(positions, [10.0, 11.0, 12.0, 40.0]);
+                }
+                CapacityHandling::RecycleOldest => {
+                    assert_eq!(inserted.recycled, [1, 2]);
+                    assert_eq!(positions, [10.0, 41.0, 42.0, 40.0]);
+                }
+            }
+            assert_eq!(inserted.iter().count(), inserted.len());
+        }
+    }
+
+    #[test]
+    fn removals_in_one_frame_compose_into_a_single_remap() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particles((0..5).map(|i| water_at(10.0 + i as Real, 10.0)).collect());
+        let mut remap = ParticleRemap::default();
+
+        state.particles_mut()[1].failed = true;
+        remap.compose(state.remove_failed_particles());
+        // Nothing failed in between: the remap is left as is
+        remap.compose(state.remove_failed_particles());
+        // Original index 3 sits at index 2 after the first pass
+        state.particles_mut()[2].failed = true;
+        remap.compose(state.remove_failed_particles());
+
+        assert_eq!(remap.map, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(state.particles()[2].position, Vector::new(14.0, 10.0));
+    }
+
+    #[test]
+    fn removing_one_particle_leaves_the_rest_where_the_remap_says() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        let batch: Vec<_> = (0..5)
+            .map(|i| {
+                let mut particle = water_at(10.0 + i as Real, 10.0);
+                particle.user_data = i;
+                particle.with_velocity(Vector::new(i as Real, -1.0))
+            })
+            .collect();
+        state.add_particles(batch.clone());
+        // Failed particles elsewhere stay until the usual removal pass
+        state.particles_mut()[4].failed = true;
+
+        let mut remap = ParticleRemap::default();
+        remap.compose(state.remove_particle(2));
+        assert_eq!(remap.map, vec![Some(0), Some(1), None, Some(2), Some(3)]);
+        for (old, new) in remap.map.iter().enumerate() {
+            let Some(new) = *new else { continue };
+            let particle = &state.particles()[new];
+            assert_eq!(particle.user_data, batch[old].user_data);
+            assert_eq!(particle.position, batch[old].position);
+            assert_eq!(particle.velocity, batch[old].velocity);
+        }
+        assert!(state.particles()[3].failed);
+        assert!(state.remove_particle(4).is_empty());
+        assert_eq!(state.particle_count(), 4);
+    }
+
+    #[test]
+    fn a_particle_leaving_the_grid_is_reported_out_of_bounds() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particles(vec![water_at(20.0, 20.0), water_at(30.0, 20.0)]);
+        let mut world = World::new();
+        world.insert_resource(state);
+        world.insert_resource(ParticleRemap::default());
+        world.init_resource::<Messages<ParticleFailed>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(remove_failed_particles_system);
+
+        let mut state = world.resource_mut::<MpmState>();
+        state.particles_mut()[1].position = Vector::new(-5.0, 20.0);
+        state.rebuild_particle_bins();
+        schedule.run(&mut world);
+
+        let failures: Vec<_> = world
+            .resource_mut::<Messages<ParticleFailed>>()
+            .drain()
+            .collect();
+        let expected = ParticleFailed {
+            index: 1,
+            position: Vector::new(-5.0, 20.0),
+            reason: FailureReason::OutOfBounds,
+        };
+        assert_eq!(failures, vec![expected]);
+        assert_eq!(world.resource::<MpmState>().particle_count(), 1);
+    }
+
+    #[test]
+    fn world_positions_round_trip_through_sim_space() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        let config = GridConfig::default()
+            .with_origin(Vector::new(-256.0, -256.0))
+            .with_scale(4.0);
+        *state.grid_mut() = Grid::from_config(&config);
+
+        assert_eq!(state.world_to_sim(Vec2::ZERO), Vector::new(64.0, 64.0));
+        assert_eq!(
+            state.sim_to_world(Vector::new(0.0, 0.0)),
+            Vec2::new(-256.0, -256.0)
+        );
+        for world in [
+            Vec2::new(13.5, -7.25),
+            Vec2::new(-300.0, 500.0),
+            Vec2::new(0.1, 0.2),
+        ] {
+            let back = state.sim_to_world(state.world_to_sim(world));
+            assert!(
+                (back - world).length() < 1e-4,
+                "{world} came back as {back}"
+            );
+        }
+    }
+
+    #[test]
+    fn sampling_at_a_node_centre_returns_its_velocity() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        let node = state.grid_mut().get_cell_coord_mut(IVec2::new(40, 30));
+        node.mass = 2.0;
+        node.velocity = Vector::new(3.0, -1.5);
+        // A lighter, slower neighbour inside the stencil pulls the blend off
+        // the node velocity everywhere except at the node itself
+        let neighbour = state.grid_mut().get_cell_coord_mut(IVec2::new(41, 30));
+        neighbour.mass = 1.0;
+        neighbour.velocity = zero_vector();
+
+        let centre = Vector::new(40.5, 30.5);
+        let velocity = state.sample_velocity(centre);
+        let expected = Vector::new(3.0, -1.5) * (2.0 * 0.5625) / (2.0 * 0.5625 + 0.09375);
+        assert!((velocity - expected).norm() < 1e-5, "{velocity}");
+        assert!((state.sample_density(centre) - (2.0 * 0.5625 + 0.09375)).abs() < 1e-5);
+
+        *state.grid_mut().get_cell_coord_mut(IVec2::new(41, 30)) = GridNode::default();
+        assert!((state.sample_velocity(centre) - Vector::new(3.0, -1.5)).norm() < 1e-5);
+        assert_eq!(
+            state.sample_velocity(Vector::new(90.0, 90.0)),
+            zero_vector()
+        );
+        assert_eq!(state.sample_density(Vector::new(90.0, 90.0)), 0.0);
+    }
+
+    #[test]
+    fn damping_drains_kinetic_energy_every_step() {
+        let dt = 1.0 / 60.0;
+        let params = SolverParams::default().with_linear_damping(1.0);
+        let mut state = MpmState::new(params, zero_vector());
+        for j in 0..10 {
+            for i in 0..10 {
+                let particle = water_at(60.25 + i as Real * 0.5, 60.25 + j as Real * 0.5);
+                state.add_particle(particle.with_velocity(Vector::new(8.0, -5.0)));
+            }
+        }
+        let kinetic_energy = |state: &MpmState| -> Real {
+            let particles = state.particles().iter();
+            particles
+                .map(|p| 0.5 * p.mass * p.velocity.norm_squared())
+                .sum()
+        };
+
+        let mut previous = kinetic_energy(&state);
+        for step in 0..30 {
+            step_serial(&mut state, dt);
+            let energy = kinetic_energy(&state);
+            assert!(energy < previous, "step {step}: {energy} after {previous}");
+            previous = energy;
+        }
+    }
+
+    #[test]
+    fn radius_query_matches_a_brute_force_scan() {
+        use crate::core::BoundaryHandling;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for boundary in [BoundaryHandling::Stick, BoundaryHandling::Periodic] {
+            let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+            *state.grid_mut() = Grid::with_cell_width(0.75);
+            state.set_boundary_mode(boundary);
+            for _ in 0..2000 {
+                let position =
+                    Vector::new(rng.random_range(2.0..90.0), rng.random_range(2.0..90.0));
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+            let brute_force = |state: &MpmState, center: Vector, radius: Real| {
+                let mut indices: Vec<usize> = (0..state.particle_count())
+                    .filter(|&i| (state.particles()[i].position - center).norm() <= radius)
+                    .collect();
+                indices.sort_unstable();
+                indices
+            };
+            let queries = [
+                (Vector::new(40.0, 40.0), 6.0),
+                (Vector::new(3.0, 88.0), 10.0),
+                (Vector::new(60.3, 20.7), 0.4),
+                (Vector::new(200.0, 200.0), 5.0),
+            ];
+            let check = |state: &MpmState| {
+                for (center, radius) in queries {
+                    let mut found = state.particle_set().query_radius(center, radius);
+                    found.sort_unstable();
+                    assert_eq!(
+                        found,
+                        brute_force(state, center, radius),
+                        "{center} {radius}"
+                    );
+                }
+            };
+
+            // Stale index: nothing binned yet
+            check(&state);
+            state.rebuild_particle_bins();
+            check(&state);
+            // Particles drift less than a cell between rebuilds
+            for particle in state.particles_mut() {
+                particle.position += Vector::new(rng.random_range(-0.7..0.7), 0.5);
+            }
+            check(&state);
+        }
+    }
+
+    #[test]
+    fn aabb_over_the_whole_domain_selects_every_live_particle() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..20 {
+            for i in 0..30 {
+                state.add_particle(water_at(10.0 + i as Real * 3.3, 5.0 + j as Real * 5.7));
+            }
+        }
+        state.rebuild_particle_bins();
+        state.particles_mut()[17].failed = true;
+        state.particles_mut()[301].failed = true;
+
+        let size = state.grid().resolution() as Real;
+        let mut everything = state
+            .particle_set()
+            .query_aabb(zero_vector(), Vector::repeat(size));
+        everything.sort_unstable();
+        let live: Vec<usize> = (0..state.particle_count())
+            .filter(|&i| i != 17 && i != 301)
+            .collect();
+        assert_eq!(everything, live);
+
+        let count =
+            |min: Vector, max: Vector| state.particle_set().query_aabb_iter(min, max).count();
+        assert_eq!(count(Vector::new(40.0, 40.0), Vector::new(20.0, 60.0)), 0);
+        assert_eq!(count(Vector::new(0.0, Real::NAN), Vector::repeat(size)), 0);
+        let infinite = Vector::repeat(Real::INFINITY);
+        assert_eq!(count(-infinite, infinite), live.len());
+        // Ten columns by twenty rows, the left edge included
+        assert_eq!(count(Vector::new(10.0, 0.0), Vector::new(39.8, size)), 200);
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_lattice_particle() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        assert_eq!(state.particle_set().nearest(Vector::new(5.0, 5.0)), None);
+
+        // Particle `j * 10 + i` sits at (20 + 4i, 30 + 4j)
+        for j in 0..10 {
+            for i in 0..10 {
+                state.add_particle(water_at(20.0 + 4.0 * i as Real, 30.0 + 4.0 * j as Real));
+            }
+        }
+        let expectations = [
+            (Vector::new(33.0, 42.5), 3 * 10 + 3, Vector::new(32.0, 42.0)),
+            (Vector::new(0.0, 0.0), 0, Vector::new(20.0, 30.0)),
+            (Vector::new(90.0, 61.0), 8 * 10 + 9, Vector::new(56.0, 62.0)),
+            (Vector::new(400.0, 400.0), 99, Vector::new(56.0, 66.0)),
+        ];
+        let check = |state: &MpmState| {
+            for (point, index, position) in expectations {
+                let (found, distance) = state.particle_set().nearest(point).unwrap();
+                assert_eq!(found, index, "nearest to {point}");
+                assert!((distance - (position - point).norm()).abs() < 1e-4);
+            }
+        };
+
+        check(&state);
+        state.rebuild_particle_bins();
+        check(&state);
+        // Ties go to the lower index
+        let midpoint = Vector::new(22.0, 30.0);
+        assert_eq!(state.particle_set().nearest(midpoint).unwrap().0, 0);
+        state.particles_mut()[0].failed = true;
+        assert_eq!(state.particle_set().nearest(midpoint).unwrap().0, 1);
+
+        // Moved far from where it was binned, the last particle is still found
+        state.particles_mut()[99].position = Vector::new(5.0, 5.0);
+        let (found, distance) = state.particle_set().nearest(Vector::new(0.0, 0.0)).unwrap();
+        assert_eq!(found, 99);
+        assert!((distance - (50.0 as Real).sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn a_hot_patch_rises_and_a_cold_one_sinks() {
+        let dt = 1.0 / 60.0;
+        let params = SolverParams::default()
+            .with_buoyancy(4.0, 0.0)
+            .with_thermal_diffusivity(0.05);
+        let mut state = MpmState::new(params, GRAVITY);
+        *state.grid_mut() = Grid::from_config(&GridConfig::default().with_resolution(32));
+        let (mut hot, mut cold) = (Vec::new(), Vec::new());
+        for j in 0..24 {
+            for i in 0..52 {
+                let (x, y) = (3.25 + i as Real * 0.5, 3.25 + j as Real * 0.5);
+                let mut particle = water_at(x, y);
+                if (9.0..13.0).contains(&x) && y < 7.0 {
+                    particle = particle.with_temperature(10.0);
+                    hot.push(state.particle_count());
+                } else if (19.0..23.0).contains(&x) && y > 11.0 {
+                    particle = particle.with_temperature(-10.0);
+                    cold.push(state.particle_count());
+                }
+                state.add_particle(particle);
+            }
+        }
+        let mean_height = |state: &MpmState, patch: &[usize]| -> Real {
+            let particles = state.particles();
+            patch
+                .iter()
+                .map(|&index| particles[index].position.y)
+                .sum::<Real>()
+                / patch.len() as Real
+        };
+        let (hot_start, cold_start) = (mean_height(&state, &hot), mean_height(&state, &cold));
+
+        for _ in 0..90 {
+            step_serial(&mut state, dt);
+        }
+
+        assert!(state.particles().iter().all(|particle| !particle.failed));
+        let (hot_end, cold_end) = (mean_height(&state, &hot), mean_height(&state, &cold));
+        assert!(
+            hot_end > hot_start + 1.0,
+            "hot patch went from {hot_start} to {hot_end}"
+        );
+        assert!(
+            cold_end < cold_start - 1.0,
+            "cold patch went from {cold_start} to {cold_end}"
+        );
+    }
+
+    #[test]
+    fn dye_travels_with_the_water_carrying_it() {
+        let dt = 1.0 / 60.0;
+        let flow = Vector::new(6.0, 0.0);
+        let params = SolverParams::default().with_colour_diffusion(0.1);
+        let mut state = MpmState::new(params, zero_vector());
+        for j in 0..20 {
+            for i in 0..40 {
+                let mut particle = water_at(40.25 + i as Real * 0.5, 60.25 + j as Real * 0.5);
+                if i < 12 {
+                    particle = particle.with_colour(Color::linear_rgb(1.0, 0.0, 0.0));
+                }
+                state.add_particle(particle.with_velocity(flow));
+            }
+        }
+        // Dye is whatever green the particles have lost
+        let dye_centroid = |state: &MpmState| -> Vector {
+            let particles = state.particles();
+            let dye: Real = particles.iter().map(|p| 1.0 - p.colour[1]).sum();
+            particles.iter().fold(zero_vector(), |sum, p| {
+                sum + p.position * (1.0 - p.colour[1])
+            }) / dye
+        };
+        let start = dye_centroid(&state);
+
+        for _ in 0..20 {
+            step_serial(&mut state, dt);
+        }
+
+        let shift = dye_centroid(&state) - start;
+        assert!(
+            (shift - flow * (20.0 * dt)).norm() < 0.25,
+            "dye moved by {shift}"
+        );
+        let dyed = state.sample_colour(start + shift).unwrap().to_linear();
+        assert!(dyed.red > 0.9 && dyed.green < 0.2, "{dyed:?}");
+        let clear = state
+            .sample_colour(Vector::new(55.0, 65.0))
+            .unwrap()
+            .to_linear();
+        assert!(clear.green > 0.9, "{clear:?}");
+        assert!(state.sample_colour(Vector::new(10.0, 10.0)).is_none());
+    }
+
+    #[test]
+    fn only_the_fluid_with_volume_preservation_pulls_itself_in() {
+        let dt = 1.0 / 60.0;
+        let syrup = MaterialType::fluid(FluidParams::new(
+            "syrup",
+            REST_DENSITY,
+            EOS_STIFFNESS,
+            EOS_POWER,
+        ));
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let preserving = SolverParams::with_volume_preservation().with_correction_strength(1.0);
+        state.set_material_params(&syrup, preserving);
+        for j in 0..20 {
+            for i in 0..20 {
+                let (x, y) = (i as Real * 0.5, j as Real * 0.5);
+                state.add_particle(water_at(30.25 + x, 60.25 + y));
+                state.add_particle(Particle::new(
+                    Vector::new(80.25 + x, 60.25 + y),
+                    syrup.clone(),
+                ));
+            }
+        }
+        assert!(state.solver_params_for(&syrup).preserve_fluid_volume);
+        assert!(
+            !state
+                .solver_params_for(&MaterialType::water())
+                .preserve_fluid_volume
+        );
+
+        for _ in 0..60 {
+            step_serial(&mut state, dt);
+        }
+
+        let width = |name: &str| {
+            let xs = state
+                .particles()
+                .iter()
+                .filter(|particle| particle.material_type.material_name() == name)
+                .map(|particle| particle.position.x);
+            xs.clone().fold(Real::MIN, Real::max) - xs.fold(Real::MAX, Real::min)
+        };
+        let (water_width, syrup_width) = (width("water"), width("syrup"));
+        assert!(
+            syrup_width < water_width - 0.1,
+            "syrup spans {syrup_width}, water {water_width}"
+        );
+    }
 }
@@ -2,15 +2,22 @@
 //!
 //! Particles carry position, velocity, mass and material properties.
 
+use bevy::prelude::{Color, LinearRgba};
+use rayon::prelude::*;
+
 use crate::materials::MaterialType;
 use crate::math::{
-    Matrix, Real, Vector, identity_matrix, matrix_determinant, matrix_trace, zero_matrix,
-    zero_vector,
+    Matrix, Real, Vector, condition_number, consts, identity_matrix, matrix_determinant,
+    zero_matrix, zero_vector,
 };
 
 /// Boundary contact information stored alongside a particle when interaction
 /// with static geometry is enabled.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct ParticleContact {
     pub boundary_normal: Vector,
     pub boundary_distance: Real,
@@ -27,22 +34,41 @@ impl Default for ParticleContact {
 
 /// Fracture-related parameters used by snow / brittle materials.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct ParticleFracture {
+    /// Damage gained per second per unit of stress ratio above the threshold.
     pub crack_propagation_factor: Real,
+    /// Deviatoric stress magnitude the material withstands without damage.
     pub crack_threshold: Real,
+    /// Accumulated damage; the particle cracks once it reaches 1.
+    pub damage: Real,
 }
 
-impl Default for ParticleFracture {
-    fn default() -> Self {
+impl ParticleFracture {
+    pub fn new(crack_propagation_factor: Real, crack_threshold: Real) -> Self {
         Self {
-            crack_propagation_factor: 0.0,
-            crack_threshold: Real::MAX,
+            crack_propagation_factor,
+            crack_threshold,
+            damage: 0.0,
         }
     }
 }
 
+impl Default for ParticleFracture {
+    fn default() -> Self {
+        Self::new(0.0, Real::MAX)
+    }
+}
+
 /// Internal material state carried per particle for plasticity / hardening.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct ParticlePlasticityState {
     pub nacc_alpha: Real,
     pub plastic_hardening: Real,
@@ -61,7 +87,32 @@ impl Default for ParticlePlasticityState {
     }
 }
 
+/// Why a particle was failed, reported by `ParticleFailed` when it is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum FailureReason {
+    /// Non-finite state or a deformation gradient past the condition
+    /// threshold (see `Particle::update_health`).
+    Numerical,
+    /// Its kernel stencil left the grid.
+    OutOfBounds,
+    /// Drained by a `Sink`.
+    Sink,
+    /// Carried past a `BoundaryHandling::Outflow` edge.
+    Outflow,
+    /// `failed` was set directly.
+    #[default]
+    Other,
+}
+
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct Particle {
     pub position: Vector,
     pub velocity: Vector,
@@ -71,8 +122,15 @@ pub struct Particle {
     pub affine_momentum_matrix: Matrix, // MLS affine velocity field (C matrix)
     pub velocity_gradient: Matrix,
     pub deformation_gradient: Matrix,
+    /// Displacement gradient `C dt` over the last substep as projected by
+    /// the `PbmpmConfig` constraints, and the one from the substep before.
+    pub deformation_displacement: Matrix,
+    pub prev_deformation_displacement: Matrix,
     pub plastic_deformation_gradient_det: Real,
     pub material_type: MaterialType,
+    /// Density around the particle as of the last P2G, from the same kernel
+    /// estimate its pressure uses; 0 before the first step.
+    pub density: Real,
 
     // Simulation bookkeeping
     pub grid_index: u64,
@@ -82,14 +140,42 @@ pub struct Particle {
     pub parameter2: Real,
     pub crack_propagation_factor: Real,
     pub crack_threshold: Real,
+    /// Node mass at which this particle's surroundings count as dense and
+    /// cohesion stops pulling them together, if reached before the material
+    /// fills the node at rest density (`Real::MAX` leaves it to that).
     pub cohesion_mass: Real,
+    /// How strongly this particle takes part in cohesion, relative to
+    /// `SolverParams::cohesion_strength` (0.0 opts it out).
     pub cohesion_energy: Real,
     pub phase_buffer: Vector,
+    /// Carried through the grid when `SolverParams::transfers_heat`; hotter
+    /// than `SolverParams::ambient_temperature` floats, colder sinks.
+    pub temperature: Real,
+    /// Latent heat taken in (or given up) part-way through a melt (or freeze),
+    /// see `PhaseTransition`.
+    pub stored_latent_heat: Real,
+    /// Linear RGBA dye, carried along with the material and blended on the
+    /// grid when `SolverParams::colour_diffusion` is set. Never affects the
+    /// dynamics.
+    pub colour: [Real; 4],
+    /// Simulated seconds since the particle was inserted.
+    pub age: Real,
     pub is_static: bool,
+    /// Collision layers; a static obstacle only blocks particles sharing at
+    /// least one bit with it.
+    pub collision_mask: u32,
     pub kinematic_velocity: Option<Vector>,
+    /// Resting: G2P leaves the particle in place until the grid around it
+    /// moves (see `SolverParams::sleep_threshold`). It still scatters its
+    /// mass, so the material around it is held up as before.
+    pub sleeping: bool,
+    /// Consecutive steps spent below the sleep threshold.
+    pub idle_steps: u32,
 
     // Health tracking
     pub failed: bool,
+    /// Meaningful only once `failed` is set.
+    pub failure_reason: FailureReason,
     pub condition_number: Real,
 
     // Optional physics extensions
@@ -111,8 +197,11 @@ impl Particle {
             affine_momentum_matrix: zero_matrix(),
             velocity_gradient: zero_matrix(),
             deformation_gradient: identity_matrix(),
+            deformation_displacement: zero_matrix(),
+            prev_deformation_displacement: zero_matrix(),
             plastic_deformation_gradient_det: 1.0,
             material_type,
+            density: 0.0,
             grid_index: 0,
             phase: 1.0,
             psi_pos: 0.0,
@@ -121,11 +210,19 @@ impl Particle {
             crack_propagation_factor: 0.0,
             crack_threshold: Real::MAX,
             cohesion_mass: Real::MAX,
-            cohesion_energy: 0.0,
+            cohesion_energy: 1.0,
             phase_buffer: zero_vector(),
+            temperature: 0.0,
+            stored_latent_heat: 0.0,
+            colour: [1.0; 4],
+            age: 0.0,
             is_static: false,
+            collision_mask: u32::MAX,
             kinematic_velocity: None,
+            sleeping: false,
+            idle_steps: 0,
             failed: false,
+            failure_reason: FailureReason::Other,
             condition_number: 1.0,
             plasticity: ParticlePlasticityState::default(),
             contact: None,
@@ -153,6 +250,27 @@ impl Particle {
         self
     }
 
+    pub fn with_temperature(mut self, temperature: Real) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_colour(mut self, colour: Color) -> Self {
+        let LinearRgba {
+            red,
+            green,
+            blue,
+            alpha,
+        } = colour.to_linear();
+        self.colour = [red, green, blue, alpha].map(Real::from);
+        self
+    }
+
+    pub fn with_collision_mask(mut self, collision_mask: u32) -> Self {
+        self.collision_mask = collision_mask;
+        self
+    }
+
     pub fn with_radius(mut self, radius: Real) -> Self {
         self.radius0 = radius;
         self
@@ -160,7 +278,7 @@ impl Particle {
 
     /// Create particle with specific density and radius
     pub fn with_density(radius: Real, density: Real) -> Self {
-        let volume = std::f32::consts::PI * radius * radius;
+        let volume = consts::PI * radius * radius;
         Self {
             position: zero_vector(),
             velocity: zero_vector(),
@@ -186,6 +304,8 @@ impl Particle {
     }
 
     pub fn with_fracture(mut self, fracture: ParticleFracture) -> Self {
+        self.crack_propagation_factor = fracture.crack_propagation_factor;
+        self.crack_threshold = fracture.crack_threshold;
         self.fracture = Some(fracture);
         self
     }
@@ -194,6 +314,12 @@ impl Particle {
         self.fracture = None;
     }
 
+    /// Marks the particle for the next failed-particle removal.
+    pub fn fail(&mut self, reason: FailureReason) {
+        self.failed = true;
+        self.failure_reason = reason;
+    }
+
     #[inline(always)]
     pub fn current_volume(&self, density: Real) -> Real {
         if density > 0.0 {
@@ -221,6 +347,15 @@ impl Particle {
         }
     }
 
+    /// Rest density of the particle's material, falling back to the
+    /// particle's own `rest_density` for materials without one.
+    #[inline(always)]
+    pub fn material_rest_density(&self) -> Real {
+        self.material_type
+            .rest_density()
+            .unwrap_or_else(|| self.rest_density())
+    }
+
     #[inline(always)]
     pub fn jacobian(&self) -> Real {
         matrix_determinant(&self.deformation_gradient)
@@ -237,48 +372,217 @@ impl Particle {
         self.volume0 * jacobian.abs()
     }
 
+    /// Fails the particle once anything it carries is non-finite or its
+    /// deformation gradient's condition number exceeds `condition_threshold`
+    /// (see `SolverParams::condition_threshold`).
+    ///
+    /// The condition number is taken of `F` rather than the affine matrix `C`:
+    /// a particle moving rigidly has a singular `C`, which says nothing about
+    /// its health.
     #[inline(always)]
-    pub fn update_health(&mut self) {
+    pub fn update_health(&mut self, condition_threshold: Real) {
         if !matrix_is_finite(&self.affine_momentum_matrix) {
-            self.failed = true;
+            self.fail(FailureReason::Numerical);
             self.condition_number = Real::INFINITY;
             return;
         }
 
-        let det = matrix_determinant(&self.affine_momentum_matrix).abs();
-        let trace = matrix_trace(&self.affine_momentum_matrix).abs();
-
-        self.condition_number = if det > 1e-12 {
-            trace / det
-        } else {
-            Real::INFINITY
-        };
-
-        const CONDITION_THRESHOLD: Real = 1e6;
-        if self.condition_number > CONDITION_THRESHOLD || !self.condition_number.is_finite() {
-            self.failed = true;
+        self.condition_number = condition_number(&self.deformation_gradient);
+        if self.condition_number > condition_threshold || !self.condition_number.is_finite() {
+            self.fail(FailureReason::Numerical);
         }
 
-        if !self.position.x.is_finite() || !self.position.y.is_finite()
-            || !self.velocity.x.is_finite() || !self.velocity.y.is_finite()
+        if !self.position.x.is_finite()
+            || !self.position.y.is_finite()
+            || !self.velocity.x.is_finite()
+            || !self.velocity.y.is_finite()
             || !self.mass.is_finite()
             || self.mass <= 0.0
         {
-            self.failed = true;
+            self.fail(FailureReason::Numerical);
         }
 
         if !self.volume0.is_finite() || self.volume0 <= 0.0 {
-            self.failed = true;
+            self.fail(FailureReason::Numerical);
+        }
+    }
+}
+
+/// How a `ParticleBuilder` was told the particle's size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ParticleSize {
+    RadiusDensity { radius: Real, density: Real },
+    MassVolume { mass: Real, volume: Real },
+}
+
+/// Builds a `Particle` whose `mass`, `volume0` and `radius0` agree, from a
+/// radius and density or a mass and volume. Without either it fills a unit
+/// volume at the material's rest density (1.0 for solids).
+#[derive(Clone)]
+pub struct ParticleBuilder {
+    material_type: MaterialType,
+    position: Vector,
+    velocity: Vector,
+    size: Option<ParticleSize>,
+}
+
+impl ParticleBuilder {
+    pub fn new(material_type: MaterialType) -> Self {
+        Self {
+            material_type,
+            position: zero_vector(),
+            velocity: zero_vector(),
+            size: None,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vector) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vector) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// A disc of `radius` at `density`, as in `Particle::with_density`.
+    pub fn with_radius_and_density(mut self, radius: Real, density: Real) -> Self {
+        self.size = Some(ParticleSize::RadiusDensity { radius, density });
+        self
+    }
+
+    /// `mass` spread over `volume`; the radius is that of a disc of `volume`.
+    pub fn with_mass_and_volume(mut self, mass: Real, volume: Real) -> Self {
+        self.size = Some(ParticleSize::MassVolume { mass, volume });
+        self
+    }
+
+    /// The particle, undeformed and with the plastic state its material
+    /// starts from.
+    pub fn build(self) -> Particle {
+        let (mass, volume0, radius0) = match self.size {
+            Some(ParticleSize::RadiusDensity { radius, density }) => {
+                let volume = consts::PI * radius * radius;
+                (volume * density, volume, radius)
+            }
+            Some(ParticleSize::MassVolume { mass, volume }) => {
+                (mass, volume, (volume / consts::PI).sqrt())
+            }
+            None => {
+                let density = self.material_type.rest_density().unwrap_or(1.0);
+                (density, 1.0, (1.0 / consts::PI).sqrt())
+            }
+        };
+        let mut plasticity = ParticlePlasticityState::default();
+        if let MaterialType::Sand(_) | MaterialType::Snow(_) = self.material_type {
+            // Sand and snow harden with the plastic strain they accumulate
+            // from zero
+            plasticity.plastic_hardening = 0.0;
+        }
+        Particle {
+            position: self.position,
+            velocity: self.velocity,
+            mass,
+            volume0,
+            radius0,
+            deformation_gradient: identity_matrix(),
+            plastic_deformation_gradient_det: 1.0,
+            plasticity,
+            ..Particle::zeroed(self.material_type)
         }
     }
 }
 
 fn matrix_is_finite(m: &Matrix) -> bool {
-    m[(0,0)].is_finite() && m[(0,1)].is_finite() && m[(1,0)].is_finite() && m[(1,1)].is_finite()
+    m[(0, 0)].is_finite() && m[(0, 1)].is_finite() && m[(1, 0)].is_finite() && m[(1, 1)].is_finite()
+}
+
+/// Fewest particles `update_particles_health` checks in parallel; below this
+/// spinning up the thread pool costs more than the checks.
+pub const PARALLEL_HEALTH_MIN_PARTICLES: usize = 4096;
+
+/// Runs `Particle::update_health` on every particle, in parallel for
+/// `PARALLEL_HEALTH_MIN_PARTICLES` or more.
+pub fn update_particles_health(particles: &mut [Particle], condition_threshold: Real) {
+    if particles.len() < PARALLEL_HEALTH_MIN_PARTICLES {
+        update_particles_health_serial(particles, condition_threshold);
+        return;
+    }
+    particles
+        .par_iter_mut()
+        .for_each(|particle| particle.update_health(condition_threshold));
 }
 
-pub fn update_particles_health(particles: &mut [Particle]) {
+/// Single-threaded `update_particles_health`, for comparison and profiling.
+pub fn update_particles_health_serial(particles: &mut [Particle], condition_threshold: Real) {
     for particle in particles.iter_mut() {
-        particle.update_health();
+        particle.update_health(condition_threshold);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{SandParams, SnowParams};
+    use crate::math::diagonal_from_vec;
+
+    #[test]
+    fn parallel_health_checks_match_the_serial_loop() {
+        let mut particles: Vec<Particle> = (0..PARALLEL_HEALTH_MIN_PARTICLES + 100)
+            .map(|i| {
+                let position = Vector::new((i % 100) as Real, (i / 100) as Real);
+                let mut particle = Particle::new(position, MaterialType::water());
+                match i % 7 {
+                    1 => particle.position.x = Real::NAN,
+                    2 => particle.velocity.y = Real::INFINITY,
+                    3 => particle.affine_momentum_matrix[(1, 0)] = Real::NAN,
+                    4 => particle.deformation_gradient = diagonal_from_vec(Vector::new(1e4, 1e-4)),
+                    5 => particle.mass = 0.0,
+                    _ => {}
+                }
+                particle
+            })
+            .collect();
+        let mut serial = particles.clone();
+
+        update_particles_health(&mut particles, 1.0e6);
+        update_particles_health_serial(&mut serial, 1.0e6);
+        for (index, (parallel, serial)) in particles.iter().zip(&serial).enumerate() {
+            assert_eq!(parallel.failed, serial.failed, "particle {index}");
+            let (a, b) = (parallel.condition_number, serial.condition_number);
+            assert!(
+                a == b || (a.is_nan() && b.is_nan()),
+                "particle {index}: {a} vs {b}"
+            );
+            assert_eq!(
+                parallel.failed,
+                !matches!(index % 7, 0 | 6),
+                "particle {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_builder_sized_by_radius_and_density_matches_with_density() {
+        let expected = Particle::with_density(0.3, 2.0);
+        let built = ParticleBuilder::new(MaterialType::water())
+            .with_radius_and_density(0.3, 2.0)
+            .build();
+        assert_eq!(built.mass, expected.mass);
+        assert_eq!(built.volume0, expected.volume0);
+        assert_eq!(built.radius0, expected.radius0);
+        assert_eq!(built.rest_density(), 2.0);
+
+        let built = ParticleBuilder::new(MaterialType::water())
+            .with_mass_and_volume(expected.mass, expected.volume0)
+            .build();
+        assert!((built.radius0 - 0.3).abs() < 1e-6);
+
+        let sand = ParticleBuilder::new(MaterialType::sand(SandParams::sand())).build();
+        assert_eq!(sand.plasticity.plastic_hardening, 0.0);
+        assert_eq!(sand.rest_density(), 1.0);
+        let snow = ParticleBuilder::new(MaterialType::snow(SnowParams::snow())).build();
+        assert_eq!(snow.plasticity.plastic_hardening, 0.0);
     }
 }
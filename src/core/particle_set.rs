@@ -1,11 +1,14 @@
 use indexmap::IndexSet;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::ops::Range;
 
-use crate::core::Particle;
-use crate::core::grid::{NEIGHBOR_COUNT, is_coord_neighborhood_safe};
-use crate::core::kernel::{cell_colour, cell_from_position, populate_transfer_cache};
-use crate::math::Real;
-use bevy::prelude::{IVec2, Vec2};
+use crate::core::grid::{MAX_NEIGHBOR_COUNT, is_valid_grid_coord, wrap_grid_coord_on};
+use crate::core::kernel::{KernelKind, cell_colour, cell_from_position, populate_transfer_cache};
+use crate::core::{FailureReason, Particle};
+use crate::geometry::sp_grid::unpack_to_ivec;
+use crate::math::{Real, Vector};
+use bevy::prelude::{BVec2, IVec2};
 
 pub type PackedCell = u64;
 
@@ -13,19 +16,31 @@ fn pack_coords(ix: i32, iy: i32) -> PackedCell {
     ((ix as u64) << 32) | (iy as u32 as u64)
 }
 
+/// A particle's stencil: node coordinate, weight and node-minus-particle
+/// offset for each of the kernel's nodes.
 #[derive(Clone, Copy)]
 pub struct ParticleTransferCache {
-    pub neighbors: [(IVec2, f32, Vec2); NEIGHBOR_COUNT],
+    pub(crate) neighbors: [(IVec2, Real, Vector); MAX_NEIGHBOR_COUNT],
+    pub(crate) len: u8,
 }
 
 impl Default for ParticleTransferCache {
     fn default() -> Self {
         Self {
-            neighbors: [(IVec2::ZERO, 0.0, Vec2::ZERO); NEIGHBOR_COUNT],
+            neighbors: [(IVec2::ZERO, 0.0, Vector::zeros()); MAX_NEIGHBOR_COUNT],
+            len: 0,
         }
     }
 }
 
+impl ParticleTransferCache {
+    /// The stencil nodes in use; empty for particles off the grid.
+    #[inline]
+    pub fn neighbors(&self) -> &[(IVec2, Real, Vector)] {
+        &self.neighbors[..self.len as usize]
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct ParticleBin {
     pub colour: u8,
@@ -53,6 +68,35 @@ impl ParticleBin {
     }
 }
 
+/// Most particles may change cell between rebuilds while `rebuild_bins` still
+/// patches the previous order: one in `INCREMENTAL_REBIN_DIVISOR`.
+const INCREMENTAL_REBIN_DIVISOR: usize = 8;
+
+/// Grid settings the current bins were built with.
+#[derive(Clone, Copy, PartialEq)]
+struct BinLayout {
+    cell_width: Real,
+    periodic: BVec2,
+    resolution: usize,
+}
+
+/// Working buffers `rebuild_bins` keeps between calls, so rebinning a steady
+/// particle count allocates nothing.
+#[derive(Clone, Default)]
+struct RebinScratch {
+    moved: Vec<usize>,
+    is_moved: Vec<bool>,
+    kept: Vec<usize>,
+}
+
+/// The two velocity buffers G2P alternates between when double-buffering is
+/// on: it reads `previous` and writes `current`, then the two swap.
+#[derive(Clone, Default)]
+struct VelocityBuffers {
+    previous: Vec<Vector>,
+    current: Vec<Vector>,
+}
+
 #[derive(Clone)]
 pub struct ParticleSet {
     particles: Vec<Particle>,
@@ -62,6 +106,21 @@ pub struct ParticleSet {
     active_cells: Vec<PackedCell>,
     particle_bins: Vec<ParticleBin>,
     transfer_cache: Vec<ParticleTransferCache>,
+    layout: Option<BinLayout>,
+    scratch: RebinScratch,
+    /// Per-particle velocity buffers when double-buffering is on, kept in
+    /// step with `particles` as they are removed.
+    velocities: Option<VelocityBuffers>,
+    /// Particle indices from the largest `age` to the smallest. Every
+    /// particle ages by the same step, so the order holds on its own: new
+    /// particles join at the back and recycling takes from the front.
+    age_order: VecDeque<usize>,
+}
+
+impl Default for ParticleSet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ParticleSet {
@@ -78,6 +137,10 @@ impl ParticleSet {
             active_cells: Vec::new(),
             particle_bins: Vec::new(),
             transfer_cache: Vec::new(),
+            layout: None,
+            scratch: RebinScratch::default(),
+            velocities: None,
+            age_order: VecDeque::new(),
         }
     }
 
@@ -92,23 +155,83 @@ impl ParticleSet {
     pub fn insert(&mut self, particle: Particle) -> usize {
         let index = self.particles.len();
         self.particles.push(particle);
+        self.enqueue_by_age(index);
         self.invalidate_spatial_index();
         index
     }
 
-    pub fn insert_batch(&mut self, mut batch: Vec<Particle>) {
+    /// Appends `batch` and returns the index of its first particle; the
+    /// rest follow contiguously.
+    pub fn insert_batch(&mut self, mut batch: Vec<Particle>) -> usize {
+        let start = self.particles.len();
         self.particles.append(&mut batch);
+        for index in start..self.particles.len() {
+            self.enqueue_by_age(index);
+        }
         self.invalidate_spatial_index();
+        start
     }
 
     pub fn push(&mut self, particle: Particle) -> usize {
         self.insert(particle)
     }
 
+    /// Overwrites the particle at `index` in place. Finding it in the age
+    /// order is a linear scan; `recycle_oldest` is the constant-time way to
+    /// overwrite the oldest.
+    pub fn replace(&mut self, index: usize, particle: Particle) {
+        self.particles[index] = particle;
+        if let Some(position) = self.age_order.iter().position(|&other| other == index) {
+            self.age_order.remove(position);
+        }
+        self.enqueue_by_age(index);
+        self.refresh_previous_velocity(index);
+        self.invalidate_spatial_index();
+    }
+
+    /// Overwrites the oldest particle with `particle` and returns its index,
+    /// or `None` when the set is empty.
+    pub fn recycle_oldest(&mut self, particle: Particle) -> Option<usize> {
+        let index = self.age_order.pop_front()?;
+        self.particles[index] = particle;
+        self.enqueue_by_age(index);
+        self.refresh_previous_velocity(index);
+        self.invalidate_spatial_index();
+        Some(index)
+    }
+
+    /// Index of the particle with the largest `age` (the lowest index on ties).
+    pub fn oldest(&self) -> Option<usize> {
+        self.age_order.front().copied()
+    }
+
+    /// Re-sorts the age order, for code that set `Particle::age` directly.
+    pub fn refresh_age_order(&mut self) {
+        let particles = &self.particles;
+        let mut order: Vec<usize> = (0..particles.len()).collect();
+        order.sort_by(|&a, &b| particles[b].age.total_cmp(&particles[a].age));
+        self.age_order = order.into();
+    }
+
+    /// Files `index` into the age order behind every older particle and
+    /// every lower index of the same age. New particles are the youngest, so
+    /// this is usually a push.
+    fn enqueue_by_age(&mut self, index: usize) {
+        let particles = &self.particles;
+        let age = particles[index].age;
+        let position = self.age_order.partition_point(|&other| {
+            let other_age = particles[other].age;
+            other_age > age || (other_age == age && other < index)
+        });
+        self.age_order.insert(position, index);
+    }
+
     pub fn particles(&self) -> &[Particle] {
         &self.particles
     }
 
+    /// Ages changed through here leave `oldest` stale until
+    /// `refresh_age_order`.
     pub fn particles_mut(&mut self) -> &mut [Particle] {
         &mut self.particles
     }
@@ -161,11 +284,118 @@ impl ParticleSet {
         (particles, cache)
     }
 
+    /// Double-buffers particle velocities: G2P then starts every particle
+    /// from the velocity the previous G2P left it with, read from a back
+    /// buffer no particle writes to, so a step sees the same values whatever
+    /// order particles are updated in.
+    ///
+    /// Velocities written outside G2P only reach the back buffer through
+    /// `refresh_previous_velocity`; particles appended since the last step
+    /// start from their own velocity.
+    pub fn set_velocity_double_buffer(&mut self, enabled: bool) {
+        if enabled != self.velocities.is_some() {
+            self.velocities = enabled.then(VelocityBuffers::default);
+        }
+    }
+
+    pub fn is_velocity_double_buffered(&self) -> bool {
+        self.velocities.is_some()
+    }
+
+    /// The velocities the last G2P wrote, one per particle, which the next
+    /// one reads. `None` when double-buffering is off.
+    pub fn previous_velocities(&self) -> Option<&[Vector]> {
+        self.velocities
+            .as_ref()
+            .map(|buffers| buffers.previous.as_slice())
+    }
+
+    /// Makes the particle at `index` start the next G2P from its current
+    /// velocity, for code that rewrote it between steps.
+    pub fn refresh_previous_velocity(&mut self, index: usize) {
+        if let Some(buffers) = &mut self.velocities
+            && let Some(previous) = buffers.previous.get_mut(index)
+        {
+            *previous = self.particles[index].velocity;
+        }
+    }
+
+    /// `refresh_previous_velocity` for every particle.
+    pub fn refresh_previous_velocities(&mut self) {
+        if let Some(buffers) = &mut self.velocities {
+            buffers.previous.clear();
+        }
+        self.sync_velocity_buffers();
+    }
+
+    /// Sizes both buffers to the particle count, seeding the back buffer of
+    /// particles appended since the last step with their own velocity.
+    pub fn sync_velocity_buffers(&mut self) {
+        if let Some(buffers) = &mut self.velocities {
+            let seeded = buffers.previous.len().min(self.particles.len());
+            buffers.previous.truncate(seeded);
+            let appended = self.particles[seeded..].iter();
+            buffers
+                .previous
+                .extend(appended.map(|particle| particle.velocity));
+            buffers
+                .current
+                .resize(self.particles.len(), Vector::zeros());
+        }
+    }
+
+    /// Swaps the front and back buffers, so what G2P just wrote becomes what
+    /// the next step reads. Does nothing when double-buffering is off.
+    pub fn swap_velocity_buffers(&mut self) {
+        if let Some(buffers) = &mut self.velocities {
+            std::mem::swap(&mut buffers.previous, &mut buffers.current);
+        }
+    }
+
+    /// The particles to write, next to their caches and, when
+    /// double-buffering is on, the back buffer to read and the front buffer
+    /// to write. Call `sync_velocity_buffers` first.
+    pub fn particles_mut_cache_and_velocities(
+        &mut self,
+    ) -> (
+        &mut [Particle],
+        &[ParticleTransferCache],
+        Option<(&[Vector], &mut [Vector])>,
+    ) {
+        let buffers = self
+            .velocities
+            .as_mut()
+            .map(|buffers| (buffers.previous.as_slice(), buffers.current.as_mut_slice()));
+        (&mut self.particles, &self.transfer_cache, buffers)
+    }
+
     pub fn remove_failed(&mut self) -> Vec<Option<usize>> {
         if !self.particles.iter().any(|particle| particle.failed) {
             return Vec::new();
         }
 
+        self.retain(|_, particle| !particle.failed)
+    }
+
+    /// Removes the particles at `indices`, keeping the others in order, and
+    /// returns the mapping in the form `remove_failed` does (empty when none
+    /// of them are in range).
+    pub fn remove_many(&mut self, indices: &[usize]) -> Vec<Option<usize>> {
+        let mut removed = vec![false; self.particles.len()];
+        for &index in indices {
+            if let Some(flag) = removed.get_mut(index) {
+                *flag = true;
+            }
+        }
+        if !removed.contains(&true) {
+            return Vec::new();
+        }
+
+        self.retain(|index, _| !removed[index])
+    }
+
+    /// Keeps the particles `keep` accepts and returns the old-to-new mapping.
+    fn retain(&mut self, keep: impl Fn(usize, &Particle) -> bool) -> Vec<Option<usize>> {
         let old_len = self.particles.len();
         let mut mapping = vec![None; old_len];
         let mut survivors = Vec::with_capacity(old_len);
@@ -177,7 +407,7 @@ impl ParticleSet {
             .zip(self.transfer_cache.drain(..))
             .enumerate()
         {
-            if !particle.failed {
+            if keep(old_idx, &particle) {
                 let new_idx = survivors.len();
                 mapping[old_idx] = Some(new_idx);
                 survivors.push(particle);
@@ -187,6 +417,42 @@ impl ParticleSet {
 
         self.particles = survivors;
         self.transfer_cache = cache_survivors;
+        if let Some(buffers) = &mut self.velocities {
+            let mut slots = mapping.iter();
+            buffers
+                .previous
+                .retain(|_| slots.next().is_some_and(Option::is_some));
+        }
+        remap_age_order(&mut self.age_order, &mapping);
+        self.invalidate_spatial_index();
+        mapping
+    }
+
+    /// Removes the particle at `index`, keeping the others in order, and
+    /// returns the old-to-new mapping in the form `remove_failed` does (empty
+    /// when `index` is out of range).
+    pub fn remove(&mut self, index: usize) -> Vec<Option<usize>> {
+        if index >= self.particles.len() {
+            return Vec::new();
+        }
+
+        let mapping: Vec<Option<usize>> = (0..self.particles.len())
+            .map(|old_idx| match old_idx.cmp(&index) {
+                Ordering::Less => Some(old_idx),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(old_idx - 1),
+            })
+            .collect();
+        self.particles.remove(index);
+        if index < self.transfer_cache.len() {
+            self.transfer_cache.remove(index);
+        }
+        if let Some(buffers) = &mut self.velocities
+            && index < buffers.previous.len()
+        {
+            buffers.previous.remove(index);
+        }
+        remap_age_order(&mut self.age_order, &mapping);
         self.invalidate_spatial_index();
         mapping
     }
@@ -194,18 +460,46 @@ impl ParticleSet {
     pub fn clear(&mut self) {
         self.particles.clear();
         self.transfer_cache.clear();
+        if let Some(buffers) = &mut self.velocities {
+            buffers.previous.clear();
+        }
+        self.age_order.clear();
         self.invalidate_spatial_index();
     }
 
-    pub fn rebuild_bins(&mut self, cell_width: Real) {
+    /// Re-sorts particles into cells and refreshes their transfer caches.
+    ///
+    /// Along `periodic` axes, cells and kernel stencils wrap across the
+    /// domain edges instead of failing particles whose stencil leaves the grid.
+    ///
+    /// Every cache is refreshed, since weights move with the particles, but
+    /// when the bins are current for the same grid and few particles changed
+    /// cell, only those are re-sorted and merged back into the previous order;
+    /// the result is the same as sorting from scratch.
+    pub fn rebuild_bins(
+        &mut self,
+        cell_width: Real,
+        periodic: BVec2,
+        resolution: usize,
+        kernel: KernelKind,
+    ) {
         let particle_count = self.particles.len();
         if particle_count == 0 {
             self.invalidate_spatial_index();
             return;
         }
 
-        self.order.clear();
-        self.order.extend(0..particle_count);
+        let layout = BinLayout {
+            cell_width,
+            periodic,
+            resolution,
+        };
+        // Insertions and removals drop the layout, so the previous order and
+        // cells still line up with the particles whenever it matches
+        let incremental = self.layout == Some(layout) && self.order.len() == particle_count;
+        let mut moved = std::mem::take(&mut self.scratch.moved);
+        moved.clear();
+
         self.active_regions.clear();
         self.regions.clear();
         self.particle_bins.clear();
@@ -214,26 +508,53 @@ impl ParticleSet {
             .resize(particle_count, ParticleTransferCache::default());
 
         for (idx, particle) in self.particles.iter_mut().enumerate() {
-            let cell_coord = cell_from_position(particle.position, cell_width);
-            if !is_coord_neighborhood_safe(cell_coord) {
-                // TODO: Stream this particle into neighbouring world chunks once open-world paging exists.
-                particle.failed = true;
-                particle.grid_index = u64::MAX;
-                self.active_cells[idx] = u64::MAX;
-                self.transfer_cache[idx] = ParticleTransferCache::default();
-                continue;
+            let cell_coord = wrap_grid_coord_on(
+                cell_from_position(particle.position, cell_width),
+                periodic,
+                resolution,
+            );
+            let cache = &mut self.transfer_cache[idx];
+            populate_transfer_cache(particle.position, kernel, cell_width, cache);
+            // Stencils only need room inside the grid along axes that don't wrap
+            let off_grid = cache.neighbors().iter().any(|&(coord, _, _)| {
+                !is_valid_grid_coord(IVec2::select(periodic, IVec2::ZERO, coord), resolution)
+            });
+            let packed = if off_grid {
+                u64::MAX
+            } else {
+                pack_coords(cell_coord.x, cell_coord.y)
+            };
+            if incremental && self.active_cells[idx] != packed {
+                moved.push(idx);
             }
-
-            let packed = pack_coords(cell_coord.x, cell_coord.y);
             particle.grid_index = packed;
             self.active_cells[idx] = packed;
+            if off_grid {
+                // TODO: Stream this particle into neighbouring world chunks once open-world paging exists.
+                particle.fail(FailureReason::OutOfBounds);
+                *cache = ParticleTransferCache::default();
+                continue;
+            }
 
-            populate_transfer_cache(particle.position, &mut self.transfer_cache[idx]);
+            if periodic.any() {
+                let len = cache.len as usize;
+                for (coord, _, _) in cache.neighbors[..len].iter_mut() {
+                    *coord = wrap_grid_coord_on(*coord, periodic, resolution);
+                }
+            }
         }
 
-        // Simple sort (will be parallel with rayon later)
-        self.order
-            .sort_by_key(|&idx| self.particles[idx].grid_index);
+        if incremental && moved.len() * INCREMENTAL_REBIN_DIVISOR <= particle_count {
+            self.reinsert_moved(&mut moved);
+        } else {
+            self.order.clear();
+            self.order.extend(0..particle_count);
+            // Unstable, so no merge buffer is allocated; the index tie-break
+            // keeps the order the stable sort would give
+            self.order
+                .sort_unstable_by_key(|&idx| (self.particles[idx].grid_index, idx));
+        }
+        self.scratch.moved = moved;
 
         let mut current_region: Option<(PackedCell, usize)> = None;
         let mut current_bin: Option<ParticleBin> = None;
@@ -264,10 +585,10 @@ impl ParticleSet {
             };
 
             if renew_bin {
-                if let Some(bin) = current_bin.take() {
-                    if bin.len > 0 {
-                        self.particle_bins.push(bin);
-                    }
+                if let Some(bin) = current_bin.take()
+                    && bin.len > 0
+                {
+                    self.particle_bins.push(bin);
                 }
                 current_bin = Some(ParticleBin::new(colour));
             }
@@ -277,19 +598,240 @@ impl ParticleSet {
             }
         }
 
-        if let Some(bin) = current_bin {
-            if bin.len > 0 {
-                self.particle_bins.push(bin);
-            }
+        if let Some(bin) = current_bin
+            && bin.len > 0
+        {
+            self.particle_bins.push(bin);
         }
 
         if let Some((cell, start_idx)) = current_region {
             self.regions.push((cell, start_idx..self.order.len()));
             self.active_regions.insert(cell);
         }
+        self.layout = Some(layout);
     }
 
-    fn invalidate_spatial_index(&mut self) {
+    /// Moves the particles in `moved`, whose cell changed, to their new place
+    /// in `order`. Both the rest of the order and the sorted `moved` run by
+    /// cell and then index, exactly as the stable full sort leaves them, so
+    /// one merge restores it.
+    fn reinsert_moved(&mut self, moved: &mut [usize]) {
+        if moved.is_empty() {
+            return;
+        }
+        let cells = &self.active_cells;
+        let key = |idx: usize| (cells[idx], idx);
+        moved.sort_unstable_by_key(|&idx| key(idx));
+
+        let RebinScratch { is_moved, kept, .. } = &mut self.scratch;
+        is_moved.clear();
+        is_moved.resize(self.particles.len(), false);
+        for &idx in moved.iter() {
+            is_moved[idx] = true;
+        }
+        kept.clear();
+        kept.extend(self.order.iter().copied().filter(|&idx| !is_moved[idx]));
+
+        self.order.clear();
+        let (mut kept, mut moved) = (kept.iter().peekable(), moved.iter().peekable());
+        while let (Some(&&a), Some(&&b)) = (kept.peek(), moved.peek()) {
+            if key(a) < key(b) {
+                self.order.push(a);
+                kept.next();
+            } else {
+                self.order.push(b);
+                moved.next();
+            }
+        }
+        self.order.extend(kept.chain(moved));
+    }
+
+    /// Heap bytes reserved by the spatial index, the transfer caches and the
+    /// rebinning scratch (not the particles themselves). Roughly, for the
+    /// index set.
+    pub fn index_heap_bytes(&self) -> usize {
+        use std::mem::size_of;
+        self.order.capacity() * size_of::<usize>()
+            + self.regions.capacity() * size_of::<(PackedCell, Range<usize>)>()
+            + self.active_regions.capacity() * size_of::<PackedCell>()
+            + self.active_cells.capacity() * size_of::<PackedCell>()
+            + self.particle_bins.capacity() * size_of::<ParticleBin>()
+            + self.transfer_cache.capacity() * size_of::<ParticleTransferCache>()
+            + (self.scratch.moved.capacity() + self.scratch.kept.capacity()) * size_of::<usize>()
+            + self.scratch.is_moved.capacity()
+    }
+
+    /// Indices of the live particles within `radius` of `center`, in no
+    /// particular order.
+    ///
+    /// Only the cells overlapping the disk are visited; see
+    /// `binned_candidates`. Until the bins are rebuilt after an insertion or
+    /// removal this falls back to checking every particle.
+    pub fn query_radius(&self, center: Vector, radius: Real) -> Vec<usize> {
+        let within = |idx: &usize| {
+            let particle = &self.particles[*idx];
+            !particle.failed && (particle.position - center).norm_squared() <= radius * radius
+        };
+        let extent = Vector::new(radius, radius);
+        match self.binned_candidates(center - extent, center + extent) {
+            Some(candidates) => candidates.filter(within).collect(),
+            None => (0..self.particles.len()).filter(within).collect(),
+        }
+    }
+
+    /// Indices of the live particles inside the box from `min` to `max`
+    /// (inclusive), in no particular order. An empty or inverted box selects
+    /// nothing.
+    pub fn query_aabb(&self, min: Vector, max: Vector) -> Vec<usize> {
+        self.query_aabb_iter(min, max).collect()
+    }
+
+    /// Allocation-free `query_aabb`.
+    pub fn query_aabb_iter(&self, min: Vector, max: Vector) -> impl Iterator<Item = usize> + '_ {
+        let inside = move |idx: &usize| {
+            let particle = &self.particles[*idx];
+            let position = particle.position;
+            !particle.failed
+                && (min.x..=max.x).contains(&position.x)
+                && (min.y..=max.y).contains(&position.y)
+        };
+        // Also catches NaN corners
+        let empty = !(min.x <= max.x && min.y <= max.y);
+        let binned = if empty {
+            None
+        } else {
+            self.binned_candidates(min, max)
+        };
+        let scan = (!empty && binned.is_none()).then(|| 0..self.particles.len());
+        binned
+            .into_iter()
+            .flatten()
+            .chain(scan.into_iter().flatten())
+            .filter(inside)
+    }
+
+    /// Closest live particle to `point` and its distance, the lowest index
+    /// on ties; `None` when there is no live particle.
+    ///
+    /// Searches outward one ring of cells at a time and stops once no
+    /// unvisited ring can hold anything closer, allowing for however far
+    /// particles have moved out of the cells they were binned into. Until the
+    /// bins are rebuilt after an insertion or removal this checks every
+    /// particle instead.
+    pub fn nearest(&self, point: Vector) -> Option<(usize, Real)> {
+        let closer = |a: &(usize, Real), b: &(usize, Real)| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0));
+        let live = |&idx: &usize| !self.particles[idx].failed;
+        let measure = |idx: usize| (idx, (self.particles[idx].position - point).norm());
+        let Some(layout) = self.layout else {
+            return (0..self.particles.len())
+                .filter(live)
+                .map(measure)
+                .min_by(closer);
+        };
+
+        // Particles stay within `drift` of the domain, so none is closer to
+        // `point` than to its projection onto the padded domain
+        let cell_width = layout.cell_width;
+        let drift = self.drift_since_binning(cell_width);
+        let size = layout.resolution as Real * cell_width;
+        let projected = point.map(|x| x.clamp(-drift, size + drift));
+        let center = cell_from_position(projected, cell_width);
+        let last = layout.resolution as i32 - 1;
+        let reach = (drift / cell_width).ceil() as i32;
+
+        let mut best: Option<(usize, Real)> = None;
+        for ring in 0..=layout.resolution as i32 + reach {
+            // A particle binned `ring` cells out lies at least `ring - 1`
+            // cells away, less however far it has drifted since binning
+            if let Some((_, distance)) = best
+                && distance <= (ring - 1) as Real * cell_width - drift
+            {
+                break;
+            }
+            for cell in ring_cells(center, ring) {
+                if cell.x < 0 || cell.y < 0 || cell.x > last || cell.y > last {
+                    continue;
+                }
+                let Some(region) = self
+                    .active_regions
+                    .get_index_of(&pack_coords(cell.x, cell.y))
+                else {
+                    continue;
+                };
+                let range = self.regions[region].1.clone();
+                for candidate in self.order[range].iter().copied().filter(live).map(measure) {
+                    if best.is_none_or(|best| closer(&candidate, &best).is_lt()) {
+                        best = Some(candidate);
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Furthest any live particle sits outside the cell it was last binned
+    /// into.
+    fn drift_since_binning(&self, cell_width: Real) -> Real {
+        self.particles
+            .iter()
+            .filter(|particle| !particle.failed && particle.grid_index != u64::MAX)
+            .map(|particle| {
+                let cell = unpack_to_ivec(particle.grid_index);
+                let low = Vector::new(cell.x as Real, cell.y as Real) * cell_width;
+                let high = low.add_scalar(cell_width);
+                let below = (low - particle.position).map(|x| x.max(0.0));
+                let above = (particle.position - high).map(|x| x.max(0.0));
+                (below + above).norm()
+            })
+            .fold(0.0, Real::max)
+    }
+
+    /// Particles binned into the cells overlapping the box from `min` to
+    /// `max`, padded by one cell since particles keep moving after they are
+    /// binned (less than a cell per substep). `None` when the bins are stale
+    /// or the box is wider than a periodic domain, which would visit wrapped
+    /// cells twice; callers then scan every particle instead.
+    fn binned_candidates(
+        &self,
+        min: Vector,
+        max: Vector,
+    ) -> Option<impl Iterator<Item = usize> + '_> {
+        let layout = self.layout?;
+        let last = layout.resolution as i32 - 1;
+        let cell_bounds = |low: Real, high: Real, periodic: bool| {
+            let low = (low / layout.cell_width - 1.0).floor() as i32;
+            let high = (high / layout.cell_width + 1.0).floor() as i32;
+            if periodic {
+                let span = high as i64 - low as i64 + 1;
+                (span < layout.resolution as i64).then_some((low, high))
+            } else {
+                // Particles are only ever binned on the grid
+                Some((low.max(0), high.min(last)))
+            }
+        };
+        let (min_x, max_x) = cell_bounds(min.x, max.x, layout.periodic.x)?;
+        let (min_y, max_y) = cell_bounds(min.y, max.y, layout.periodic.y)?;
+
+        let cells = (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)));
+        Some(
+            cells
+                .filter_map(move |(x, y)| {
+                    let cell =
+                        wrap_grid_coord_on(IVec2::new(x, y), layout.periodic, layout.resolution);
+                    let region = self
+                        .active_regions
+                        .get_index_of(&pack_coords(cell.x, cell.y))?;
+                    Some(&self.order[self.regions[region].1.clone()])
+                })
+                .flatten()
+                .copied(),
+        )
+    }
+
+    /// Forgets the bins, so queries scan every particle and the next
+    /// `rebuild_bins` sorts from scratch.
+    pub fn invalidate_spatial_index(&mut self) {
+        self.layout = None;
         self.order.clear();
         self.regions.clear();
         self.active_regions.clear();
@@ -297,3 +839,30 @@ impl ParticleSet {
         self.particle_bins.clear();
     }
 }
+
+/// Points the age order at the indices `mapping` moved particles to,
+/// dropping removed ones.
+fn remap_age_order(age_order: &mut VecDeque<usize>, mapping: &[Option<usize>]) {
+    age_order.retain_mut(|index| match mapping[*index] {
+        Some(new_index) => {
+            *index = new_index;
+            true
+        }
+        None => false,
+    });
+}
+
+/// Cells exactly `ring` steps from `center` in the chessboard metric.
+fn ring_cells(center: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    let rows = [-ring, ring]
+        .into_iter()
+        .take(if ring == 0 { 1 } else { 2 });
+    let horizontal = rows.flat_map(move |dy| (-ring..=ring).map(move |dx| IVec2::new(dx, dy)));
+    let columns = [-ring, ring]
+        .into_iter()
+        .take(if ring == 0 { 0 } else { 2 });
+    let vertical = columns.flat_map(move |dx| (1 - ring..ring).map(move |dy| IVec2::new(dx, dy)));
+    horizontal
+        .chain(vertical)
+        .map(move |offset| center + offset)
+}
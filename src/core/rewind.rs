@@ -0,0 +1,232 @@
+//! In-memory history for stepping a simulation backwards.
+//!
+//! A `RewindBuffer` keeps every particle whole, so a restored frame carries
+//! all of their dynamic state: velocity gradients, plasticity and hardening,
+//! temperature and latent heat, phase changes, age and sleep. The grid is
+//! rebuilt from the particles every step, so it needs no copy. The particle
+//! count has to be unchanged since, so a frame recorded before particles
+//! were added or removed can't be restored.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::mpm_state::MpmState;
+use super::particle::Particle;
+
+/// The particles of one recorded frame, by particle index.
+#[derive(Clone, Default)]
+pub struct RewindFrame {
+    pub particles: Vec<Particle>,
+}
+
+impl RewindFrame {
+    fn capture(state: &MpmState) -> Self {
+        Self {
+            particles: state.particles().to_vec(),
+        }
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+}
+
+/// The last `capacity` frames, recorded by `record_rewind` when present.
+/// Older frames are dropped first, both past `capacity` and once the frames
+/// held add up to more than `particle_budget` particles; a frame with more
+/// particles than the whole budget isn't recorded at all.
+#[derive(Resource, Clone)]
+pub struct RewindBuffer {
+    /// Oldest first.
+    frames: VecDeque<RewindFrame>,
+    capacity: usize,
+    particle_budget: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            particle_budget: usize::MAX,
+        }
+    }
+
+    /// Most particle states kept across all frames.
+    pub fn with_particle_budget(mut self, particle_budget: usize) -> Self {
+        self.particle_budget = particle_budget;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Records `state` as the newest frame.
+    pub fn record(&mut self, state: &MpmState) {
+        if self.capacity == 0 || state.particle_count() > self.particle_budget {
+            return;
+        }
+        self.frames.push_back(RewindFrame::capture(state));
+        let mut stored: usize = self.frames.iter().map(RewindFrame::particle_count).sum();
+        while self.frames.len() > self.capacity || stored > self.particle_budget {
+            let Some(oldest) = self.frames.pop_front() else {
+                break;
+            };
+            stored -= oldest.particle_count();
+        }
+    }
+
+    /// The frame recorded `frames_back` frames before the newest (0 is the
+    /// newest).
+    pub fn frame(&self, frames_back: usize) -> Option<&RewindFrame> {
+        let index = self.frames.len().checked_sub(frames_back + 1)?;
+        self.frames.get(index)
+    }
+}
+
+impl MpmState {
+    /// Puts the particles back as they were `frames_back` frames before the
+    /// newest one in `rewind`, and drops the frames after it, so rewinding by
+    /// one again steps back another frame. Returns false, changing nothing,
+    /// when there is no such frame or the particle count has changed since.
+    pub fn restore_from_rewind(&mut self, rewind: &mut RewindBuffer, frames_back: usize) -> bool {
+        let Some(frame) = rewind.frame(frames_back) else {
+            return false;
+        };
+        if frame.particle_count() != self.particle_count() {
+            return false;
+        }
+
+        self.particles_mut().clone_from_slice(&frame.particles);
+        self.particle_set_mut().refresh_previous_velocities();
+        self.particle_set_mut().refresh_age_order();
+        let kept = rewind.frames.len() - frames_back;
+        rewind.frames.truncate(kept);
+        self.rebuild_particle_bins();
+        true
+    }
+}
+
+/// Records the frame into `RewindBuffer` when present.
+pub fn record_rewind(state: Res<MpmState>, rewind: Option<ResMut<RewindBuffer>>) {
+    if let Some(mut rewind) = rewind {
+        rewind.record(&state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::core::Particle;
+    use crate::materials::{FluidParams, MaterialType};
+    use crate::math::{Matrix, Real, Vector};
+    use crate::solver::grid_to_particle;
+    use crate::test_support::{frame_world, solver_schedule};
+
+    #[test]
+    fn rewinding_three_of_ten_frames_restores_frame_seven() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..8 {
+            for i in 0..8 {
+                let position = Vector::new(60.25, 40.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.insert_resource(RewindBuffer::new(5));
+        let mut schedule = solver_schedule();
+        schedule.add_systems(record_rewind.after(grid_to_particle));
+
+        let positions = |world: &World| -> Vec<Vector> {
+            let particles = world.resource::<MpmState>().particles();
+            particles.iter().map(|particle| particle.position).collect()
+        };
+        let mut frame_seven = Vec::new();
+        for frame in 1..=10 {
+            schedule.run(&mut world);
+            if frame == 7 {
+                frame_seven = positions(&world);
+            }
+        }
+        assert_eq!(world.resource::<RewindBuffer>().len(), 5);
+        assert_ne!(positions(&world), frame_seven);
+
+        world.resource_scope(|world, mut rewind: Mut<RewindBuffer>| {
+            let mut state = world.resource_mut::<MpmState>();
+            assert!(!state.restore_from_rewind(&mut rewind, 5));
+            assert!(state.restore_from_rewind(&mut rewind, 3));
+        });
+        assert_eq!(positions(&world), frame_seven);
+        assert_eq!(world.resource::<RewindBuffer>().len(), 2);
+    }
+
+    #[test]
+    fn a_restored_frame_brings_back_the_whole_particle_state() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particle(Particle::new(
+            Vector::new(40.0, 40.0),
+            MaterialType::water(),
+        ));
+        let mut rewind = RewindBuffer::new(4);
+        rewind.record(&state);
+
+        {
+            let particle = &mut state.particles_mut()[0];
+            particle.position = Vector::new(50.0, 30.0);
+            particle.velocity_gradient = Matrix::new(1.0, 2.0, 3.0, 4.0);
+            particle.plastic_deformation_gradient_det = 0.5;
+            particle.plasticity.plastic_hardening = 3.0;
+            particle.temperature = 80.0;
+            particle.stored_latent_heat = 12.0;
+            particle.material_type = MaterialType::fluid(FluidParams::honey());
+            particle.age = 9.0;
+            particle.sleeping = true;
+            particle.idle_steps = 30;
+        }
+        rewind.record(&state);
+        state.particles_mut()[0].temperature = -5.0;
+
+        assert!(state.restore_from_rewind(&mut rewind, 1));
+        let particle = &state.particles()[0];
+        let fresh = Particle::new(Vector::new(40.0, 40.0), MaterialType::water());
+        assert_eq!(particle.position, fresh.position);
+        assert_eq!(particle.velocity_gradient, fresh.velocity_gradient);
+        assert_eq!(
+            particle.plastic_deformation_gradient_det,
+            fresh.plastic_deformation_gradient_det
+        );
+        assert_eq!(particle.plasticity, fresh.plasticity);
+        assert_eq!(particle.temperature, fresh.temperature);
+        assert_eq!(particle.stored_latent_heat, fresh.stored_latent_heat);
+        assert_eq!(particle.material_type.material_name(), "water");
+        assert_eq!(particle.age, fresh.age);
+        assert!(!particle.sleeping);
+        assert_eq!(particle.idle_steps, 0);
+    }
+
+    #[test]
+    fn the_particle_budget_drops_the_oldest_frames() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for i in 0..10 {
+            let position = Vector::new(40.0 + i as Real, 40.0);
+            state.add_particle(Particle::new(position, MaterialType::water()));
+        }
+        let mut rewind = RewindBuffer::new(8).with_particle_budget(35);
+        for _ in 0..5 {
+            rewind.record(&state);
+        }
+        assert_eq!(rewind.len(), 3);
+    }
+}
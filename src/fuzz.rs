@@ -0,0 +1,158 @@
+//! Randomised scene generation for fuzzing the solver
+//!
+//! Scenes are always valid inputs: positions stay inside the safe interior of
+//! the grid, velocities are finite and every particle carries a fluid
+//! material. Every draw comes from a `SimRng` seeded through
+//! `DeterministicConfig`, and positions from `geometry::poisson_disk_fill`,
+//! so the same seed always produces the same scene.
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::config::{DeterministicConfig, GRAVITY, SolverParams};
+use crate::core::{GRID_RESOLUTION, MpmState, Particle};
+use crate::geometry::{RectRegion, poisson_disk_fill};
+use crate::materials::{FluidParams, MaterialType};
+use crate::math::{Real, Vector};
+
+/// Cells kept free between spawned particles and the domain edge so every
+/// particle's 3x3 stencil stays inside the grid.
+pub const DOMAIN_MARGIN: Real = 4.0;
+
+/// Largest velocity component assigned to a fuzzed particle.
+pub const MAX_SPEED: Real = 20.0;
+
+/// Random scene of `n` particles filling the whole safe interior of the grid.
+pub fn random_scene(seed: u64, n: usize) -> MpmState {
+    let max = GRID_RESOLUTION as Real - DOMAIN_MARGIN;
+    random_scene_in_region(
+        seed,
+        n,
+        Vector::new(DOMAIN_MARGIN, DOMAIN_MARGIN),
+        Vector::new(max, max),
+    )
+}
+
+/// Random scene of `n` particles seeded inside the `[min, max]` box.
+///
+/// The box is clipped to the safe interior; a box without area collapses to
+/// a single point, which is still a valid (if stiff) input.
+pub fn random_scene_in_region(seed: u64, n: usize, min: Vector, max: Vector) -> MpmState {
+    let mut rng = DeterministicConfig::new(seed).rng();
+    let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+
+    let limit = GRID_RESOLUTION as Real - DOMAIN_MARGIN;
+    let lo = min.map(|v| v.clamp(DOMAIN_MARGIN, limit));
+    let hi = max.map(|v| v.clamp(DOMAIN_MARGIN, limit));
+    let region = RectRegion::new(lo.inf(&hi), lo.sup(&hi));
+
+    // Spaced to fit well over `n` samples, then shuffled so the `n` kept
+    // spread over the whole box rather than around where sampling started
+    let size = region.max - region.min;
+    let spacing = 0.5 * (size.x * size.y / n as Real).sqrt();
+    let mut positions = poisson_disk_fill(&region, spacing, &mut *rng);
+    positions.shuffle(&mut *rng);
+    if positions.is_empty() {
+        positions.push(region.min);
+    }
+
+    for position in positions.into_iter().cycle().take(n) {
+        let params = FluidParams::new(
+            "fuzz",
+            rng.random_range(0.5..=4.0),
+            rng.random_range(0.5..=8.0),
+            rng.random_range(1..=7),
+        );
+
+        let velocity = Vector::new(
+            rng.random_range(-MAX_SPEED..=MAX_SPEED),
+            rng.random_range(-MAX_SPEED..=MAX_SPEED),
+        );
+
+        state.add_particle(
+            Particle::new(position, MaterialType::fluid(params)).with_velocity(velocity),
+        );
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::core::update_particles_health;
+    use crate::solver::grid_to_particle;
+    use crate::test_support::run_frames_with;
+
+    /// Steps `state` through `frames` frames, with or without the health
+    /// check failing particles after each step.
+    fn step(state: MpmState, frames: usize, health_checks: bool) -> World {
+        run_frames_with(state, frames, |_, schedule| {
+            if health_checks {
+                let check = |mut state: ResMut<MpmState>| {
+                    let threshold = state.solver_params().condition_threshold;
+                    update_particles_health(state.particles_mut(), threshold);
+                };
+                schedule.add_systems(check.after(grid_to_particle));
+            }
+        })
+    }
+
+    /// Particles that went non-finite without being failed for it.
+    fn unflagged_non_finite(state: &MpmState) -> Vec<usize> {
+        let finite = |particle: &Particle| {
+            particle.position.iter().all(|v| v.is_finite())
+                && particle.velocity.iter().all(|v| v.is_finite())
+                && particle.deformation_gradient.iter().all(|v| v.is_finite())
+        };
+        let particles = state.particles().iter().enumerate();
+        particles
+            .filter(|(_, particle)| !particle.failed && !finite(particle))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    #[test]
+    fn random_scenes_stay_finite_over_ten_steps() {
+        for seed in 0..50 {
+            let world = step(random_scene(seed, 64), 10, true);
+            let unflagged = unflagged_non_finite(world.resource::<MpmState>());
+            assert!(
+                unflagged.is_empty(),
+                "seed {seed}: {unflagged:?} went non-finite"
+            );
+        }
+    }
+
+    #[test]
+    fn a_poisoned_scene_is_caught_only_by_the_health_check() {
+        let poisoned = || {
+            let mut state = random_scene(0, 64);
+            state.particles_mut()[0].velocity.x = Real::NAN;
+            state
+        };
+
+        // Unchecked, one step spreads the NaN through the grid to the
+        // neighbours, and nothing fails them before the next rebinning
+        let world = step(poisoned(), 1, false);
+        assert!(!unflagged_non_finite(world.resource::<MpmState>()).is_empty());
+
+        let world = step(poisoned(), 1, true);
+        let state = world.resource::<MpmState>();
+        assert!(unflagged_non_finite(state).is_empty());
+        assert!(state.particles()[0].failed);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_scene() {
+        let positions = |seed| -> Vec<Vector> {
+            let state = random_scene(seed, 64);
+            state.particles().iter().map(|p| p.position).collect()
+        };
+        assert_eq!(positions(3), positions(3));
+        assert_ne!(positions(3), positions(4));
+        assert_eq!(random_scene(3, 64).particle_count(), 64);
+    }
+}
@@ -0,0 +1,755 @@
+//! Static obstacles described by signed distance fields.
+//!
+//! Colliders act on the grid after gravity and the domain walls: every active
+//! node inside a collider loses the part of its velocity heading into it, the
+//! same one-sided slip the domain walls use, bouncing back the share of it
+//! set by `Colliders::with_restitution`. Moving colliders hand their normal
+//! speed to the nodes they sweep over, so paddles push. Particles crossing
+//! into a collider are reported once per stay through
+//! `ParticleEnteredCollider`.
+//!
+//! Each collider has a collision mask (every layer by default). It only acts
+//! on nodes holding material whose `Particle::collision_mask` shares a layer
+//! with it, and only reports those particles entering, so a ghost fluid can
+//! pass through a gate the rest of the scene can't.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::core::{
+    Grid, MpmState, ParticleContact, ParticleRemap, add_rebound, node_center, project_slip_moving,
+};
+use crate::math::{Real, Vector, zero_vector};
+
+/// A static obstacle described by its signed distance field.
+pub trait Collider: Send + Sync + 'static {
+    /// Signed distance from `p` to the surface, negative inside.
+    fn sdf(&self, p: Vector) -> Real;
+
+    /// Outward unit normal of the surface closest to `p`.
+    fn normal(&self, p: Vector) -> Vector;
+
+    /// Velocity the surface moves at; static colliders keep zero.
+    fn velocity(&self) -> Vector {
+        zero_vector()
+    }
+
+    /// Moves the collider forward by `dt`; static colliders stay put.
+    fn advance(&mut self, _dt: Real) {}
+}
+
+/// Solid disc.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CircleCollider {
+    pub center: Vector,
+    pub radius: Real,
+}
+
+impl CircleCollider {
+    pub fn new(center: Vector, radius: Real) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl Collider for CircleCollider {
+    fn sdf(&self, p: Vector) -> Real {
+        (p - self.center).norm() - self.radius
+    }
+
+    fn normal(&self, p: Vector) -> Vector {
+        // Straight up from the dead centre, where every direction is as close
+        (p - self.center)
+            .try_normalize(Real::EPSILON)
+            .unwrap_or_else(|| Vector::new(0.0, 1.0))
+    }
+}
+
+/// Everything on the far side of a line through `origin`; `normal` points
+/// away from the solid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HalfPlaneCollider {
+    pub origin: Vector,
+    pub normal: Vector,
+}
+
+impl HalfPlaneCollider {
+    pub fn new(origin: Vector, normal: Vector) -> Self {
+        Self {
+            origin,
+            normal: normal.normalize(),
+        }
+    }
+}
+
+impl Collider for HalfPlaneCollider {
+    fn sdf(&self, p: Vector) -> Real {
+        (p - self.origin).dot(&self.normal)
+    }
+
+    fn normal(&self, _p: Vector) -> Vector {
+        self.normal
+    }
+}
+
+/// Solid axis-aligned box. Inside, the normal points out through the nearest
+/// face, so material is pushed out along the axis it penetrated least.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AabbCollider {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl AabbCollider {
+    pub fn new(min: Vector, max: Vector) -> Self {
+        Self { min, max }
+    }
+
+    /// Per-axis distance outside the box faces (negative inside) and the
+    /// offset from the box centre.
+    fn face_distances(&self, p: Vector) -> (Vector, Vector) {
+        let center = (self.min + self.max) * 0.5;
+        let half_extents = (self.max - self.min) * 0.5;
+        let offset = p - center;
+        (offset.abs() - half_extents, offset)
+    }
+}
+
+impl Collider for AabbCollider {
+    fn sdf(&self, p: Vector) -> Real {
+        let (distances, _) = self.face_distances(p);
+        let outside = distances.map(|d| d.max(0.0)).norm();
+        let inside = distances.x.max(distances.y).min(0.0);
+        outside + inside
+    }
+
+    fn normal(&self, p: Vector) -> Vector {
+        let (distances, offset) = self.face_distances(p);
+        let sign = offset.map(|o| if o < 0.0 { -1.0 } else { 1.0 });
+        if distances.x > 0.0 || distances.y > 0.0 {
+            let outward = distances.map(|d| d.max(0.0)).component_mul(&sign);
+            return outward.normalize();
+        }
+        if distances.x > distances.y {
+            Vector::new(sign.x, 0.0)
+        } else {
+            Vector::new(0.0, sign.y)
+        }
+    }
+}
+
+/// Terrain: solid below a height profile. `samples` are heights at evenly
+/// spaced x across `x_range`, joined by straight segments; past either end
+/// the outermost height carries on flat.
+///
+/// The distance is measured square to the segment under `p`, exact away
+/// from the kinks. The normal follows the slope interpolated between the
+/// samples, so it turns smoothly through a valley instead of flipping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightfieldCollider {
+    pub samples: Vec<Real>,
+    pub x_range: (Real, Real),
+}
+
+impl HeightfieldCollider {
+    /// `x_range` runs from the first sample's x to the last's.
+    pub fn new(samples: Vec<Real>, x_range: (Real, Real)) -> Self {
+        Self { samples, x_range }
+    }
+
+    /// Segment under `x` as `(index, fraction along it, sample spacing)`;
+    /// `None` past either end or with fewer than two samples.
+    fn segment(&self, x: Real) -> Option<(usize, Real, Real)> {
+        let segments = self.samples.len().checked_sub(1).filter(|&n| n > 0)?;
+        let (start, end) = self.x_range;
+        let spacing = (end - start) / segments as Real;
+        let along = (x - start) / spacing;
+        if !(0.0..=segments as Real).contains(&along) {
+            return None;
+        }
+        let index = (along.floor() as usize).min(segments - 1);
+        Some((index, along - index as Real, spacing))
+    }
+
+    fn segment_slope(&self, index: usize, spacing: Real) -> Real {
+        (self.samples[index + 1] - self.samples[index]) / spacing
+    }
+
+    /// Slope at sample `index`: the mean of the segments either side.
+    fn sample_slope(&self, index: usize, spacing: Real) -> Real {
+        let last = self.samples.len() - 2;
+        let before = self.segment_slope(index.saturating_sub(1), spacing);
+        let after = self.segment_slope(index.min(last), spacing);
+        0.5 * (before + after)
+    }
+
+    /// Height and slope of the terrain at `x`.
+    fn height_and_slope(&self, x: Real) -> (Real, Real) {
+        if let Some((index, fraction, spacing)) = self.segment(x) {
+            let (low, high) = (self.samples[index], self.samples[index + 1]);
+            return (low + (high - low) * fraction, (high - low) / spacing);
+        }
+        // Flat past the ends; no samples at all is no terrain
+        let outermost = if x < self.x_range.0 {
+            self.samples.first()
+        } else {
+            self.samples.last()
+        };
+        (outermost.copied().unwrap_or(Real::NEG_INFINITY), 0.0)
+    }
+}
+
+impl Collider for HeightfieldCollider {
+    fn sdf(&self, p: Vector) -> Real {
+        let (height, slope) = self.height_and_slope(p.x);
+        (p.y - height) / (1.0 + slope * slope).sqrt()
+    }
+
+    fn normal(&self, p: Vector) -> Vector {
+        let slope = match self.segment(p.x) {
+            Some((index, fraction, spacing)) => {
+                let low = self.sample_slope(index, spacing);
+                let high = self.sample_slope(index + 1, spacing);
+                low + (high - low) * fraction
+            }
+            None => 0.0,
+        };
+        Vector::new(-slope, 1.0).normalize()
+    }
+}
+
+/// Any collider translated at a constant `velocity`, e.g. a paddle or piston.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KinematicCollider<C> {
+    pub collider: C,
+    pub velocity: Vector,
+    /// Distance travelled so far.
+    pub offset: Vector,
+}
+
+impl<C: Collider> KinematicCollider<C> {
+    pub fn new(collider: C, velocity: Vector) -> Self {
+        Self {
+            collider,
+            velocity,
+            offset: zero_vector(),
+        }
+    }
+}
+
+impl<C: Collider> Collider for KinematicCollider<C> {
+    fn sdf(&self, p: Vector) -> Real {
+        self.collider.sdf(p - self.offset)
+    }
+
+    fn normal(&self, p: Vector) -> Vector {
+        self.collider.normal(p - self.offset)
+    }
+
+    fn velocity(&self) -> Vector {
+        self.velocity
+    }
+
+    fn advance(&mut self, dt: Real) {
+        self.offset += self.velocity * dt;
+    }
+}
+
+/// Sent when a particle moves into a collider it was not inside last frame.
+#[derive(Message, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParticleEnteredCollider {
+    pub index: usize,
+    pub collider_id: usize,
+}
+
+/// Obstacles `grid_update` keeps the material out of.
+#[derive(Resource, Default)]
+pub struct Colliders {
+    colliders: Vec<Box<dyn Collider>>,
+    /// Collision mask of each collider, by id.
+    masks: Vec<u32>,
+    /// `(particle index, collider id)` pairs inside as of the last
+    /// `detect_collider_entries` run.
+    inside: HashSet<(usize, usize)>,
+    /// Share of the closing speed nodes bounce back with (0 absorbs it).
+    restitution: Real,
+}
+
+impl Colliders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bounces `restitution` of the speed material hits a collider with
+    /// back off it, 0 absorbing it all and 1 bouncing perfectly.
+    pub fn with_restitution(mut self, restitution: Real) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn restitution(&self) -> Real {
+        self.restitution
+    }
+
+    /// Adds `collider` on every collision layer and returns its id (its
+    /// index in this set).
+    pub fn add(&mut self, collider: impl Collider) -> usize {
+        self.add_with_mask(collider, u32::MAX)
+    }
+
+    /// Adds `collider` acting only on material sharing a layer with
+    /// `collision_mask`, and returns its id.
+    pub fn add_with_mask(&mut self, collider: impl Collider, collision_mask: u32) -> usize {
+        self.colliders.push(Box::new(collider));
+        self.masks.push(collision_mask);
+        self.colliders.len() - 1
+    }
+
+    /// Collision mask of the collider with `id`.
+    pub fn collision_mask(&self, id: usize) -> Option<u32> {
+        self.masks.get(id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.colliders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colliders.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Collider> {
+        self.colliders.iter().map(Box::as_ref)
+    }
+
+    /// Moves every kinematic collider forward by `dt`.
+    pub fn advance(&mut self, dt: Real) {
+        for collider in &mut self.colliders {
+            collider.advance(dt);
+        }
+    }
+
+    /// Contact with the deepest collider containing `p`, if any.
+    pub fn contact(&self, p: Vector) -> Option<ParticleContact> {
+        self.deepest(p, u32::MAX)
+            .map(|(collider, distance)| ParticleContact {
+                boundary_normal: collider.normal(p),
+                boundary_distance: distance,
+            })
+    }
+
+    /// Deepest collider containing `p` among those sharing a layer with
+    /// `collision_mask`.
+    fn deepest(&self, p: Vector, collision_mask: u32) -> Option<(&dyn Collider, Real)> {
+        self.colliders
+            .iter()
+            .zip(&self.masks)
+            .filter(|&(_, &mask)| mask & collision_mask != 0)
+            .map(|(collider, _)| (collider.as_ref(), collider.sdf(p)))
+            .filter(|&(_, distance)| distance < 0.0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Updates which particles, given as `(position, collision mask)`, are
+    /// inside which collider they share a layer with and returns the pairs
+    /// that were not inside before. `remap` carries the previous indices
+    /// over removals since the last call.
+    pub fn update_membership(
+        &mut self,
+        particles: impl Iterator<Item = (Vector, u32)>,
+        remap: &[Option<usize>],
+    ) -> Vec<ParticleEnteredCollider> {
+        let previous = std::mem::take(&mut self.inside);
+        let previous: HashSet<(usize, usize)> = if remap.is_empty() {
+            previous
+        } else {
+            previous
+                .into_iter()
+                .filter_map(|(index, id)| Some((remap.get(index).copied().flatten()?, id)))
+                .collect()
+        };
+
+        let mut entered = Vec::new();
+        for (index, (position, collision_mask)) in particles.enumerate() {
+            let colliders = self.colliders.iter().zip(&self.masks).enumerate();
+            for (collider_id, (collider, &mask)) in colliders {
+                if mask & collision_mask == 0 || collider.sdf(position) >= 0.0 {
+                    continue;
+                }
+                self.inside.insert((index, collider_id));
+                if !previous.contains(&(index, collider_id)) {
+                    entered.push(ParticleEnteredCollider { index, collider_id });
+                }
+            }
+        }
+        entered
+    }
+
+    /// Removes the velocity closing in on a collider from every active node
+    /// inside one sharing a layer with the node's material, less what bounces
+    /// back; nodes inside a moving collider take on its normal speed.
+    pub fn project_grid(&self, grid: &mut Grid) {
+        if self.is_empty() {
+            return;
+        }
+        let cell_width = grid.cell_width();
+        grid.for_each_node_mut(|coord, node| {
+            let position = node_center(coord, cell_width);
+            let Some((collider, _)) = self.deepest(position, node.collision_mask) else {
+                return;
+            };
+            let normal = collider.normal(position);
+            let surface_velocity = collider.velocity();
+            let closing_speed = (node.velocity - surface_velocity).dot(&normal);
+            let projected = project_slip_moving(node.velocity, normal, surface_velocity);
+            node.velocity = add_rebound(projected, normal, closing_speed, self.restitution);
+        });
+    }
+}
+
+impl MpmState {
+    /// Two-way coupling hook for a rigid body simulated elsewhere.
+    ///
+    /// Projects every grid node with mass inside `collider` the way
+    /// `Colliders::project_grid` does, against a surface moving at
+    /// `body_velocity`, and returns the impulse the fluid exerted on the body
+    /// (the momentum the nodes lost). The caller applies it and integrates
+    /// the body; call this between `grid_update` and `grid_to_particle`.
+    pub fn apply_rigid_body(&mut self, collider: &dyn Collider, body_velocity: Vector) -> Vector {
+        let grid = self.grid_mut();
+        let cell_width = grid.cell_width();
+        let mut impulse = zero_vector();
+        grid.for_each_node_mut(|coord, node| {
+            let position = node_center(coord, cell_width);
+            if node.mass <= 0.0 || collider.sdf(position) >= 0.0 {
+                return;
+            }
+            let normal = collider.normal(position);
+            let projected = project_slip_moving(node.velocity, normal, body_velocity);
+            impulse += (node.velocity - projected) * node.mass;
+            node.velocity = projected;
+        });
+        impulse
+    }
+}
+
+/// Reports particles entering a collider. Runs after particle removal so the
+/// frame's `ParticleRemap` is complete.
+pub fn detect_collider_entries(
+    state: Res<MpmState>,
+    remap: Res<ParticleRemap>,
+    mut colliders: ResMut<Colliders>,
+    mut entered: MessageWriter<ParticleEnteredCollider>,
+) {
+    if colliders.is_empty() {
+        return;
+    }
+    let particles = state
+        .particles()
+        .iter()
+        .map(|particle| (particle.position, particle.collision_mask));
+    entered.write_batch(colliders.update_membership(particles, &remap.map));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::core::{MpmState, Particle};
+    use crate::materials::MaterialType;
+    use crate::solver::{grid_to_particle, grid_update};
+    use crate::test_support::{frame_world, run_frames_with, solver_schedule};
+
+    #[test]
+    fn aabb_pushes_out_through_the_nearest_face() {
+        let crate_box = AabbCollider::new(Vector::new(10.0, 10.0), Vector::new(20.0, 14.0));
+        let near_top = Vector::new(13.0, 13.5);
+        assert_eq!(crate_box.sdf(near_top), -0.5);
+        assert_eq!(crate_box.normal(near_top), Vector::new(0.0, 1.0));
+        let near_left = Vector::new(10.5, 12.0);
+        assert_eq!(crate_box.normal(near_left), Vector::new(-1.0, 0.0));
+        assert_eq!(crate_box.sdf(Vector::new(23.0, 18.0)), 5.0);
+    }
+
+    #[test]
+    fn the_contact_surface_sits_where_the_collider_says_on_a_fine_grid() {
+        // Node 9 is centred at y = 4.75, inside a floor at 5.1; node 10 at
+        // 5.25, above it
+        let mut colliders = Colliders::new();
+        colliders.add(HalfPlaneCollider::new(
+            Vector::new(0.0, 5.1),
+            Vector::new(0.0, 1.0),
+        ));
+        let mut grid = Grid::with_cell_width(0.5);
+        for y in [9, 10] {
+            let node = grid.get_cell_coord_mut(IVec2::new(20, y));
+            node.mass = 1.0;
+            node.collision_mask = u32::MAX;
+            node.velocity = Vector::new(1.0, -2.0);
+        }
+
+        colliders.project_grid(&mut grid);
+
+        let velocity = |y| grid.get_cell_coord(IVec2::new(20, y)).unwrap().velocity;
+        assert_eq!(velocity(9), Vector::new(1.0, 0.0));
+        assert_eq!(velocity(10), Vector::new(1.0, -2.0));
+    }
+
+    #[test]
+    fn a_rigid_body_only_takes_momentum_from_nodes_centred_inside_it() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        *state.grid_mut() = Grid::with_cell_width(0.5);
+        for y in [9, 10] {
+            let node = state.grid_mut().get_cell_coord_mut(IVec2::new(20, y));
+            node.mass = 2.0;
+            node.velocity = Vector::new(0.0, -3.0);
+        }
+        // Its top at 5.1 lies between the centres of nodes 9 and 10
+        let body = AabbCollider::new(Vector::new(0.0, 0.0), Vector::new(20.0, 5.1));
+
+        let impulse = state.apply_rigid_body(&body, zero_vector());
+
+        assert_eq!(impulse, Vector::new(0.0, -6.0));
+        let velocity = |y| {
+            state
+                .grid()
+                .get_cell_coord(IVec2::new(20, y))
+                .unwrap()
+                .velocity
+        };
+        assert_eq!(velocity(9), zero_vector());
+        assert_eq!(velocity(10), Vector::new(0.0, -3.0));
+    }
+
+    #[test]
+    fn one_enter_event_per_continuous_stay() {
+        let mut colliders = Colliders::new();
+        let region = AabbCollider::new(Vector::new(10.0, 10.0), Vector::new(20.0, 20.0));
+        let sensor = colliders.add(region);
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particle(Particle::new(Vector::new(5.0, 15.0), MaterialType::water()));
+
+        let mut world = World::new();
+        world.insert_resource(state);
+        world.insert_resource(colliders);
+        world.insert_resource(ParticleRemap::default());
+        world.init_resource::<Messages<ParticleEnteredCollider>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(detect_collider_entries);
+
+        let mut enter_count = 0;
+        for x in [5.0, 12.0, 15.0, 18.0, 25.0, 15.0, 16.0] {
+            world.resource_mut::<MpmState>().particles_mut()[0]
+                .position
+                .x = x;
+            schedule.run(&mut world);
+            let mut messages = world.resource_mut::<Messages<ParticleEnteredCollider>>();
+            for message in messages.drain() {
+                assert_eq!(
+                    message,
+                    ParticleEnteredCollider {
+                        index: 0,
+                        collider_id: sensor
+                    }
+                );
+                enter_count += 1;
+            }
+        }
+        // In at x = 12, out at 25 and back in at 15
+        assert_eq!(enter_count, 2);
+    }
+
+    #[test]
+    fn a_collider_only_stops_and_reports_material_on_its_layers() {
+        const SOLID_LAYER: u32 = 1 << 0;
+        const GHOST_LAYER: u32 = 1 << 1;
+        let floor = HalfPlaneCollider::new(Vector::new(0.0, 20.0), Vector::new(0.0, 1.0));
+        let mut colliders = Colliders::new();
+        let gate = colliders.add_with_mask(floor, SOLID_LAYER);
+        assert_eq!(colliders.collision_mask(gate), Some(SOLID_LAYER));
+
+        // Two patches far enough apart not to share a node
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for (x, layer) in [(20.25, SOLID_LAYER), (80.25, GHOST_LAYER)] {
+            for j in 0..8 {
+                for i in 0..8 {
+                    let position = Vector::new(x, 30.25) + Vector::new(i as Real, j as Real) * 0.5;
+                    state.add_particle(
+                        Particle::new(position, MaterialType::water()).with_collision_mask(layer),
+                    );
+                }
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.insert_resource(colliders);
+        world.insert_resource(ParticleRemap::default());
+        world.init_resource::<Messages<ParticleEnteredCollider>>();
+        let mut schedule = solver_schedule();
+        schedule.add_systems(detect_collider_entries.after(grid_to_particle));
+        let mut entered = HashSet::new();
+        for _ in 0..60 {
+            schedule.run(&mut world);
+            let mut messages = world.resource_mut::<Messages<ParticleEnteredCollider>>();
+            entered.extend(messages.drain().map(|message| message.index));
+        }
+
+        let particles = world.resource::<MpmState>().particles();
+        for (index, particle) in particles.iter().enumerate() {
+            if particle.collision_mask == SOLID_LAYER {
+                assert!(particle.position.y > 18.5, "{}", particle.position);
+            } else {
+                assert!(particle.position.y < 15.0, "{}", particle.position);
+                assert!(!entered.contains(&index), "ghost {index} reported");
+            }
+        }
+    }
+
+    #[test]
+    fn moving_wall_drags_a_resting_column_sideways() {
+        let paddle = AabbCollider::new(Vector::new(30.0, 0.0), Vector::new(38.0, 30.0));
+        let mut colliders = Colliders::new();
+        colliders.add(KinematicCollider::new(paddle, Vector::new(10.0, 0.0)));
+
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..20 {
+            for i in 0..20 {
+                let position = Vector::new(40.25, 2.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+        let centroid_x = |state: &MpmState| {
+            let particles = state.particles();
+            particles.iter().map(|p| p.position.x).sum::<Real>() / particles.len() as Real
+        };
+        let start = centroid_x(&state);
+
+        let mut world = frame_world(state);
+        world.insert_resource(colliders);
+        let mut schedule = solver_schedule();
+        for _ in 0..90 {
+            schedule.run(&mut world);
+        }
+
+        // The paddle's face has swept from x = 38 to 53
+        let end = centroid_x(world.resource::<MpmState>());
+        assert!(end.is_finite());
+        assert!(end > start + 4.0, "centroid moved from {start} to {end}");
+    }
+
+    #[test]
+    fn water_landing_on_a_floating_box_pushes_it_down() {
+        #[derive(Resource, Default)]
+        struct Reaction(Vector);
+
+        fn couple_box(mut state: ResMut<MpmState>, mut reaction: ResMut<Reaction>) {
+            let floating_box = AabbCollider::new(Vector::new(54.0, 20.0), Vector::new(74.0, 30.0));
+            reaction.0 += state.apply_rigid_body(&floating_box, zero_vector());
+        }
+
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..10 {
+            for i in 0..20 {
+                let position = Vector::new(59.25, 34.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        let world = run_frames_with(state, 60, |world, schedule| {
+            world.init_resource::<Reaction>();
+            schedule.add_systems(couple_box.after(grid_update).before(grid_to_particle));
+        });
+
+        let reaction = world.resource::<Reaction>().0;
+        assert!(reaction.y < -1.0, "the box only felt {reaction}");
+    }
+
+    #[test]
+    fn fluid_dropped_into_a_v_shaped_valley_pools_at_the_bottom() {
+        // Walls rising from y = 10 at x = 64 to y = 60 either side
+        let valley = HeightfieldCollider::new(vec![60.0, 10.0, 60.0], (20.0, 108.0));
+        assert!(valley.sdf(Vector::new(42.0, 35.0)).abs() < 1e-5);
+        assert!(valley.sdf(Vector::new(42.0, 30.0)) < 0.0);
+        assert_eq!(
+            valley.normal(Vector::new(64.0, 12.0)),
+            Vector::new(0.0, 1.0)
+        );
+        let mut colliders = Colliders::new();
+        colliders.add(valley.clone());
+
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..16 {
+            for i in 0..16 {
+                let position = Vector::new(50.25, 50.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.insert_resource(colliders);
+        let mut schedule = solver_schedule();
+
+        let mut deepest: Real = 0.0;
+        for _ in 0..240 {
+            schedule.run(&mut world);
+            let particles = world.resource::<MpmState>().particles();
+            deepest = particles.iter().fold(deepest, |deepest, particle| {
+                deepest.min(valley.sdf(particle.position))
+            });
+        }
+
+        assert!(
+            deepest > -1.5,
+            "particle reached {deepest} into the terrain"
+        );
+        let particles = world.resource::<MpmState>().particles();
+        let centroid =
+            particles.iter().map(|p| p.position).sum::<Vector>() / particles.len() as Real;
+        assert!(
+            (centroid.x - 64.0).abs() < 6.0,
+            "pooled around x = {}",
+            centroid.x
+        );
+        assert!(centroid.y < 25.0, "pooled around y = {}", centroid.y);
+    }
+
+    #[test]
+    fn fluid_poured_onto_a_circle_flows_around_it() {
+        let obstacle = CircleCollider::new(Vector::new(64.0, 30.0), 8.0);
+        let mut colliders = Colliders::new();
+        colliders.add(obstacle);
+
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..20 {
+            for i in 0..16 {
+                let position = Vector::new(60.25, 44.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.insert_resource(colliders);
+        let mut schedule = solver_schedule();
+
+        let mut deepest: Real = 0.0;
+        for _ in 0..120 {
+            schedule.run(&mut world);
+            let particles = world.resource::<MpmState>().particles();
+            deepest = particles.iter().fold(deepest, |deepest, particle| {
+                deepest.min(obstacle.sdf(particle.position))
+            });
+        }
+
+        // Kernel support lets particles graze the surface, but not sink in
+        assert!(
+            deepest > -1.5,
+            "particle reached {deepest} inside the circle"
+        );
+        let particles = world.resource::<MpmState>().particles();
+        assert!(
+            particles
+                .iter()
+                .any(|particle| particle.position.y < obstacle.center.y)
+        );
+    }
+}
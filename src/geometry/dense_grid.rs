@@ -0,0 +1,142 @@
+use super::sp_grid::{PackedCell, pack_coords, unpack_coords};
+
+/// Flat `resolution x resolution` counterpart of `SpGrid`, addressed by the
+/// same packed cell ids.
+///
+/// Every slot is allocated up front; a parallel occupancy flag stands in for
+/// map membership, so lookups are a bounds check and an index.
+#[derive(Clone)]
+pub struct DenseGrid<T> {
+    resolution: usize,
+    cells: Vec<T>,
+    occupied: Vec<bool>,
+    len: usize,
+}
+
+impl<T: Default> DenseGrid<T> {
+    pub fn new(resolution: usize) -> Self {
+        let slots = resolution * resolution;
+        Self {
+            resolution,
+            cells: std::iter::repeat_with(T::default).take(slots).collect(),
+            occupied: vec![false; slots],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn slot(&self, id: PackedCell) -> Option<usize> {
+        let (ix, iy) = unpack_coords(id);
+        let (x, y) = (usize::try_from(ix).ok()?, usize::try_from(iy).ok()?);
+        (x < self.resolution && y < self.resolution).then_some(y * self.resolution + x)
+    }
+
+    #[inline]
+    fn id_of(&self, slot: usize) -> PackedCell {
+        let (x, y) = (slot % self.resolution, slot / self.resolution);
+        pack_coords(x as i32, y as i32)
+    }
+
+    pub fn get_packed(&self, id: PackedCell) -> Option<&T> {
+        self.get_packed_full(id).map(|(_, cell)| cell)
+    }
+
+    /// Looks up a cell along with its slot, its position in `slots_mut`.
+    pub fn get_packed_full(&self, id: PackedCell) -> Option<(usize, &T)> {
+        let slot = self.slot(id)?;
+        self.occupied[slot].then(|| (slot, &self.cells[slot]))
+    }
+
+    /// Panics for cells outside the grid, which has nowhere to put them.
+    pub fn get_packed_mut(&mut self, id: PackedCell) -> &mut T {
+        let slot = self.slot(id).expect("cell outside the dense grid");
+        if !self.occupied[slot] {
+            self.occupied[slot] = true;
+            self.len += 1;
+        }
+        &mut self.cells[slot]
+    }
+
+    pub fn iter_cells(&self) -> impl Iterator<Item = (PackedCell, &T)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|&(slot, _)| self.occupied[slot])
+            .map(|(slot, cell)| (self.id_of(slot), cell))
+    }
+
+    pub fn iter_cells_mut(&mut self) -> impl Iterator<Item = (PackedCell, &mut T)> {
+        let resolution = self.resolution;
+        self.cells
+            .iter_mut()
+            .zip(&self.occupied)
+            .enumerate()
+            .filter(|(_, (_, occupied))| **occupied)
+            .map(move |(slot, (cell, _))| {
+                let (x, y) = (slot % resolution, slot / resolution);
+                (pack_coords(x as i32, y as i32), cell)
+            })
+    }
+
+    /// Every slot, occupied or not, in slot order.
+    pub fn slots_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Vacated slots are reset to the default, as if freshly allocated.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(PackedCell, &mut T) -> bool,
+    {
+        for slot in 0..self.cells.len() {
+            if !self.occupied[slot] {
+                continue;
+            }
+            let id = self.id_of(slot);
+            if !f(id, &mut self.cells[slot]) {
+                self.cells[slot] = T::default();
+                self.occupied[slot] = false;
+                self.len -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dense_cells_behave_like_sparse_ones() {
+        let mut grid = DenseGrid::<i32>::new(8);
+        *grid.get_packed_mut(pack_coords(3, 5)) = 35;
+        *grid.get_packed_mut(pack_coords(7, 0)) = 70;
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.get_packed(pack_coords(3, 5)), Some(&35));
+        assert_eq!(grid.get_packed(pack_coords(5, 3)), None);
+        assert_eq!(grid.get_packed(pack_coords(-1, 5)), None);
+        assert_eq!(grid.get_packed(pack_coords(8, 5)), None);
+
+        let cells: Vec<_> = grid
+            .iter_cells()
+            .map(|(id, &v)| (unpack_coords(id), v))
+            .collect();
+        assert_eq!(cells, vec![((7, 0), 70), ((3, 5), 35)]);
+
+        grid.retain(|id, _| unpack_coords(id) != (7, 0));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(*grid.get_packed_mut(pack_coords(7, 0)), 0);
+    }
+}
@@ -1,3 +1,11 @@
+pub mod collider;
+pub mod dense_grid;
+pub mod sampling;
 pub mod sp_grid;
+pub mod surface;
 
+pub use collider::*;
+pub use dense_grid::*;
+pub use sampling::*;
 pub use sp_grid::*;
+pub use surface::*;
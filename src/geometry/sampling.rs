@@ -0,0 +1,323 @@
+//! Particle placement inside shapes.
+//!
+//! Filling a shape on a lattice lines particles up along the grid axes,
+//! which shows as regular ripples once they start to move. Poisson-disk
+//! sampling keeps them evenly spaced without any preferred direction; a
+//! jittered lattice is the cheaper middle ground for polygonal tanks.
+
+use bevy::prelude::Vec2;
+use rand::Rng;
+
+use crate::math::{Real, Vector, consts, from_bevy_vec2, to_bevy_vec2};
+
+/// A shape to fill with particles, or to drain them from (see
+/// `sources::Sink`).
+pub trait Region: Send + Sync + 'static {
+    fn contains(&self, p: Vector) -> bool;
+
+    /// Corners `(min, max)` of a box enclosing the region.
+    fn bounds(&self) -> (Vector, Vector);
+}
+
+/// Disc of `radius` around `center`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CircleRegion {
+    pub center: Vector,
+    pub radius: Real,
+}
+
+impl CircleRegion {
+    pub fn new(center: Vector, radius: Real) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl Region for CircleRegion {
+    fn contains(&self, p: Vector) -> bool {
+        (p - self.center).norm_squared() <= self.radius * self.radius
+    }
+
+    fn bounds(&self) -> (Vector, Vector) {
+        let extent = Vector::new(self.radius, self.radius);
+        (self.center - extent, self.center + extent)
+    }
+}
+
+/// Axis-aligned rectangle from `min` to `max`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RectRegion {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl RectRegion {
+    pub fn new(min: Vector, max: Vector) -> Self {
+        Self { min, max }
+    }
+}
+
+impl Region for RectRegion {
+    fn contains(&self, p: Vector) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x) && (self.min.y..=self.max.y).contains(&p.y)
+    }
+
+    fn bounds(&self) -> (Vector, Vector) {
+        (self.min, self.max)
+    }
+}
+
+/// Simple polygon, convex or not, given by its vertices in either winding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolygonRegion {
+    pub vertices: Vec<Vec2>,
+}
+
+impl PolygonRegion {
+    pub fn new(vertices: Vec<Vec2>) -> Self {
+        Self { vertices }
+    }
+}
+
+impl Region for PolygonRegion {
+    fn contains(&self, p: Vector) -> bool {
+        polygon_contains(&self.vertices, to_bevy_vec2(&p))
+    }
+
+    fn bounds(&self) -> (Vector, Vector) {
+        let (min, max) = polygon_bounds(&self.vertices);
+        (from_bevy_vec2(min), from_bevy_vec2(max))
+    }
+}
+
+/// Even-odd test: a ray from `p` towards +x crosses the outline an odd
+/// number of times exactly when `p` is inside.
+pub fn polygon_contains(vertices: &[Vec2], p: Vec2) -> bool {
+    let mut inside = false;
+    let mut previous = match vertices.last() {
+        Some(&last) => last,
+        None => return false,
+    };
+    for &vertex in vertices {
+        // Half-open in y so a ray through a vertex counts it once
+        if (vertex.y > p.y) != (previous.y > p.y) {
+            let t = (p.y - vertex.y) / (previous.y - vertex.y);
+            if p.x < vertex.x + t * (previous.x - vertex.x) {
+                inside = !inside;
+            }
+        }
+        previous = vertex;
+    }
+    inside
+}
+
+fn polygon_bounds(vertices: &[Vec2]) -> (Vec2, Vec2) {
+    vertices.iter().fold(
+        (Vec2::INFINITY, Vec2::NEG_INFINITY),
+        |(min, max), &vertex| (min.min(vertex), max.max(vertex)),
+    )
+}
+
+/// Lattice points jittered by up to a quarter of the lattice step along each
+/// axis, so particles don't start out lined up along the grid.
+const LATTICE_JITTER: f32 = 0.25;
+
+/// Points on a `spacing` lattice over the polygon's bounding box, each
+/// nudged by a fixed pseudo-random jitter and kept if it lands inside the
+/// polygon. The same polygon always gets the same points.
+///
+/// Returns nothing for fewer than three vertices, a polygon without area
+/// or a spacing that isn't positive and finite.
+pub fn fill_polygon(vertices: &[Vec2], spacing: Real) -> Vec<Vector> {
+    // The outline is in Bevy's f32, so the lattice is laid out in it too
+    let spacing = spacing as f32;
+    let doubled_area: f32 = vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum();
+    let (min, max) = polygon_bounds(vertices);
+    let valid = vertices.len() >= 3
+        && doubled_area.is_finite()
+        && doubled_area.abs() > f32::EPSILON
+        && spacing.is_finite()
+        && spacing > 0.0;
+    if !valid {
+        return Vec::new();
+    }
+
+    let steps = ((max - min) / spacing).ceil().as_uvec2();
+    let mut points = Vec::new();
+    for j in 0..steps.y {
+        for i in 0..steps.x {
+            let jitter = (lattice_noise(i, j) - 0.5) * (2.0 * LATTICE_JITTER);
+            let point = min + (Vec2::new(i as f32, j as f32) + 0.5 + jitter) * spacing;
+            if polygon_contains(vertices, point) {
+                points.push(from_bevy_vec2(point));
+            }
+        }
+    }
+    points
+}
+
+/// Two uniform values in `[0, 1)` hashed from a lattice index.
+fn lattice_noise(i: u32, j: u32) -> Vec2 {
+    // SplitMix64 finaliser over the packed index
+    let mut hash = (((i as u64) << 32) | j as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^= hash >> 31;
+    let unit = |bits: u64| (bits & 0xFF_FFFF) as f32 / (1 << 24) as f32;
+    Vec2::new(unit(hash), unit(hash >> 32))
+}
+
+/// Candidates tried around each sample before it is retired (Bridson's `k`).
+const CANDIDATES_PER_SAMPLE: usize = 30;
+
+/// Random points filling `region` with no two closer than `spacing`, by
+/// Bridson's algorithm ("Fast Poisson Disk Sampling in Arbitrary
+/// Dimensions", 2007).
+///
+/// Samples grow outward from a random seed until no candidate fits, so a
+/// region made of separate pieces is only filled where the seed landed.
+/// The points end up about `spacing` to `2 * spacing` apart; give each
+/// particle the mass its share of the area holds at the target density.
+pub fn poisson_disk_fill(region: &dyn Region, spacing: Real, rng: &mut impl Rng) -> Vec<Vector> {
+    let (min, max) = region.bounds();
+    let size = max - min;
+    let valid = spacing.is_finite()
+        && spacing > 0.0
+        && size
+            .iter()
+            .all(|&extent| extent.is_finite() && extent >= 0.0);
+    if !valid {
+        return Vec::new();
+    }
+
+    // Cells small enough to hold at most one sample each
+    let cell = spacing / Real::sqrt(2.0);
+    let columns = (size.x / cell).floor() as usize + 1;
+    let rows = (size.y / cell).floor() as usize + 1;
+    let cell_of = |p: Vector| {
+        let offset = (p - min) / cell;
+        (
+            (offset.x as usize).min(columns - 1),
+            (offset.y as usize).min(rows - 1),
+        )
+    };
+    let mut cells: Vec<Option<usize>> = vec![None; columns * rows];
+    let mut samples = Vec::new();
+    let mut active = Vec::new();
+
+    // The bounding box may be mostly outside the region; give the seed a
+    // fair number of tries to land inside
+    let Some(seed) = (0..CANDIDATES_PER_SAMPLE * 10)
+        .map(|_| {
+            Vector::new(
+                rng.random_range(min.x..=max.x),
+                rng.random_range(min.y..=max.y),
+            )
+        })
+        .find(|&p| region.contains(p))
+    else {
+        return samples;
+    };
+    let (x, y) = cell_of(seed);
+    cells[y * columns + x] = Some(0);
+    samples.push(seed);
+    active.push(0);
+
+    while !active.is_empty() {
+        let slot = rng.random_range(0..active.len());
+        let origin = samples[active[slot]];
+        let mut placed = false;
+        for _ in 0..CANDIDATES_PER_SAMPLE {
+            // Uniform over the annulus between one and two spacings
+            let angle = rng.random_range(0.0..consts::TAU);
+            let radius = spacing * rng.random_range(1.0..4.0 as Real).sqrt();
+            let candidate = origin + Vector::new(angle.cos(), angle.sin()) * radius;
+            if !region.contains(candidate) {
+                continue;
+            }
+            let (x, y) = cell_of(candidate);
+            let too_close = (y.saturating_sub(2)..(y + 3).min(rows)).any(|ny| {
+                (x.saturating_sub(2)..(x + 3).min(columns)).any(|nx| {
+                    cells[ny * columns + nx]
+                        .is_some_and(|other| (samples[other] - candidate).norm() < spacing)
+                })
+            });
+            if too_close {
+                continue;
+            }
+            cells[y * columns + x] = Some(samples.len());
+            active.push(samples.len());
+            samples.push(candidate);
+            placed = true;
+            break;
+        }
+        if !placed {
+            active.swap_remove(slot);
+        }
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn samples_stay_inside_and_keep_their_distance() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let spacing = 0.5;
+        let circle = CircleRegion::new(Vector::new(30.0, 40.0), 6.0);
+        let rect = RectRegion::new(Vector::new(10.0, 10.0), Vector::new(22.0, 15.0));
+        let regions: [(&dyn Region, Real); 2] = [(&circle, consts::PI * 36.0), (&rect, 12.0 * 5.0)];
+
+        for (region, area) in regions {
+            let points = poisson_disk_fill(region, spacing, &mut rng);
+            assert!(points.iter().all(|&p| region.contains(p)));
+            for (i, a) in points.iter().enumerate() {
+                for b in &points[i + 1..] {
+                    assert!((a - b).norm() >= spacing, "{a} and {b} are too close");
+                }
+            }
+            // Bridson packs at roughly 0.6 to 0.7 points per spacing^2
+            let density = points.len() as Real * spacing * spacing / area;
+            assert!(density > 0.5, "only {} points", points.len());
+        }
+    }
+
+    #[test]
+    fn an_l_shaped_tank_is_filled_without_spilling() {
+        let l_shape = [
+            Vec2::new(10.0, 10.0),
+            Vec2::new(30.0, 10.0),
+            Vec2::new(30.0, 16.0),
+            Vec2::new(16.0, 16.0),
+            Vec2::new(16.0, 40.0),
+            Vec2::new(10.0, 40.0),
+        ];
+        let spacing = 0.5;
+        let points = fill_polygon(&l_shape, spacing);
+        let region = PolygonRegion::new(l_shape.to_vec());
+        assert!(points.iter().all(|&p| region.contains(p)));
+        // Nothing in the notch the concave corner cuts out
+        assert!(!points.iter().any(|p| p.x > 16.5 && p.y > 16.5));
+        // About one point per lattice cell over the 20x6 + 6x24 area
+        let expected = (20.0 * 6.0 + 6.0 * 24.0) / (spacing * spacing);
+        assert!(
+            (points.len() as Real - expected).abs() < expected * 0.05,
+            "{}",
+            points.len()
+        );
+        assert_eq!(points, fill_polygon(&l_shape, spacing));
+
+        assert!(fill_polygon(&l_shape[..2], spacing).is_empty());
+        let collinear = [Vec2::ZERO, Vec2::ONE, Vec2::splat(2.0)];
+        assert!(fill_polygon(&collinear, spacing).is_empty());
+        assert!(fill_polygon(&l_shape, 0.0).is_empty());
+    }
+}
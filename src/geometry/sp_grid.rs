@@ -1,3 +1,5 @@
+use std::hash::{BuildHasherDefault, Hasher};
+
 use indexmap::IndexMap;
 
 use bevy::prelude::IVec2;
@@ -18,6 +20,38 @@ const NEIGHBOR_OFFSETS: [(i32, i32); 9] = [
     (1, 1),
 ];
 
+/// FxHash multiplier (the 64-bit golden ratio, rounded to odd).
+const HASH_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Hasher for `PackedCell` keys: one multiply per key instead of SipHash.
+///
+/// The multiply pushes entropy towards the high bits, so `finish` rotates
+/// them down to where the table picks its bucket; otherwise cells sharing a
+/// `y` would all collide.
+#[derive(Clone, Copy, Default)]
+pub struct PackedCellHasher(u64);
+
+impl Hasher for PackedCellHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0.rotate_left(26)
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.0 = (self.0 ^ value).wrapping_mul(HASH_SEED);
+    }
+}
+
+pub type PackedCellBuildHasher = BuildHasherDefault<PackedCellHasher>;
+
 #[inline]
 pub fn pack_coords(ix: i32, iy: i32) -> PackedCell {
     ((ix as u64) << 32) | (iy as u32 as u64)
@@ -41,17 +75,39 @@ pub fn unpack_to_ivec(id: PackedCell) -> IVec2 {
     IVec2::new(ix, iy)
 }
 
+/// Spreads the bits of `value` out to the even bit positions.
+#[inline]
+fn spread_bits(value: u32) -> u64 {
+    let mut bits = value as u64;
+    bits = (bits | (bits << 16)) & 0x0000_ffff_0000_ffff;
+    bits = (bits | (bits << 8)) & 0x00ff_00ff_00ff_00ff;
+    bits = (bits | (bits << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    bits = (bits | (bits << 2)) & 0x3333_3333_3333_3333;
+    (bits | (bits << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Z-order (Morton) key of a cell: the bits of `x` and `y` interleaved.
+///
+/// The sign bits are flipped first so negative coordinates sort before
+/// positive ones instead of after.
+#[inline]
+pub fn morton_code(id: PackedCell) -> u64 {
+    let (ix, iy) = unpack_coords(id);
+    let flip = |value: i32| (value as u32) ^ 0x8000_0000;
+    spread_bits(flip(ix)) | (spread_bits(flip(iy)) << 1)
+}
+
 #[derive(Clone)]
 pub struct SpGrid<T> {
     cell_width: Real,
-    cells: IndexMap<PackedCell, T>,
+    cells: IndexMap<PackedCell, T, PackedCellBuildHasher>,
 }
 
 impl<T: Default> SpGrid<T> {
     pub fn new(cell_width: Real) -> Self {
         Self {
             cell_width,
-            cells: IndexMap::new(),
+            cells: IndexMap::default(),
         }
     }
 
@@ -64,7 +120,14 @@ impl<T: Default> SpGrid<T> {
     }
 
     pub fn get_packed_mut(&mut self, id: PackedCell) -> &mut T {
-        self.cells.entry(id).or_insert_with(T::default)
+        self.cells.entry(id).or_default()
+    }
+
+    /// Looks up a cell along with its position in iteration order.
+    pub fn get_packed_full(&self, id: PackedCell) -> Option<(usize, &T)> {
+        self.cells
+            .get_full(&id)
+            .map(|(index, _, cell)| (index, cell))
     }
 
     pub fn for_each_neighbor_packed_mut<F>(&mut self, base_id: PackedCell, mut f: F)
@@ -73,7 +136,7 @@ impl<T: Default> SpGrid<T> {
     {
         let (ix, iy) = unpack_coords(base_id);
         for (dx, dy) in NEIGHBOR_OFFSETS.iter() {
-            let shift = IVec2::new((dx + 1) as i32, (dy + 1) as i32);
+            let shift = IVec2::new(dx + 1, dy + 1);
             let neighbor_id = pack_coords(ix + dx, iy + dy);
             let cell = self.get_packed_mut(neighbor_id);
             f(neighbor_id, shift, cell);
@@ -86,7 +149,7 @@ impl<T: Default> SpGrid<T> {
     {
         let (ix, iy) = unpack_coords(base_id);
         for (dx, dy) in NEIGHBOR_OFFSETS.iter() {
-            let shift = IVec2::new((dx + 1) as i32, (dy + 1) as i32);
+            let shift = IVec2::new(dx + 1, dy + 1);
             let neighbor_id = pack_coords(ix + dx, iy + dy);
             if let Some(cell) = self.cells.get(&neighbor_id) {
                 f(neighbor_id, shift, cell);
@@ -121,6 +184,15 @@ impl<T: Default> SpGrid<T> {
         self.cells.retain(|&id, node| f(id, node));
     }
 
+    /// Reorders the cells along the Z-order curve, so iteration walks
+    /// neighbouring cells together. Lookups are unaffected, and removals keep
+    /// the order, so only cells inserted since the last sort trigger a new one.
+    pub fn sort_by_morton(&mut self) {
+        if !self.cells.keys().is_sorted_by_key(|&&id| morton_code(id)) {
+            self.cells.sort_unstable_by_key(|&id, _| morton_code(id));
+        }
+    }
+
     pub fn cell_center(&self, id: PackedCell) -> Vector {
         let (ix, iy) = unpack_coords(id);
         Vector::new(ix as Real * self.cell_width, iy as Real * self.cell_width)
@@ -144,3 +216,33 @@ impl<T> SpGrid<T> {
         neighbors
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_round_trip_through_the_fast_hasher() {
+        let coords = (-70..70).flat_map(|x| (-70..70).map(move |y| IVec2::new(x * 3, y * 5)));
+        let label = |coord: IVec2| coord.x * 1000 + coord.y;
+        let mut grid = SpGrid::<i32>::new(1.0);
+        for coord in coords.clone() {
+            *grid.get_packed_mut(pack_from_ivec(coord)) = label(coord);
+        }
+        assert_eq!(grid.len(), 140 * 140);
+
+        for coord in coords {
+            let id = pack_from_ivec(coord);
+            assert_eq!(unpack_to_ivec(id), coord);
+            assert_eq!(grid.get_packed(id), Some(&label(coord)));
+        }
+        for (id, value) in grid.iter_cells() {
+            assert_eq!(*value, label(unpack_to_ivec(id)));
+        }
+        assert_eq!(grid.get_packed(pack_coords(1, 1)), None);
+
+        grid.retain(|id, _| unpack_coords(id).0 < 0);
+        assert_eq!(grid.len(), 70 * 140);
+        assert_eq!(grid.get_packed(pack_coords(-3, -5)), Some(&-3005));
+    }
+}
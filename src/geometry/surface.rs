@@ -0,0 +1,170 @@
+//! Iso-contours of the grid mass field, for drawing fluids as a surface.
+
+use bevy::prelude::*;
+
+use crate::core::{Grid, node_center};
+use crate::math::{Real, to_bevy_scalar, to_bevy_vec2};
+
+/// Line segments, in simulation space, where the grid's node `mass` crosses
+/// `iso`, found with marching squares over the nodes.
+///
+/// Squares are walked over the active nodes' bounding box grown by one node,
+/// so regions touching the edge of that box are still closed off against
+/// the empty nodes around them. Where a square's diagonal corners alone are
+/// inside (the saddle cases), the mean of its four corners decides whether
+/// the inside corners join across the middle. Each edge crossing is
+/// computed in a fixed corner order, so neighbouring squares produce
+/// bit-identical endpoints and the segments chain into closed loops.
+pub fn extract_contour(grid: &Grid, iso: Real) -> Vec<[Vec2; 2]> {
+    let mut min = IVec2::MAX;
+    let mut max = IVec2::MIN;
+    for ((x, y), _) in grid.iter_active_cells() {
+        min = min.min(IVec2::new(x, y));
+        max = max.max(IVec2::new(x, y));
+    }
+    if min.cmpgt(max).any() {
+        return Vec::new();
+    }
+    let (min, max) = (min - IVec2::ONE, max + IVec2::ONE);
+
+    let mass = |coord: IVec2| grid.get_cell_coord(coord).map_or(0.0, |node| node.mass);
+    let cell_width = grid.cell_width();
+    let node_position = |coord: IVec2| to_bevy_vec2(&node_center(coord, cell_width));
+    // Crossing on the edge from `a` to `b`, which must differ by +x or +y
+    let crossing = |a: IVec2, b: IVec2| {
+        let (mass_a, mass_b) = (mass(a), mass(b));
+        let t = ((iso - mass_a) / (mass_b - mass_a)).clamp(0.0, 1.0);
+        node_position(a).lerp(node_position(b), to_bevy_scalar(t))
+    };
+
+    let mut segments = Vec::new();
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            // Corners counter-clockwise from the bottom left
+            let corners = [
+                IVec2::new(x, y),
+                IVec2::new(x + 1, y),
+                IVec2::new(x + 1, y + 1),
+                IVec2::new(x, y + 1),
+            ];
+            let case = corners
+                .iter()
+                .enumerate()
+                .filter(|&(_, &corner)| mass(corner) >= iso)
+                .fold(0, |case, (bit, _)| case | (1 << bit));
+            if case == 0 || case == 15 {
+                continue;
+            }
+            let edge = |index: usize| match index {
+                0 => crossing(corners[0], corners[1]),
+                1 => crossing(corners[1], corners[2]),
+                2 => crossing(corners[3], corners[2]),
+                _ => crossing(corners[0], corners[3]),
+            };
+            let centre_inside =
+                || corners.iter().map(|&corner| mass(corner)).sum::<Real>() >= iso * 4.0;
+            let edges: &[[usize; 2]] = match case {
+                1 => &[[3, 0]],
+                2 => &[[0, 1]],
+                3 => &[[3, 1]],
+                4 => &[[1, 2]],
+                // Bottom-left and top-right inside
+                5 if centre_inside() => &[[0, 1], [2, 3]],
+                5 => &[[3, 0], [1, 2]],
+                6 => &[[0, 2]],
+                7 => &[[3, 2]],
+                8 => &[[2, 3]],
+                9 => &[[2, 0]],
+                // Bottom-right and top-left inside
+                10 if centre_inside() => &[[3, 0], [1, 2]],
+                10 => &[[0, 1], [2, 3]],
+                11 => &[[2, 1]],
+                12 => &[[1, 3]],
+                13 => &[[1, 0]],
+                _ => &[[0, 3]],
+            };
+            segments.extend(edges.iter().map(|&[a, b]| [edge(a), edge(b)]));
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn a_round_blob_gives_one_closed_loop() {
+        let mut grid = Grid::new();
+        let centre = Vec2::new(60.0, 40.0);
+        for y in 25..56 {
+            for x in 45..76 {
+                let coord = IVec2::new(x, y);
+                let distance = (coord.as_vec2() + 0.5).distance(centre);
+                let mass = (12.0 - distance as Real).max(0.0);
+                if mass > 0.0 {
+                    grid.get_cell_coord_mut(coord).mass = mass;
+                }
+            }
+        }
+
+        let segments = extract_contour(&grid, 2.0);
+        assert!(segments.len() > 20);
+
+        // Every endpoint is shared by exactly two segments...
+        let key = |point: Vec2| (point.x.to_bits(), point.y.to_bits());
+        let mut touching: HashMap<_, Vec<usize>> = HashMap::new();
+        for (index, segment) in segments.iter().enumerate() {
+            for &point in segment {
+                touching.entry(key(point)).or_default().push(index);
+            }
+        }
+        assert!(touching.values().all(|segments| segments.len() == 2));
+
+        // ...and walking from one to the next visits them all before coming back
+        let mut visited = vec![false; segments.len()];
+        let (mut current, mut point) = (0, segments[0][1]);
+        for _ in 0..segments.len() {
+            visited[current] = true;
+            let ends = &touching[&key(point)];
+            current = if ends[0] == current { ends[1] } else { ends[0] };
+            let [a, b] = segments[current];
+            point = if key(a) == key(point) { b } else { a };
+        }
+        assert_eq!(current, 0);
+        assert!(visited.iter().all(|&visited| visited));
+
+        // The loop encloses the blob at about the radius where mass = iso
+        for segment in &segments {
+            let radius = segment[0].distance(centre);
+            assert!((radius - 10.0).abs() < 1.0, "{radius}");
+        }
+    }
+
+    #[test]
+    fn contours_follow_the_cell_width() {
+        let mut grid = Grid::with_cell_width(0.5);
+        // A cone peaking on node (60, 40), which sits at (30.25, 20.25)
+        for y in 25..56 {
+            for x in 45..76 {
+                let coord = IVec2::new(x, y);
+                let distance = coord.as_vec2().distance(Vec2::new(60.0, 40.0));
+                let mass = (12.0 - distance as Real).max(0.0);
+                if mass > 0.0 {
+                    grid.get_cell_coord_mut(coord).mass = mass;
+                }
+            }
+        }
+
+        let segments = extract_contour(&grid, 2.0);
+        assert!(segments.len() > 20);
+        // Ten nodes out is five units at half-width cells
+        let centre = Vec2::new(30.25, 20.25);
+        for point in segments.iter().flatten() {
+            let radius = point.distance(centre);
+            assert!((radius - 5.0).abs() < 0.5, "{radius}");
+        }
+    }
+}
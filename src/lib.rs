@@ -1,93 +1,366 @@
 //! MLS-MPM (Moving Least Squares Material Point Method) simulation for Bevy
 //!
-//! ```rust
+//! ```rust,no_run
 //! use bevy::prelude::*;
 //! use mpm2d::MpmPlugin;
 //!
 //! App::new().add_plugins((DefaultPlugins, MpmPlugin::default())).run();
 //! ```
 
+use bevy::ecs::schedule::ScheduleLabel;
 use bevy::prelude::*;
 
 pub mod config;
 pub mod core;
+pub mod fuzz;
 pub mod geometry;
 pub mod materials;
 pub mod math;
 pub mod solver;
+pub mod sources;
+#[cfg(test)]
+mod test_support;
+pub mod visuals;
+pub mod viz;
 
 // Clean public API - everything you need to get started
-pub use config::{GRAVITY, REST_DENSITY, SolverParams};
-pub use core::{GRID_RESOLUTION, Grid, GridNode, MpmState, Particle, ParticleRemap};
+pub use config::{
+    DeterministicConfig, GRAVITY, GridConfig, MpmConfig, MpmConfigError, REST_DENSITY, SimRng,
+    SolverParams,
+};
+pub use core::{
+    FailureReason, GRID_RESOLUTION, Grid, GridBackend, GridNode, MpmState, Particle,
+    ParticleBuilder, ParticleFailed, ParticleRemap, RewindBuffer,
+};
 pub use materials::{FluidParams, MaterialType};
+pub use solver::{
+    PbmpmConfig, PhaseTransition, PhaseTransitions, ResamplingConfig, SimControl, SimDiagnostics,
+    SolverTimings,
+};
 
 use crate::core::update_particles_health;
 use crate::core::{
-    cleanup_grid_cells, clear_particle_remap_system, remove_failed_particles_system, zero_grid,
+    cleanup_grid_cells, clear_particle_remap_system, record_rewind, remove_failed_particles_system,
+    zero_grid,
+};
+use crate::solver::{
+    MpmSubstep, advance_sim_control, drift_half_step, grid_to_particle, grid_update,
+    particle_to_grid, pbmpm_constraints, resample_particles, run_substeps, sim_running,
+    update_fracture, update_phase_transitions, update_sim_diagnostics,
 };
-use crate::solver::{grid_to_particle, grid_update, particle_to_grid};
 
+#[derive(Default)]
 pub struct MpmPlugin {
-    pub solver_params: Option<SolverParams>,
+    pub config: MpmConfig,
     pub debug: bool,
+    pub profiling: bool,
+    pub diagnostics: bool,
+    pub fracture: bool,
 }
 
-impl Default for MpmPlugin {
-    fn default() -> Self {
-        Self {
-            solver_params: None,
-            debug: false,
-        }
+impl MpmPlugin {
+    /// Plugin for a validated `MpmConfig`.
+    pub fn from_config(config: MpmConfig) -> Result<Self, MpmConfigError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            ..Self::default()
+        })
     }
-}
 
-impl MpmPlugin {
     pub fn with_params(solver_params: SolverParams) -> Self {
         Self {
-            solver_params: Some(solver_params),
-            debug: false,
+            config: MpmConfig::default().with_solver_params(solver_params),
+            ..Self::default()
         }
     }
 
     pub fn with_debug() -> Self {
         Self {
-            solver_params: None,
             debug: true,
+            ..Self::default()
         }
     }
+
+    /// Record per-stage durations into the `SolverTimings` resource.
+    pub fn with_profiling(mut self) -> Self {
+        self.profiling = true;
+        self
+    }
+
+    /// Record conservation totals into the `SimDiagnostics` resource.
+    pub fn with_diagnostics(mut self) -> Self {
+        self.diagnostics = true;
+        self
+    }
+
+    /// Accumulate damage on particles carrying a `ParticleFracture`.
+    pub fn with_fracture(mut self) -> Self {
+        self.fracture = true;
+        self
+    }
 }
 
 impl Plugin for MpmPlugin {
     fn build(&self, app: &mut App) {
-        let params = self
-            .solver_params
-            .clone()
-            .unwrap_or_else(SolverParams::default);
-        app.insert_resource(MpmState::new(params, GRAVITY));
+        app.insert_resource(self.config.build_state());
+        app.insert_resource(self.config.grid);
+        app.insert_resource(self.config.build_rng());
+        app.init_resource::<SimControl>();
         app.insert_resource(ParticleRemap::default());
+        app.init_resource::<geometry::Colliders>();
+        app.add_message::<geometry::ParticleEnteredCollider>();
+        app.add_message::<ParticleFailed>();
+        if self.profiling {
+            app.init_resource::<SolverTimings>();
+        }
+        if self.diagnostics {
+            app.init_resource::<SimDiagnostics>();
+        }
 
         app.add_systems(
-            Update,
+            MpmSubstep,
             (
-                update_particle_health_system,
                 zero_grid,
+                drift_half_step,
                 particle_to_grid,
                 cleanup_grid_cells,
                 grid_update,
+                pbmpm_constraints,
                 grid_to_particle,
-                remove_failed_particles_system,
-                clear_particle_remap_system,
             )
                 .chain(),
         );
+        #[cfg(debug_assertions)]
+        app.add_systems(
+            MpmSubstep,
+            solver::check_mass_conservation
+                .after(particle_to_grid)
+                .before(cleanup_grid_cells),
+        );
+        let systems = (
+            update_particle_health_system,
+            run_substeps,
+            remove_failed_particles_system,
+            clear_particle_remap_system,
+        )
+            .chain()
+            .run_if(sim_running);
+        let schedule = match self.config.timestep {
+            Some(timestep) => {
+                app.insert_resource(Time::<Fixed>::from_duration(timestep));
+                FixedUpdate.intern()
+            }
+            None => Update.intern(),
+        };
+        app.add_systems(schedule, systems);
+        app.add_systems(
+            schedule,
+            advance_sim_control
+                .before(update_particle_health_system)
+                .before(sources::emit_particles)
+                .before(sources::feed_inflows),
+        );
+        app.add_systems(
+            schedule,
+            (sources::emit_particles, sources::feed_inflows)
+                .before(run_substeps)
+                .run_if(sim_running),
+        );
+        // They all rewrite the particles, so one fixed order keeps runs
+        // repeatable: damage, phase changes, sinks, then resampling what's left
+        let particle_updates = (
+            update_phase_transitions,
+            sources::drain_sinks,
+            resample_particles,
+        )
+            .chain();
+        if self.fracture {
+            app.add_systems(
+                schedule,
+                (update_fracture, particle_updates)
+                    .chain()
+                    .after(run_substeps)
+                    .before(remove_failed_particles_system)
+                    .run_if(sim_running),
+            );
+        } else {
+            app.add_systems(
+                schedule,
+                particle_updates
+                    .after(run_substeps)
+                    .before(remove_failed_particles_system)
+                    .run_if(sim_running),
+            );
+        }
+        app.add_systems(
+            schedule,
+            geometry::detect_collider_entries
+                .after(remove_failed_particles_system)
+                .before(clear_particle_remap_system)
+                .run_if(sim_running),
+        );
+        app.add_systems(
+            schedule,
+            record_rewind
+                .after(remove_failed_particles_system)
+                .before(clear_particle_remap_system)
+                .run_if(sim_running),
+        );
+        app.add_systems(
+            schedule,
+            update_sim_diagnostics
+                .after(remove_failed_particles_system)
+                .before(clear_particle_remap_system)
+                .run_if(sim_running),
+        );
 
         if self.debug {
             info!("MPM debug mode enabled");
+            app.add_systems(Update, viz::draw_debug_gizmos);
         }
     }
 }
 
 fn update_particle_health_system(mut state: ResMut<MpmState>) {
+    let condition_threshold = state.solver_params().condition_threshold;
     let particles = state.particles_mut();
-    update_particles_health(particles);
+    update_particles_health(particles, condition_threshold);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::math::{Real, Vector};
+
+    /// App running `plugin` on a manually advanced clock, with a block of
+    /// water to simulate.
+    fn app_with(plugin: MpmPlugin) -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>().add_plugins(plugin);
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        for j in 0..20 {
+            for i in 0..20 {
+                let position = Vector::new(50.0 + i as Real, 50.0 + j as Real);
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+        app
+    }
+
+    fn advance_frame(app: &mut App) {
+        let frame = Duration::from_secs_f64(1.0 / 60.0);
+        app.world_mut().resource_mut::<Time>().advance_by(frame);
+        app.update();
+    }
+
+    #[test]
+    fn profiling_records_stage_durations() {
+        let mut app = app_with(MpmPlugin::default().with_profiling());
+        advance_frame(&mut app);
+        let timings = *app.world().resource::<SolverTimings>();
+        assert!(timings.p2g > Duration::ZERO);
+        assert!(timings.g2p > Duration::ZERO);
+
+        let app = app_with(MpmPlugin::default());
+        assert!(app.world().get_resource::<SolverTimings>().is_none());
+    }
+
+    #[test]
+    fn config_values_reach_the_state_and_schedule() {
+        let timestep = Duration::from_secs_f64(1.0 / 120.0);
+        let config = MpmConfig::default()
+            .with_solver_params(SolverParams::default().with_correction_strength(0.25))
+            .with_gravity(Vector::new(0.0, -9.81))
+            .with_boundary(crate::core::BoundaryHandling::Stick)
+            .with_cell_width(0.5)
+            .with_resolution(64)
+            .with_fixed_timestep(timestep);
+        let mut app = App::new();
+        app.add_plugins(MpmPlugin::from_config(config).unwrap());
+
+        let state = app.world().resource::<MpmState>();
+        assert_eq!(state.gravity(), Vector::new(0.0, -9.81));
+        assert_eq!(
+            state.boundary_mode(),
+            crate::core::BoundaryHandling::Stick.into()
+        );
+        assert_eq!(state.grid().cell_width(), 0.5);
+        assert_eq!(state.grid().resolution(), 64);
+        assert_eq!(app.world().resource::<GridConfig>().resolution, 64);
+        assert_eq!(state.solver_params().volume_correction_strength, 0.25);
+        assert_eq!(app.world().resource::<Time<Fixed>>().timestep(), timestep);
+        let fixed = app
+            .get_schedule(FixedUpdate)
+            .map_or(0, Schedule::systems_len);
+        assert!(fixed > 0);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let zero_width = MpmConfig::default().with_cell_width(0.0);
+        assert_eq!(
+            MpmPlugin::from_config(zero_width).err(),
+            Some(MpmConfigError::InvalidCellWidth(0.0))
+        );
+        let resolution = MpmConfig::default().with_resolution(100);
+        assert_eq!(
+            MpmPlugin::from_config(resolution).err(),
+            Some(MpmConfigError::InvalidResolution(100))
+        );
+        let gravity = MpmConfig::default().with_gravity(Vector::new(Real::NAN, 0.0));
+        assert_eq!(
+            MpmPlugin::from_config(gravity).err(),
+            Some(MpmConfigError::NonFiniteGravity)
+        );
+        let friction = crate::core::BoundaryHandling::Friction(-0.5);
+        assert_eq!(
+            MpmPlugin::from_config(MpmConfig::default().with_boundary(friction)).err(),
+            Some(MpmConfigError::InvalidFriction(-0.5))
+        );
+        let timestep = MpmConfig::default().with_fixed_timestep(Duration::ZERO);
+        let error = MpmPlugin::from_config(timestep).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "fixed timestep must be greater than zero"
+        );
+    }
+
+    #[test]
+    fn a_bouncing_elastic_block_passes_every_health_check() {
+        let params = SolverParams::default().with_singular_value_clamp(0.5, 2.0);
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_plugins(MpmPlugin::with_params(params));
+        let material = MaterialType::elastic(materials::ElasticParams::jelly());
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        for j in 0..20 {
+            for i in 0..20 {
+                let position = Vector::new(54.25, 10.25) + Vector::new(i as Real, j as Real) * 0.5;
+                let mut particle = Particle::new(position, material.clone()).with_mass(0.25);
+                particle.volume0 = 0.25;
+                state.add_particle(particle.with_velocity(Vector::new(0.0, 20.0)));
+            }
+        }
+
+        let mut lowest = Real::INFINITY;
+        for _ in 0..500 {
+            advance_frame(&mut app);
+            let particles = app.world().resource::<MpmState>().particles();
+            assert_eq!(particles.len(), 400);
+            lowest = particles
+                .iter()
+                .fold(lowest, |lowest, p| lowest.min(p.position.y));
+        }
+        // It did come down onto the floor
+        assert!(lowest < 3.0, "{lowest}");
+        let particles = app.world().resource::<MpmState>().particles();
+        assert!(
+            particles
+                .iter()
+                .all(|particle| particle.condition_number < 1.0e6)
+        );
+    }
 }
@@ -6,21 +6,33 @@
 //! solver.
 
 use crate::config;
+use crate::math::Real;
 
 /// Parameters describing a generic fluid material.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct FluidParams {
+    #[cfg_attr(
+        feature = "serde-serialize",
+        serde(deserialize_with = "crate::materials::utils::deserialize_name")
+    )]
     pub name: &'static str,
-    pub rest_density: f32,
-    pub eos_stiffness: f32,
+    pub rest_density: Real,
+    pub eos_stiffness: Real,
     pub eos_power: u8,
+    /// Dynamic viscosity of this fluid; `None` falls back to
+    /// `SolverParams::dynamic_viscosity`.
+    pub dynamic_viscosity: Option<Real>,
 }
 
 impl FluidParams {
     pub const fn new(
         name: &'static str,
-        rest_density: f32,
-        eos_stiffness: f32,
+        rest_density: Real,
+        eos_stiffness: Real,
         eos_power: u8,
     ) -> Self {
         Self {
@@ -28,6 +40,7 @@ impl FluidParams {
             rest_density,
             eos_stiffness,
             eos_power,
+            dynamic_viscosity: None,
         }
     }
 
@@ -50,6 +63,62 @@ impl FluidParams {
             config::constants::EOS_POWER,
         )
     }
+
+    /// Thick, heavy fluid that oozes rather than splashes.
+    ///
+    /// Real honey is thousands of times more viscous than water; this keeps
+    /// the viscosity as high as the explicit solver stays stable with at the
+    /// default timestep.
+    pub const fn honey() -> Self {
+        Self::new("honey", 2.8, config::constants::EOS_STIFFNESS, 4).with_dynamic_viscosity(1.0)
+    }
+
+    /// Light fluid that floats on water and flows slightly slower.
+    pub const fn oil() -> Self {
+        Self::new("oil", 1.6, config::constants::EOS_STIFFNESS, 4).with_dynamic_viscosity(0.05)
+    }
+
+    /// Returns a copy with its own dynamic viscosity.
+    pub const fn with_dynamic_viscosity(mut self, dynamic_viscosity: Real) -> Self {
+        self.dynamic_viscosity = Some(dynamic_viscosity);
+        self
+    }
+
+    /// Returns a copy whose EOS stiffness is derived from a bulk modulus.
+    ///
+    /// Linearising `p = k * ((rho / rho0)^n - 1)` around the rest density gives
+    /// `K = rho0 * dp/drho = k * n`, so the stiffness is `K / n`. The rest
+    /// state keeps zero pressure regardless of `K`.
+    pub const fn with_bulk_modulus(mut self, bulk_modulus: Real) -> Self {
+        self.eos_stiffness = bulk_modulus / self.linear_power();
+        self
+    }
+
+    /// Returns a copy tuned to the given speed of sound (`K = rho0 * c^2`).
+    pub const fn with_sound_speed(self, sound_speed: Real) -> Self {
+        let bulk_modulus = self.rest_density * sound_speed * sound_speed;
+        self.with_bulk_modulus(bulk_modulus)
+    }
+
+    /// Effective bulk modulus of the EOS at rest density.
+    pub const fn bulk_modulus(&self) -> Real {
+        self.eos_stiffness * self.linear_power()
+    }
+
+    /// Speed of sound implied by the bulk modulus and rest density.
+    pub fn sound_speed(&self) -> Real {
+        (self.bulk_modulus() / self.rest_density).sqrt()
+    }
+
+    /// EOS power as used by the bulk-modulus conversions, a zero power
+    /// counting as linear.
+    const fn linear_power(&self) -> Real {
+        if self.eos_power == 0 {
+            1.0
+        } else {
+            self.eos_power as Real
+        }
+    }
 }
 
 impl Default for FluidParams {
@@ -57,3 +126,85 @@ impl Default for FluidParams {
         Self::defaults()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::Particle;
+    use crate::materials::fluids::water;
+    use crate::materials::{MaterialType, utils};
+    use crate::math::{Real, zero_vector};
+    use crate::test_support::{mean_layer_heights_after, spread_after_one_second};
+
+    /// Pressure the water EOS pushes back with at `density`.
+    fn restoring_pressure(fluid: FluidParams, density: Real) -> Real {
+        let particle = Particle::new(zero_vector(), MaterialType::fluid(fluid));
+        let params = SolverParams::default();
+        -utils::pressure(water::calculate_stress(&particle, density, &params, &fluid))
+    }
+
+    #[test]
+    fn higher_bulk_modulus_restores_harder_from_the_same_compression() {
+        let soft = FluidParams::water().with_bulk_modulus(10.0);
+        let stiff = FluidParams::water().with_bulk_modulus(40.0);
+        assert!((stiff.bulk_modulus() - 40.0).abs() < 1e-4);
+
+        let compressed = 1.05 * soft.rest_density;
+        let soft_pressure = restoring_pressure(soft, compressed);
+        assert!(soft_pressure > 0.0);
+        assert!(restoring_pressure(stiff, compressed) > 3.5 * soft_pressure);
+
+        // The rest state stays pressure-free whatever the modulus
+        assert_eq!(restoring_pressure(stiff, stiff.rest_density), 0.0);
+    }
+
+    #[test]
+    fn bulk_modulus_round_trips_for_every_eos_power() {
+        for power in [0, 1, 4, 7] {
+            let fluid = FluidParams::new("fluid", 2.0, 1.0, power).with_bulk_modulus(50.0);
+            assert!((fluid.bulk_modulus() - 50.0).abs() < 1e-4, "power {power}");
+            assert!((fluid.sound_speed() - 5.0).abs() < 1e-4, "power {power}");
+            let tuned = fluid.with_sound_speed(3.0);
+            assert!((tuned.sound_speed() - 3.0).abs() < 1e-4, "power {power}");
+        }
+    }
+
+    #[test]
+    fn presets_are_physically_distinct() {
+        let (water, honey, oil) = (
+            FluidParams::water(),
+            FluidParams::honey(),
+            FluidParams::oil(),
+        );
+        let default_viscosity = SolverParams::default().dynamic_viscosity;
+        assert!(honey.dynamic_viscosity.unwrap() >= 100.0 * default_viscosity);
+        assert!(oil.rest_density < water.rest_density);
+        assert!(honey.rest_density > water.rest_density);
+    }
+
+    #[test]
+    fn honey_spreads_slower_than_water() {
+        let water = spread_after_one_second(MaterialType::fluid(FluidParams::water()));
+        let honey = spread_after_one_second(MaterialType::fluid(FluidParams::honey()));
+        assert!(honey.is_finite());
+        assert!(honey + 2.0 < water, "honey {honey} vs water {water}");
+    }
+
+    #[test]
+    fn denser_fluid_sinks_beneath_a_lighter_one() {
+        let stiffness = config::constants::EOS_STIFFNESS;
+        let light = FluidParams::new("light", 1.0, stiffness, 4);
+        let heavy = FluidParams::new("heavy", 4.0, stiffness, 4);
+
+        // Heavy block resting on a light one
+        let layers = [MaterialType::fluid(light), MaterialType::fluid(heavy)];
+        let heights = mean_layer_heights_after(&layers, 240);
+        let (light_height, heavy_height) = (heights[0], heights[1]);
+        assert!(heavy_height.is_finite() && light_height.is_finite());
+        assert!(
+            heavy_height < light_height,
+            "heavy {heavy_height} vs light {light_height}"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Fluids with direction-dependent viscosity, like aligned fibres
+//!
+//! Pressure follows the water EOS. The viscous stress is the viscosity
+//! tensor times the deviatoric strain rate, so each row of the tensor sets
+//! how strongly that force component resists shear. A scalar viscosity `mu`
+//! is the tensor `mu * I`. Unless the tensor is isotropic the stress is not
+//! symmetric, so these fluids only conserve angular momentum approximately.
+
+use crate::config::SolverParams;
+use crate::core::Particle;
+use crate::materials::families::FluidParams;
+use crate::materials::utils::physics;
+use crate::math::{self, Matrix, Real, Vector, diagonal_from_value, outer_product};
+
+use super::water;
+
+/// Parameters describing a fluid with a viscosity tensor.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct AnisotropicFluidParams {
+    /// Density and EOS; its own viscosity is ignored.
+    pub fluid: FluidParams,
+    pub viscosity_tensor: Matrix,
+}
+
+impl AnisotropicFluidParams {
+    pub fn new(fluid: FluidParams, viscosity_tensor: Matrix) -> Self {
+        Self {
+            fluid,
+            viscosity_tensor,
+        }
+    }
+
+    /// The same viscosity in every direction.
+    pub fn isotropic(fluid: FluidParams, viscosity: Real) -> Self {
+        Self::new(fluid, diagonal_from_value(viscosity))
+    }
+
+    /// Fibres lying along `direction`: forces along them see
+    /// `along_viscosity`, forces across them `across_viscosity`. A low
+    /// `along_viscosity` lets layers slide easily in the fibre direction.
+    pub fn aligned(
+        fluid: FluidParams,
+        direction: Vector,
+        along_viscosity: Real,
+        across_viscosity: Real,
+    ) -> Self {
+        let along = direction
+            .try_normalize(Real::EPSILON)
+            .unwrap_or(Vector::x());
+        let across = Vector::new(-along.y, along.x);
+        let tensor = outer_product(along, along) * along_viscosity
+            + outer_product(across, across) * across_viscosity;
+        Self::new(fluid, tensor)
+    }
+}
+
+/// Water EOS pressure plus the tensor viscous term.
+pub fn calculate_stress(
+    particle: &Particle,
+    density: Real,
+    params: &SolverParams,
+    material: &AnisotropicFluidParams,
+) -> Matrix {
+    let inviscid = material.fluid.with_dynamic_viscosity(0.0);
+    let pressure = water::calculate_stress(particle, density, params, &inviscid);
+
+    let jacobian = math::matrix_determinant(&particle.deformation_gradient);
+    let deviatoric_strain =
+        physics::deviatoric_part(&physics::strain_rate(&particle.velocity_gradient));
+
+    pressure + material.viscosity_tensor * deviatoric_strain * (2.0 * jacobian)
+}
+
+/// Anisotropic fluids keep an isotropic deformation gradient.
+pub fn project_deformation(particle: &mut Particle) {
+    water::project_deformation(particle);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::MpmState;
+    use crate::materials::MaterialType;
+    use crate::math::zero_vector;
+    use crate::test_support::step_serial;
+
+    #[test]
+    fn an_isotropic_tensor_matches_the_scalar_viscosity() {
+        let fluid = FluidParams::water();
+        let mut particle = Particle::new(Vector::new(10.0, 10.0), MaterialType::water());
+        particle.velocity_gradient = Matrix::new(0.3, 1.2, -0.4, -0.1);
+        let params = SolverParams::default();
+
+        let honey = fluid.with_dynamic_viscosity(0.4);
+        let scalar = water::calculate_stress(&particle, 2.2, &params, &honey);
+        let isotropic = AnisotropicFluidParams::isotropic(fluid, 0.4);
+        let tensor = calculate_stress(&particle, 2.2, &params, &isotropic);
+        assert!((scalar - tensor).norm() < 1e-5, "{scalar} vs {tensor}");
+    }
+
+    /// Share of an initial shear `velocity = rate * offset` across a 6x6
+    /// cell patch still there after a second, where `offset` is the
+    /// distance from the centre along `across`, `velocity` along `along`.
+    fn shear_left(material: &MaterialType, along: Vector, across: Vector) -> Real {
+        let dt = 1.0 / 60.0;
+        let center = Vector::new(64.0, 64.0);
+        let rate = 2.0;
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        for j in 0..12 {
+            for i in 0..12 {
+                let offset = Vector::new(i as Real - 5.5, j as Real - 5.5) * 0.5;
+                let velocity = along * (rate * offset.dot(&across));
+                let particle = Particle::new(center + offset, material.clone());
+                state.add_particle(particle.with_mass(0.5).with_velocity(velocity));
+            }
+        }
+
+        for _ in 0..60 {
+            step_serial(&mut state, dt);
+        }
+
+        // Least-squares fit of the shear rate left in the same profile
+        let (moment, spread) = state
+            .particles()
+            .iter()
+            .fold((0.0, 0.0), |(m, s), particle| {
+                let offset = (particle.position - center).dot(&across);
+                (
+                    m + particle.velocity.dot(&along) * offset,
+                    s + offset * offset,
+                )
+            });
+        moment / spread / rate
+    }
+
+    #[test]
+    fn fibres_along_x_let_horizontal_layers_slide_past_each_other() {
+        let fibres = AnisotropicFluidParams::aligned(FluidParams::water(), Vector::x(), 0.01, 1.0);
+        let material = MaterialType::anisotropic_fluid(fibres);
+        let horizontal = shear_left(&material, Vector::x(), Vector::y());
+        let vertical = shear_left(&material, Vector::y(), Vector::x());
+        assert!(
+            horizontal > vertical,
+            "horizontal {horizontal}, vertical {vertical}"
+        );
+    }
+}
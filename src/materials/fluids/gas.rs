@@ -0,0 +1,133 @@
+//! Compressible gas for smoke and air
+//!
+//! Linear ideal-gas style EOS `p = k (rho - rho0)`. Compressed gas pushes
+//! outward as water does, but without water's tension clamp: below its rest
+//! density a gas pulls back together, so a parcel settles at its rest
+//! density rather than filling the space it is given.
+
+use crate::core::Particle;
+use crate::math::{self, Matrix, Real};
+
+use super::water;
+
+/// Parameters describing a compressible gas.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct GasParams {
+    #[cfg_attr(
+        feature = "serde-serialize",
+        serde(deserialize_with = "crate::materials::utils::deserialize_name")
+    )]
+    pub name: &'static str,
+    pub rest_density: Real,
+    /// Pressure per unit of density above rest (`k` in `p = k (rho - rho0)`).
+    pub stiffness: Real,
+}
+
+impl GasParams {
+    pub const fn new(name: &'static str, rest_density: Real, stiffness: Real) -> Self {
+        Self {
+            name,
+            rest_density,
+            stiffness,
+        }
+    }
+
+    /// Light gas that rises through denser fluids.
+    pub const fn smoke() -> Self {
+        Self::new("smoke", 0.2, 1.0)
+    }
+
+    pub const fn air() -> Self {
+        Self::new("air", 0.5, 2.0)
+    }
+}
+
+impl Default for GasParams {
+    fn default() -> Self {
+        Self::air()
+    }
+}
+
+/// Linear EOS `p = k (rho - rho0)` as Kirchhoff stress `-p J I`.
+///
+/// Unlike water the pressure may go negative below rest density; there is
+/// no clamp, so the gas behaves the same whichever way it is disturbed.
+pub fn calculate_stress(particle: &Particle, density: Real, gas: &GasParams) -> Matrix {
+    let jacobian = math::matrix_determinant(&particle.deformation_gradient);
+    let pressure = gas.stiffness * (density - gas.rest_density);
+    math::identity_matrix() * (-pressure * jacobian)
+}
+
+/// Gases keep an isotropic deformation gradient, like the other fluids.
+pub fn project_deformation(particle: &mut Particle) {
+    water::project_deformation(particle);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::MpmState;
+    use crate::materials::{FluidParams, MaterialType};
+    use crate::math::{Vector, zero_vector};
+    use crate::test_support::{
+        frame_world, horizontal_extent, mean_layer_heights_after, solver_schedule,
+    };
+
+    #[test]
+    fn compressed_pocket_expands() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let material = MaterialType::gas(GasParams::air());
+        for j in 0..16 {
+            for i in 0..16 {
+                let offset = Vector::new(i as Real, j as Real) * 0.25;
+                let position = Vector::new(62.125, 62.125) + offset;
+                state.add_particle(Particle::new(position, material.clone()).with_mass(0.25));
+            }
+        }
+        let kinetic_energy = |state: &MpmState| -> Real {
+            let particles = state.particles();
+            particles
+                .iter()
+                .map(|p| 0.5 * p.mass * p.velocity.norm_squared())
+                .sum()
+        };
+        let spread = |state: &MpmState| horizontal_extent(state.particles());
+        let initial_spread = spread(&state);
+        assert_eq!(kinetic_energy(&state), 0.0);
+
+        let mut world = frame_world(state);
+        let mut schedule = solver_schedule();
+        schedule.run(&mut world);
+        let early = kinetic_energy(world.resource::<MpmState>());
+        for _ in 0..10 {
+            schedule.run(&mut world);
+        }
+
+        let state = world.resource::<MpmState>();
+        assert!(early > 0.0);
+        assert!(kinetic_energy(state) > early);
+        assert!(spread(state) > initial_spread + 1.0);
+    }
+
+    #[test]
+    fn a_light_gas_rises_through_water_above_it() {
+        // As stiff as water near rest (the same dp/drho), so only the
+        // density differs and the gas stays lighter at any depth
+        let water = FluidParams::water();
+        let gas = GasParams::new("light gas", 0.5, water.bulk_modulus() / water.rest_density);
+
+        let layers = [MaterialType::gas(gas), MaterialType::fluid(water)];
+        let heights = mean_layer_heights_after(&layers, 240);
+        let (gas_height, water_height) = (heights[0], heights[1]);
+        assert!(gas_height.is_finite() && water_height.is_finite());
+        assert!(
+            gas_height > water_height,
+            "gas {gas_height} vs water {water_height}"
+        );
+    }
+}
@@ -1,7 +1,15 @@
 //! Fluids like water, oil, and honey
 //!
-//! These materials flow and take the shape of their container.
+//! These materials flow and take the shape of their container. All of them
+//! share the water EOS; `FluidParams` presets tell them apart, and the
+//! variants here swap in their own viscosity model.
 
+pub mod anisotropic;
+pub mod gas;
+pub mod non_newtonian;
 pub mod water;
 
+pub use anisotropic::AnisotropicFluidParams;
+pub use gas::GasParams;
+pub use non_newtonian::NonNewtonianParams;
 pub use water::*;
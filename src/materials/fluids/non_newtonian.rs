@@ -0,0 +1,154 @@
+//! Yield-stress (Herschel-Bulkley) fluids for mud and paint
+//!
+//! Pressure follows the water EOS; the viscosity depends on how fast the
+//! material is sheared. Below the yield stress the apparent viscosity is
+//! very high and the fluid holds its shape, above it the fluid follows a
+//! power law.
+
+use crate::config::SolverParams;
+use crate::core::Particle;
+use crate::materials::families::FluidParams;
+use crate::materials::utils::physics;
+use crate::math::{self, Matrix, Real};
+
+use super::water;
+
+/// Parameters describing a Herschel-Bulkley fluid.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct NonNewtonianParams {
+    /// Density and EOS; its own viscosity is ignored.
+    pub fluid: FluidParams,
+    /// Shear stress the fluid withstands before it flows.
+    pub yield_stress: Real,
+    /// Power-law consistency `k`.
+    pub consistency_k: Real,
+    /// Power-law exponent `n` (< 1 shear-thinning, > 1 shear-thickening).
+    pub flow_index_n: Real,
+    /// Cap on the apparent viscosity, reached as the shear rate goes to zero.
+    /// Must stay within what the explicit solver can integrate.
+    pub max_viscosity: Real,
+}
+
+impl NonNewtonianParams {
+    pub const fn new(
+        fluid: FluidParams,
+        yield_stress: Real,
+        consistency_k: Real,
+        flow_index_n: Real,
+    ) -> Self {
+        Self {
+            fluid,
+            yield_stress,
+            consistency_k,
+            flow_index_n,
+            max_viscosity: 1.5,
+        }
+    }
+
+    /// Bingham-like mud: holds small piles, flows once pushed hard enough.
+    pub const fn mud() -> Self {
+        Self::new(
+            FluidParams::new("mud", 2.5, crate::config::EOS_STIFFNESS, 4),
+            2.0,
+            0.05,
+            1.0,
+        )
+    }
+
+    /// Shear-thinning paint.
+    pub const fn paint() -> Self {
+        Self::new(
+            FluidParams::new("paint", 2.2, crate::config::EOS_STIFFNESS, 4),
+            0.5,
+            0.2,
+            0.5,
+        )
+    }
+
+    pub const fn with_max_viscosity(mut self, max_viscosity: Real) -> Self {
+        self.max_viscosity = max_viscosity;
+        self
+    }
+
+    /// Apparent viscosity `tau_y / rate + k rate^(n - 1)` at the given shear
+    /// rate, clamped to `max_viscosity` so a resting fluid (`rate` near zero)
+    /// stays finite.
+    pub fn apparent_viscosity(&self, shear_rate: Real) -> Real {
+        if shear_rate <= Real::EPSILON {
+            return self.max_viscosity;
+        }
+        let viscosity = self.yield_stress / shear_rate
+            + self.consistency_k * shear_rate.powf(self.flow_index_n - 1.0);
+        viscosity.min(self.max_viscosity)
+    }
+}
+
+impl Default for NonNewtonianParams {
+    fn default() -> Self {
+        Self::mud()
+    }
+}
+
+/// Water EOS pressure plus a viscous term with the shear-rate dependent
+/// apparent viscosity.
+pub fn calculate_stress(
+    particle: &Particle,
+    density: Real,
+    params: &SolverParams,
+    material: &NonNewtonianParams,
+) -> Matrix {
+    let inviscid = material.fluid.with_dynamic_viscosity(0.0);
+    let pressure = water::calculate_stress(particle, density, params, &inviscid);
+
+    let jacobian = math::matrix_determinant(&particle.deformation_gradient);
+    let deviatoric_strain =
+        physics::deviatoric_part(&physics::strain_rate(&particle.velocity_gradient));
+    // Scalar shear rate sqrt(2 D:D) of the deviatoric strain rate
+    let shear_rate = (2.0 * deviatoric_strain.norm_squared()).sqrt();
+    let viscosity = material.apparent_viscosity(shear_rate);
+
+    pressure + deviatoric_strain * (2.0 * viscosity * jacobian)
+}
+
+/// Non-Newtonian fluids keep an isotropic deformation gradient.
+pub fn project_deformation(particle: &mut Particle) {
+    water::project_deformation(particle);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::MaterialType;
+    use crate::test_support::spread_after_one_second;
+
+    #[test]
+    fn apparent_viscosity_is_capped_at_rest_and_thins_past_yield() {
+        let mud = NonNewtonianParams::mud();
+        assert_eq!(mud.apparent_viscosity(0.0), mud.max_viscosity);
+        assert_eq!(mud.apparent_viscosity(1.0e-3), mud.max_viscosity);
+
+        // Far past yield only the power-law part is left
+        let fast = mud.apparent_viscosity(1.0e4);
+        assert!((fast - mud.consistency_k).abs() < 1e-3);
+
+        let paint = NonNewtonianParams::paint();
+        assert!(paint.apparent_viscosity(100.0) < paint.apparent_viscosity(10.0));
+    }
+
+    #[test]
+    fn yield_stress_keeps_a_blob_from_slumping() {
+        let mud = NonNewtonianParams::mud();
+        let runny = NonNewtonianParams {
+            yield_stress: 0.0,
+            ..mud
+        };
+        let held = spread_after_one_second(MaterialType::non_newtonian(mud));
+        let slumped = spread_after_one_second(MaterialType::non_newtonian(runny));
+        assert!(held.is_finite());
+        assert!(held + 2.0 < slumped, "mud {held} vs runny {slumped}");
+    }
+}
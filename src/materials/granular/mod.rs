@@ -3,3 +3,5 @@
 //! These materials can flow like liquids but also pile up like solids.
 
 pub mod sand;
+
+pub use sand::SandParams;
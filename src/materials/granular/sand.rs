@@ -1,18 +1,225 @@
-//! Sand material (placeholder)
+//! Drucker-Prager sand
 //!
-//! Future sand implementation.
+//! Elasticity on the Hencky (log) strain plus a return mapping onto the
+//! Drucker-Prager cone after every step (Klar et al. 2016). Sand piles up at
+//! its angle of repose instead of flowing flat.
 
-// Placeholder for future sand material implementation
-// Will use Drucker-Prager plasticity model for granular materials
+use crate::core::Particle;
+use crate::materials::utils::physics;
+use crate::math::{self, DIM, Matrix, Real, Vector};
 
-// pub fn calculate_stress(particle: &Particle, density: f32) -> Mat2 {
-//     // TODO: Implement Drucker-Prager sand stress calculation
-//     Mat2::ZERO
-// }
+/// Parameters describing a Drucker-Prager granular material.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SandParams {
+    #[cfg_attr(
+        feature = "serde-serialize",
+        serde(deserialize_with = "crate::materials::utils::deserialize_name")
+    )]
+    pub name: &'static str,
+    pub young_modulus: Real,
+    pub poisson_ratio: Real,
+    /// First Lamé parameter, derived from the two moduli above.
+    pub lambda: Real,
+    /// Shear modulus, derived from the two moduli above.
+    pub mu: Real,
+    /// Base friction angle in radians; sets the angle of repose.
+    pub friction_angle: Real,
+    /// Tensile log-strain each axis tolerates before the grains separate.
+    pub cohesion: Real,
+    /// Friction-angle hardening `h1` (radians per unit plastic strain).
+    pub hardening_gain: Real,
+    /// Decay rate `h2` of the hardening term.
+    pub hardening_decay: Real,
+    /// Friction-angle offset `h3` (radians) of the hardening term.
+    pub hardening_offset: Real,
+}
 
-// pub fn update_deformation(particle: &mut Particle, velocity_gradient: Mat2, dt: f32) {
-//     // TODO: Implement sand deformation with yield surface
-// }
+impl SandParams {
+    /// Sand with the given friction angle in degrees and the hardening
+    /// constants from Klar et al.
+    pub fn new(
+        name: &'static str,
+        young_modulus: Real,
+        poisson_ratio: Real,
+        friction_angle_degrees: Real,
+    ) -> Self {
+        let (lambda, mu) = physics::lame_lambda_mu(young_modulus, poisson_ratio);
+        Self {
+            name,
+            young_modulus,
+            poisson_ratio,
+            lambda,
+            mu,
+            friction_angle: friction_angle_degrees.to_radians(),
+            cohesion: 0.0,
+            hardening_gain: (9.0 as Real).to_radians(),
+            hardening_decay: 0.2,
+            hardening_offset: (10.0 as Real).to_radians(),
+        }
+    }
 
-// pub fn is_fluid() -> bool { false }
-// pub fn target_density() -> f32 { crate::constants::REST_DENSITY }
+    /// Dry sand with a 35 degree friction angle.
+    pub fn sand() -> Self {
+        Self::new("sand", 600.0, 0.3, 35.0)
+    }
+
+    pub fn with_cohesion(mut self, cohesion: Real) -> Self {
+        self.cohesion = cohesion;
+        self
+    }
+
+    /// Cone slope `alpha` for the accumulated plastic strain `q`.
+    fn cone_slope(&self, q: Real) -> Real {
+        let hardening =
+            (self.hardening_gain * q - self.hardening_offset) * (-self.hardening_decay * q).exp();
+        let angle = self.friction_angle + hardening;
+        let sin = angle.sin();
+        (2.0 as Real / 3.0).sqrt() * (2.0 * sin) / (3.0 - sin)
+    }
+}
+
+impl Default for SandParams {
+    fn default() -> Self {
+        Self::sand()
+    }
+}
+
+/// Hencky-strain stress in the Kirchhoff form the P2G scatter expects:
+/// `U (2 mu ln(S) + lambda tr(ln(S)) I) U^T` for `F = U S V^T`.
+pub fn calculate_stress(particle: &Particle, params: &SandParams) -> Matrix {
+    let svd = particle.deformation_gradient.svd(true, false);
+    let Some(u) = svd.u else {
+        return math::zero_matrix();
+    };
+    let strain = svd.singular_values.map(|s| s.max(1.0e-6).ln());
+    let principal = strain * (2.0 * params.mu) + Vector::repeat(params.lambda * strain.sum());
+    u * math::diagonal_from_vec(principal) * math::matrix_transpose(&u)
+}
+
+/// Return mapping onto the Drucker-Prager cone.
+///
+/// Strain in tension beyond the cohesion collapses onto the cone tip, sheared
+/// strain outside the cone is pulled back onto its surface. The volume lost
+/// this way is tracked in `log_volume_gain` so that later compression first
+/// restores it.
+pub fn project_deformation(particle: &mut Particle, params: &SandParams) {
+    let dim = DIM as Real;
+    let mut svd = particle.deformation_gradient.svd(true, true);
+    let singular = svd.singular_values.map(|s| s.max(1.0e-6));
+    let plasticity = &mut particle.plasticity;
+
+    let strain = singular.map(|s| s.ln()) + Vector::repeat(plasticity.log_volume_gain / dim);
+    let tensile_trace = strain.sum() - dim * params.cohesion;
+    let deviatoric = strain - Vector::repeat(strain.sum() / dim);
+    let deviatoric_norm = deviatoric.norm();
+
+    let (projected, plastic_strain) = if tensile_trace > 0.0 {
+        let tip = Vector::repeat(params.cohesion);
+        (tip, (strain - tip).norm())
+    } else {
+        if deviatoric_norm <= 1.0e-9 {
+            return;
+        }
+        let alpha = params.cone_slope(plasticity.plastic_hardening);
+        let gamma = deviatoric_norm
+            + (dim * params.lambda + 2.0 * params.mu) / (2.0 * params.mu) * tensile_trace * alpha;
+        if gamma <= 0.0 {
+            // Inside the yield surface
+            return;
+        }
+        (strain - deviatoric * (gamma / deviatoric_norm), gamma)
+    };
+
+    let new_singular = projected.map(|e| e.exp());
+    let previous_det = singular.product();
+    let new_det = new_singular.product();
+    plasticity.log_volume_gain += previous_det.ln() - new_det.ln();
+    plasticity.plastic_hardening += plastic_strain;
+    particle.plastic_deformation_gradient_det *= previous_det / new_det;
+
+    svd.singular_values = new_singular;
+    if let Ok(deformation) = svd.recompose() {
+        particle.deformation_gradient = deformation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::core::MpmState;
+    use crate::materials::MaterialType;
+    use crate::math::{diagonal_from_vec, zero_vector};
+    use crate::test_support::{horizontal_extent, run_frames};
+
+    #[test]
+    fn return_mapping_keeps_compression_and_drops_tension() {
+        let params = SandParams::sand();
+        let mut particle = Particle::new(zero_vector(), MaterialType::sand(params));
+
+        // Mild isotropic compression sits inside the cone
+        let compressed = diagonal_from_vec(Vector::new(0.95, 0.95));
+        particle.deformation_gradient = compressed;
+        project_deformation(&mut particle, &params);
+        assert!((particle.deformation_gradient - compressed).norm() < 1e-5);
+
+        // Stretched grains separate back to the stress-free tip
+        particle.deformation_gradient = diagonal_from_vec(Vector::new(1.2, 1.1));
+        project_deformation(&mut particle, &params);
+        assert!(calculate_stress(&particle, &params).norm() < 1e-3);
+        assert!(particle.plasticity.log_volume_gain > 0.0);
+
+        // Pure shear is pulled back onto the cone
+        let mut particle = Particle::new(zero_vector(), MaterialType::sand(params));
+        particle.deformation_gradient = diagonal_from_vec(Vector::new(1.2, 1.0 / 1.2));
+        project_deformation(&mut particle, &params);
+        let strain = particle
+            .deformation_gradient
+            .svd(false, false)
+            .singular_values;
+        assert!((strain.x.ln() - strain.y.ln()).abs() < 1e-3);
+    }
+
+    /// Width and height of a 8x24 cell column of `material` after it
+    /// collapsed under gravity for two seconds.
+    fn collapsed_extent(material: MaterialType) -> (Real, Real) {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..48 {
+            for i in 0..16 {
+                let position = Vector::new(60.25, 4.25) + Vector::new(i as Real, j as Real) * 0.5;
+                let mut particle = Particle::new(position, material.clone()).with_mass(0.25);
+                particle.volume0 = 0.25;
+                state.add_particle(particle);
+            }
+        }
+
+        let world = run_frames(state, 120);
+
+        let particles = world.resource::<MpmState>().particles();
+        let max_y = particles
+            .iter()
+            .map(|p| p.position.y)
+            .fold(Real::MIN, Real::max);
+        assert!(max_y.is_finite());
+        (horizontal_extent(particles), max_y)
+    }
+
+    #[test]
+    fn sand_column_collapses_to_a_slope_instead_of_spreading_flat() {
+        let (sand_width, sand_height) = collapsed_extent(MaterialType::sand(SandParams::sand()));
+        let (water_width, water_height) = collapsed_extent(MaterialType::water());
+        assert!(
+            sand_width < water_width,
+            "sand {sand_width} vs water {water_width}"
+        );
+        assert!(
+            sand_height > water_height,
+            "sand {sand_height} vs water {water_height}"
+        );
+        assert!(sand_height > 8.0);
+    }
+}
@@ -8,19 +8,36 @@ use bevy::prelude::*;
 use crate::config::SolverParams;
 use crate::core::Particle;
 use crate::materials::families::FluidParams;
-use crate::materials::fluids::water;
+use crate::materials::fluids::{
+    AnisotropicFluidParams, GasParams, NonNewtonianParams, anisotropic, gas, non_newtonian, water,
+};
+use crate::materials::granular::{SandParams, sand};
+use crate::materials::solids::{
+    CorotatedParams, ElasticParams, SnowParams, corotated, elastic, snow,
+};
 
-use crate::math::Matrix;
+use crate::math::{Matrix, Real};
 
 /// Shared behaviour that every material must implement.
 pub trait MaterialModel {
-    fn compute_stress(&self, particle: &Particle, density: f32, params: &SolverParams) -> Matrix;
+    fn compute_stress(&self, particle: &Particle, density: Real, params: &SolverParams) -> Matrix;
     fn project_deformation(&self, particle: &mut Particle);
 }
 
 #[derive(Component, Debug, Clone)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum MaterialType {
     Fluid(FluidParams),
+    Elastic(ElasticParams),
+    Corotated(CorotatedParams),
+    Sand(SandParams),
+    Snow(SnowParams),
+    Gas(GasParams),
+    NonNewtonian(NonNewtonianParams),
+    AnisotropicFluid(AnisotropicFluidParams),
 }
 
 impl MaterialType {
@@ -32,27 +49,95 @@ impl MaterialType {
         Self::Fluid(params)
     }
 
+    pub fn elastic(params: ElasticParams) -> Self {
+        Self::Elastic(params)
+    }
+
+    pub fn corotated(params: CorotatedParams) -> Self {
+        Self::Corotated(params)
+    }
+
+    pub fn sand(params: SandParams) -> Self {
+        Self::Sand(params)
+    }
+
+    pub fn snow(params: SnowParams) -> Self {
+        Self::Snow(params)
+    }
+
+    pub fn gas(params: GasParams) -> Self {
+        Self::Gas(params)
+    }
+
+    pub fn non_newtonian(params: NonNewtonianParams) -> Self {
+        Self::NonNewtonian(params)
+    }
+
+    pub fn anisotropic_fluid(params: AnisotropicFluidParams) -> Self {
+        Self::AnisotropicFluid(params)
+    }
+
     pub fn is_fluid(&self) -> bool {
-        matches!(self, Self::Fluid(_))
+        matches!(
+            self,
+            Self::Fluid(_) | Self::Gas(_) | Self::NonNewtonian(_) | Self::AnisotropicFluid(_)
+        )
+    }
+
+    /// Rest density of fluid-like materials; solids have none of their own
+    /// and rest at `Particle::rest_density`.
+    pub fn rest_density(&self) -> Option<Real> {
+        match self {
+            Self::Fluid(fluid) => Some(fluid.rest_density),
+            Self::Gas(vapour) => Some(vapour.rest_density),
+            Self::NonNewtonian(slurry) => Some(slurry.fluid.rest_density),
+            Self::AnisotropicFluid(fibres) => Some(fibres.fluid.rest_density),
+            Self::Elastic(_) | Self::Corotated(_) | Self::Sand(_) | Self::Snow(_) => None,
+        }
     }
 
     pub fn material_name(&self) -> &'static str {
         match self {
             Self::Fluid(fluid) => fluid.name,
+            Self::Elastic(solid) => solid.name,
+            Self::Corotated(solid) => solid.name,
+            Self::Sand(grains) => grains.name,
+            Self::Snow(flakes) => flakes.name,
+            Self::Gas(vapour) => vapour.name,
+            Self::NonNewtonian(slurry) => slurry.fluid.name,
+            Self::AnisotropicFluid(fibres) => fibres.fluid.name,
         }
     }
 }
 
 impl MaterialModel for MaterialType {
-    fn compute_stress(&self, particle: &Particle, density: f32, params: &SolverParams) -> Matrix {
+    fn compute_stress(&self, particle: &Particle, density: Real, params: &SolverParams) -> Matrix {
         match self {
             MaterialType::Fluid(fluid) => water::calculate_stress(particle, density, params, fluid),
+            MaterialType::Elastic(solid) => elastic::calculate_stress(particle, solid),
+            MaterialType::Corotated(solid) => corotated::calculate_stress(particle, solid),
+            MaterialType::Sand(grains) => sand::calculate_stress(particle, grains),
+            MaterialType::Snow(flakes) => snow::calculate_stress(particle, flakes),
+            MaterialType::Gas(vapour) => gas::calculate_stress(particle, density, vapour),
+            MaterialType::NonNewtonian(slurry) => {
+                non_newtonian::calculate_stress(particle, density, params, slurry)
+            }
+            MaterialType::AnisotropicFluid(fibres) => {
+                anisotropic::calculate_stress(particle, density, params, fibres)
+            }
         }
     }
 
     fn project_deformation(&self, particle: &mut Particle) {
         match self {
             MaterialType::Fluid(_) => water::project_deformation(particle),
+            MaterialType::Elastic(_) => elastic::project_deformation(particle),
+            MaterialType::Corotated(_) => corotated::project_deformation(particle),
+            MaterialType::Sand(grains) => sand::project_deformation(particle, grains),
+            MaterialType::Snow(flakes) => snow::project_deformation(particle, flakes),
+            MaterialType::Gas(_) => gas::project_deformation(particle),
+            MaterialType::NonNewtonian(_) => non_newtonian::project_deformation(particle),
+            MaterialType::AnisotropicFluid(_) => anisotropic::project_deformation(particle),
         }
     }
 }
@@ -3,8 +3,8 @@
 //! Three categories:
 //!
 //! * `fluid` - Water and other fluids
-//! * `solid` - Elastic materials (coming soon)
-//! * `granular` - Sand-like materials (coming soon)
+//! * `solid` - Elastic materials
+//! * `granular` - Sand-like materials
 
 pub mod families;
 pub mod fluids;
@@ -15,7 +15,10 @@ pub mod utils;
 
 // Re-export the main material type for convenience
 pub use families::FluidParams;
+pub use fluids::{AnisotropicFluidParams, GasParams, NonNewtonianParams};
+pub use granular::SandParams;
 pub use material_types::{MaterialModel, MaterialType};
+pub use solids::{CorotatedParams, ElasticParams, SnowParams};
 
 // Re-export physics utilities for easy access
 pub use utils::check;
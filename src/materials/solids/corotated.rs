@@ -0,0 +1,100 @@
+//! Fixed-corotated elastic solid
+//!
+//! The elasticity model of Stomakhin et al. 2013: strain is measured after
+//! factoring out the rotation of `F`, so spinning pieces stay stress-free.
+
+use crate::core::Particle;
+use crate::materials::utils::{self, physics};
+use crate::math::{self, Matrix, Real};
+
+/// Parameters describing a fixed-corotated elastic solid.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CorotatedParams {
+    #[cfg_attr(
+        feature = "serde-serialize",
+        serde(deserialize_with = "crate::materials::utils::deserialize_name")
+    )]
+    pub name: &'static str,
+    pub young_modulus: Real,
+    pub poisson_ratio: Real,
+    /// First Lamé parameter, derived from the two moduli above.
+    pub lambda: Real,
+    /// Shear modulus, derived from the two moduli above.
+    pub mu: Real,
+}
+
+impl CorotatedParams {
+    pub fn new(name: &'static str, young_modulus: Real, poisson_ratio: Real) -> Self {
+        let (lambda, mu) = physics::lame_lambda_mu(young_modulus, poisson_ratio);
+        Self {
+            name,
+            young_modulus,
+            poisson_ratio,
+            lambda,
+            mu,
+        }
+    }
+
+    /// Firm rubber-like solid that stays stable at the default timestep.
+    pub fn rubber() -> Self {
+        Self::new("rubber", 400.0, 0.3)
+    }
+}
+
+impl Default for CorotatedParams {
+    fn default() -> Self {
+        Self::rubber()
+    }
+}
+
+/// Fixed-corotated stress in the Kirchhoff form `P F^T` the P2G scatter
+/// expects, with `P = 2 mu (F - R) + lambda (J - 1) J F^-T`.
+/// Tension fades out as the particle cracks (`Particle::phase`).
+pub fn calculate_stress(particle: &Particle, params: &CorotatedParams) -> Matrix {
+    let stress = corotated_stress(&particle.deformation_gradient, params.lambda, params.mu);
+    utils::degrade_tension(stress, particle.jacobian(), particle.phase)
+}
+
+/// Fixed-corotated Kirchhoff stress of `deformation` for the given Lamé
+/// parameters, shared with the hardened snow model.
+pub fn corotated_stress(deformation: &Matrix, lambda: Real, mu: Real) -> Matrix {
+    let (rotation, _) = math::polar_decomposition_2x2(deformation);
+    let jacobian = math::matrix_determinant(deformation);
+
+    (deformation - rotation) * math::matrix_transpose(deformation) * (2.0 * mu)
+        + math::identity_matrix() * (lambda * (jacobian - 1.0) * jacobian)
+}
+
+/// Corotated solids keep their deformation gradient as is.
+pub fn project_deformation(_particle: &mut Particle) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::MaterialType;
+    use crate::math::{Vector, diagonal_from_vec, zero_vector};
+
+    fn rotation(angle: Real) -> Matrix {
+        let (sin, cos) = angle.sin_cos();
+        Matrix::new(cos, -sin, sin, cos)
+    }
+
+    #[test]
+    fn pure_rotation_is_stress_free() {
+        let params = CorotatedParams::rubber();
+        let mut particle = Particle::new(zero_vector(), MaterialType::corotated(params));
+        for angle in [0.3, 1.7, 3.0, -2.5] {
+            particle.deformation_gradient = rotation(angle);
+            let stress = calculate_stress(&particle, &params);
+            assert!(stress.norm() < 1e-3, "angle {angle}: {stress}");
+        }
+
+        // A rotated stretch still resists
+        particle.deformation_gradient = rotation(1.0) * diagonal_from_vec(Vector::new(1.2, 1.0));
+        assert!(calculate_stress(&particle, &params).norm() > 10.0);
+    }
+}
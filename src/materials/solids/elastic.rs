@@ -1,18 +1,129 @@
-//! Elastic material (placeholder)
+//! Neo-Hookean elastic solid
 //!
-//! Future elastic solid implementation.
+//! Tracks the full deformation gradient, so the material remembers its rest
+//! shape and springs back to it.
 
-// Placeholder for future simple elastic material implementation
-// Will use volume-only tracking like mpm88.py approach
+use crate::core::Particle;
+use crate::materials::utils::{self, physics};
+use crate::math::{self, Matrix, Real};
 
-// pub fn calculate_stress(particle: &Particle, volume_ratio: f32, youngs_modulus: f32) -> Mat2 {
-//     // TODO: Implement simple elastic stress: -4 * E * (J - 1) / dx^2
-//     Mat2::ZERO
-// }
+/// Parameters describing a Neo-Hookean elastic solid.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ElasticParams {
+    #[cfg_attr(
+        feature = "serde-serialize",
+        serde(deserialize_with = "crate::materials::utils::deserialize_name")
+    )]
+    pub name: &'static str,
+    pub young_modulus: Real,
+    pub poisson_ratio: Real,
+    /// First Lamé parameter, derived from the two moduli above.
+    pub lambda: Real,
+    /// Shear modulus, derived from the two moduli above.
+    pub mu: Real,
+}
 
-// pub fn update_deformation(volume_ratio: &mut f32, velocity_gradient: Mat2, dt: f32) {
-//     // TODO: Simple volume tracking: J *= 1 + dt * trace(velocity_gradient)
-// }
+impl ElasticParams {
+    pub fn new(name: &'static str, young_modulus: Real, poisson_ratio: Real) -> Self {
+        let (lambda, mu) = physics::lame_lambda_mu(young_modulus, poisson_ratio);
+        Self {
+            name,
+            young_modulus,
+            poisson_ratio,
+            lambda,
+            mu,
+        }
+    }
 
-// pub fn is_fluid() -> bool { false }
-// pub fn target_density() -> f32 { crate::constants::REST_DENSITY }
+    /// Soft jelly-like solid that stays stable at the default timestep.
+    pub fn jelly() -> Self {
+        Self::new("jelly", 200.0, 0.3)
+    }
+}
+
+impl Default for ElasticParams {
+    fn default() -> Self {
+        Self::jelly()
+    }
+}
+
+/// Neo-Hookean stress in the Kirchhoff form `P F^T` the P2G scatter expects.
+///
+/// With `P = mu (F - F^-T) + lambda ln(J) F^-T` this is
+/// `mu (F F^T - I) + lambda ln(J) I`, i.e. the Cauchy stress scaled by `J`.
+/// Tension fades out as the particle cracks (`Particle::phase`).
+pub fn calculate_stress(particle: &Particle, params: &ElasticParams) -> Matrix {
+    let deformation = particle.deformation_gradient;
+    // Inverted elements would make ln(J) undefined; treat them as fully crushed
+    let jacobian = math::matrix_determinant(&deformation).max(1.0e-6);
+    let left_cauchy_green = deformation * math::matrix_transpose(&deformation);
+
+    let stress = (left_cauchy_green - math::identity_matrix()) * params.mu
+        + math::identity_matrix() * (params.lambda * jacobian.ln());
+    utils::degrade_tension(stress, jacobian, particle.phase)
+}
+
+/// Elastic solids keep their deformation gradient as is.
+pub fn project_deformation(_particle: &mut Particle) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::MpmState;
+    use crate::materials::MaterialType;
+    use crate::math::{Vector, diagonal_from_vec, zero_vector};
+    use crate::test_support::{frame_world, solver_schedule};
+
+    #[test]
+    fn rest_state_is_stress_free_and_stretching_pulls_back() {
+        let params = ElasticParams::jelly();
+        let mut particle = Particle::new(zero_vector(), MaterialType::elastic(params));
+        assert!(calculate_stress(&particle, &params).norm() < 1e-5);
+
+        particle.deformation_gradient = diagonal_from_vec(Vector::new(1.3, 1.0));
+        let stress = calculate_stress(&particle, &params);
+        assert!(stress[(0, 0)] > 0.0);
+    }
+
+    #[test]
+    fn stretched_block_relaxes_toward_its_rest_shape() {
+        const STRETCH: Real = 1.3;
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let material = MaterialType::elastic(ElasticParams::jelly());
+        for j in 0..20 {
+            for i in 0..20 {
+                let offset = Vector::new(i as Real * STRETCH, j as Real) * 0.5;
+                let position = Vector::new(50.25, 50.25) + offset;
+                let mut particle = Particle::new(position, material.clone()).with_mass(0.25);
+                particle.volume0 = 0.25;
+                particle.deformation_gradient = diagonal_from_vec(Vector::new(STRETCH, 1.0));
+                state.add_particle(particle);
+            }
+        }
+
+        let mut world = frame_world(state);
+        let mut schedule = solver_schedule();
+
+        let mut least_stretch = STRETCH;
+        for _ in 0..90 {
+            schedule.run(&mut world);
+            let particles = world.resource::<MpmState>().particles();
+            let stretch = particles
+                .iter()
+                .map(|particle| particle.deformation_gradient[(0, 0)])
+                .sum::<Real>()
+                / particles.len() as Real;
+            assert!(stretch.is_finite());
+            least_stretch = least_stretch.min(stretch);
+        }
+        assert!(
+            least_stretch < 1.15,
+            "block stayed stretched at {least_stretch}"
+        );
+    }
+}
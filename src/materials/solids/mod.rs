@@ -2,4 +2,10 @@
 //!
 //! These materials hold their shape and can bounce back when deformed.
 
+pub mod corotated;
 pub mod elastic;
+pub mod snow;
+
+pub use corotated::CorotatedParams;
+pub use elastic::ElasticParams;
+pub use snow::SnowParams;
@@ -0,0 +1,138 @@
+//! Elastoplastic snow
+//!
+//! The model of Stomakhin et al. 2013: fixed-corotated elasticity whose
+//! singular values are clamped to a small window, the excess becoming
+//! permanent plastic deformation that hardens (compression) or softens
+//! (stretching) the material. Snow packs when squeezed and breaks apart when
+//! pulled.
+
+use crate::core::Particle;
+use crate::materials::utils::physics;
+use crate::math::{Matrix, Real};
+
+use super::corotated::corotated_stress;
+
+/// Parameters describing an elastoplastic snow material.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SnowParams {
+    #[cfg_attr(
+        feature = "serde-serialize",
+        serde(deserialize_with = "crate::materials::utils::deserialize_name")
+    )]
+    pub name: &'static str,
+    pub young_modulus: Real,
+    pub poisson_ratio: Real,
+    /// First Lamé parameter, derived from the two moduli above.
+    pub lambda: Real,
+    /// Shear modulus, derived from the two moduli above.
+    pub mu: Real,
+    /// Compression an elastic singular value may take (`theta_c`).
+    pub critical_compression: Real,
+    /// Stretch an elastic singular value may take (`theta_s`).
+    pub critical_stretch: Real,
+    /// Exponential hardening coefficient (`xi`), typically 3 to 10.
+    pub hardening_coeff: Real,
+}
+
+impl SnowParams {
+    pub fn new(name: &'static str, young_modulus: Real, poisson_ratio: Real) -> Self {
+        let (lambda, mu) = physics::lame_lambda_mu(young_modulus, poisson_ratio);
+        Self {
+            name,
+            young_modulus,
+            poisson_ratio,
+            lambda,
+            mu,
+            critical_compression: 2.5e-2,
+            critical_stretch: 7.5e-3,
+            hardening_coeff: 10.0,
+        }
+    }
+
+    /// Packed snow with the constants from the paper.
+    pub fn snow() -> Self {
+        Self::new("snow", 400.0, 0.2)
+    }
+}
+
+impl Default for SnowParams {
+    fn default() -> Self {
+        Self::snow()
+    }
+}
+
+/// Corotated stress of the elastic part of `F`, with both Lamé parameters
+/// scaled by the particle's `elastic_hardening`.
+pub fn calculate_stress(particle: &Particle, params: &SnowParams) -> Matrix {
+    let hardening = particle.plasticity.elastic_hardening;
+    corotated_stress(
+        &particle.deformation_gradient,
+        params.lambda * hardening,
+        params.mu * hardening,
+    )
+}
+
+/// Clamps the elastic singular values into
+/// `[1 - critical_compression, 1 + critical_stretch]` and moves the rest into
+/// `plastic_deformation_gradient_det`. `plastic_hardening` keeps the
+/// resulting compaction `1 - J_p` and `elastic_hardening` the factor
+/// `exp(hardening_coeff * (1 - J_p))` it hardens the moduli by.
+pub fn project_deformation(particle: &mut Particle, params: &SnowParams) {
+    let mut svd = particle.deformation_gradient.svd(true, true);
+    let clamped = svd.singular_values.map(|s| {
+        s.clamp(
+            1.0 - params.critical_compression,
+            1.0 + params.critical_stretch,
+        )
+    });
+
+    particle.plastic_deformation_gradient_det *= svd.singular_values.product() / clamped.product();
+    let compaction = 1.0 - particle.plastic_deformation_gradient_det;
+    particle.plasticity.plastic_hardening = compaction;
+    particle.plasticity.elastic_hardening = (params.hardening_coeff * compaction).exp();
+
+    svd.singular_values = clamped;
+    if let Ok(deformation) = svd.recompose() {
+        particle.deformation_gradient = deformation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::MaterialType;
+    use crate::math::{Vector, diagonal_from_vec, zero_vector};
+
+    #[test]
+    fn compression_past_the_critical_value_is_permanent() {
+        let params = SnowParams::snow();
+        let mut particle = Particle::new(zero_vector(), MaterialType::snow(params));
+
+        // Within the elastic window nothing changes
+        particle.deformation_gradient = diagonal_from_vec(Vector::new(0.99, 1.0));
+        project_deformation(&mut particle, &params);
+        assert!((particle.plastic_deformation_gradient_det - 1.0).abs() < 1e-6);
+
+        particle.deformation_gradient = diagonal_from_vec(Vector::new(0.9, 0.9));
+        project_deformation(&mut particle, &params);
+        let elastic_jacobian = particle.jacobian();
+        assert!((elastic_jacobian - 0.975 * 0.975).abs() < 1e-4);
+        let plastic_jacobian = particle.plastic_jacobian();
+        assert!(plastic_jacobian < 0.9);
+        let plasticity = particle.plasticity;
+        assert!((plasticity.plastic_hardening - (1.0 - plastic_jacobian)).abs() < 1e-6);
+        let hardening = (params.hardening_coeff * plasticity.plastic_hardening).exp();
+        assert!((plasticity.elastic_hardening - hardening).abs() < 1e-4);
+        assert!(plasticity.elastic_hardening > 1.0);
+
+        // Unloaded, the snow rests at the compacted volume
+        particle.deformation_gradient = crate::math::identity_matrix();
+        project_deformation(&mut particle, &params);
+        assert!(calculate_stress(&particle, &params).norm() < 1e-3);
+        assert!(particle.jacobian() * particle.plastic_jacobian() < 0.9);
+    }
+}
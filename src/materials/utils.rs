@@ -23,6 +23,21 @@ pub fn stress_magnitude(stress: Matrix) -> Real {
     stress.norm()
 }
 
+/// Scales tensile stress down for cracked particles (`phase` 1 = intact,
+/// 0 = fully cracked); compression is always kept so broken pieces still
+/// collide. A small residual stiffness keeps cracked particles well-posed.
+///
+/// Degradation as in Wolper et al. 2019, <https://dl.acm.org/doi/10.1145/3306346.3322949>.
+#[inline]
+pub fn degrade_tension(stress: Matrix, jacobian: Real, phase: Real) -> Matrix {
+    const RESIDUAL: Real = 0.001;
+    if jacobian < 1.0 || phase >= 1.0 {
+        return stress;
+    }
+    let phase = phase.max(0.0);
+    stress * ((1.0 - RESIDUAL) * phase * phase + RESIDUAL)
+}
+
 /// Physics parameter conversions - universal MPM utilities.
 /// Used by many constitutive models for material calculations.
 pub mod physics {
@@ -92,7 +107,7 @@ pub mod check {
 
     #[inline]
     pub fn viscosity_ok(viscosity: Real) -> bool {
-        viscosity >= 0.0 && viscosity < 1e6 && viscosity.is_finite()
+        (0.0..1e6).contains(&viscosity) && viscosity.is_finite()
     }
 
     /// Check if deformation gradient determinant is reasonable.
@@ -113,3 +128,28 @@ pub mod check {
         nu > -1.0 && nu < 0.5 && nu.is_finite()
     }
 }
+
+/// Reads a material name back as the `&'static str` the parameter packs
+/// hold. Each distinct name is leaked once and reused afterwards, so loading
+/// many snapshots of the same materials doesn't grow memory.
+#[cfg(feature = "serde-serialize")]
+pub(crate) fn deserialize_name<'de, D>(deserializer: D) -> Result<&'static str, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    use std::sync::Mutex;
+
+    static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+    let name = String::deserialize(deserializer)?;
+    let mut names = NAMES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&interned) = names.iter().find(|&&interned| interned == name) {
+        return Ok(interned);
+    }
+    let interned: &'static str = Box::leak(name.into_boxed_str());
+    names.push(interned);
+    Ok(interned)
+}
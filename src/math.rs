@@ -1,11 +1,21 @@
-use nalgebra::{Matrix2, Vector2};
+use nalgebra::{SMatrix, SVector};
 
+#[cfg(not(feature = "f64"))]
 pub type Real = f32;
+#[cfg(feature = "f64")]
+pub type Real = f64;
+
+/// Constants (`PI`, `TAU`, ...) at `Real` precision.
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
 pub const DIM: usize = 2;
 
-pub type Vector = Vector2<Real>;
-pub type Matrix = Matrix2<Real>;
-pub type Point = Vector2<Real>;
+pub type Vector = SVector<Real, DIM>;
+pub type Matrix = SMatrix<Real, DIM, DIM>;
+pub type Point = SVector<Real, DIM>;
 
 #[inline(always)]
 pub fn zero_vector() -> Vector {
@@ -14,7 +24,7 @@ pub fn zero_vector() -> Vector {
 
 #[inline(always)]
 pub fn repeat_vector(value: Real) -> Vector {
-    Vector::new(value, value)
+    Vector::repeat(value)
 }
 
 #[inline(always)]
@@ -44,7 +54,7 @@ pub fn matrix_determinant(m: &Matrix) -> Real {
 
 #[inline(always)]
 pub fn diagonal_from_value(value: Real) -> Matrix {
-    Matrix::from_diagonal(&Vector::new(value, value))
+    Matrix::from_diagonal_element(value)
 }
 
 #[inline(always)]
@@ -68,6 +78,75 @@ pub fn quadratic_bspline_weights(offset: Real) -> [Real; 3] {
     ]
 }
 
+/// Cubic B-spline `N(t)`, supported on `|t| < 2`.
+#[inline(always)]
+pub fn cubic_bspline(t: Real) -> Real {
+    let t = t.abs();
+    if t < 1.0 {
+        0.5 * t * t * t - t * t + 2.0 / 3.0
+    } else if t < 2.0 {
+        let s = 2.0 - t;
+        s * s * s / 6.0
+    } else {
+        0.0
+    }
+}
+
+/// Weights of the four nodes around a particle `offset` (in `[0, 1)`) past
+/// the second of them.
+#[inline(always)]
+pub fn cubic_bspline_weights(offset: Real) -> [Real; 4] {
+    [
+        cubic_bspline(offset + 1.0),
+        cubic_bspline(offset),
+        cubic_bspline(1.0 - offset),
+        cubic_bspline(2.0 - offset),
+    ]
+}
+
+/// Closed-form polar decomposition `F = R S` of a 2x2 matrix into a rotation
+/// `R` and a symmetric `S`.
+#[inline]
+pub fn polar_decomposition_2x2(m: &Matrix) -> (Matrix, Matrix) {
+    let x = m[(0, 0)] + m[(1, 1)];
+    let y = m[(1, 0)] - m[(0, 1)];
+    let norm = (x * x + y * y).sqrt();
+    let (cos, sin) = if norm > 1.0e-12 {
+        (x / norm, y / norm)
+    } else {
+        (1.0, 0.0)
+    };
+    let rotation = Matrix::new(cos, -sin, sin, cos);
+    let symmetric = rotation.transpose() * m;
+    (rotation, symmetric)
+}
+
+/// `m` with its singular values clamped into `[min, max]`, keeping its
+/// rotations; `m` itself if it is not finite or the SVD cannot be recomposed.
+pub fn clamp_singular_values(m: &Matrix, min: Real, max: Real) -> Matrix {
+    if !m.iter().all(|x| x.is_finite()) {
+        return *m;
+    }
+    let mut svd = m.svd(true, true);
+    svd.singular_values.apply(|s| *s = s.clamp(min, max));
+    svd.recompose().unwrap_or(*m)
+}
+
+/// Ratio of the largest to the smallest singular value of `m`; infinite for
+/// a singular (or non-finite) matrix.
+pub fn condition_number(m: &Matrix) -> Real {
+    if !m.iter().all(|x| x.is_finite()) {
+        return Real::INFINITY;
+    }
+    let singular_values = m.singular_values();
+    let (smallest, largest) = (singular_values.min(), singular_values.max());
+    if smallest > 1.0e-12 {
+        largest / smallest
+    } else {
+        Real::INFINITY
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DecomposedTensor {
     pub deviatoric_part: Matrix,
@@ -78,8 +157,9 @@ impl DecomposedTensor {
     pub fn decompose(tensor: &Matrix) -> Self {
         let spherical_part = matrix_trace(tensor) / (DIM as Real);
         let mut deviatoric_part = *tensor;
-        deviatoric_part[(0, 0)] -= spherical_part;
-        deviatoric_part[(1, 1)] -= spherical_part;
+        for i in 0..DIM {
+            deviatoric_part[(i, i)] -= spherical_part;
+        }
         Self {
             deviatoric_part,
             spherical_part,
@@ -95,29 +175,41 @@ impl DecomposedTensor {
 
     pub fn recompose(&self) -> Matrix {
         let mut result = self.deviatoric_part;
-        result[(0, 0)] += self.spherical_part;
-        result[(1, 1)] += self.spherical_part;
+        for i in 0..DIM {
+            result[(i, i)] += self.spherical_part;
+        }
         result
     }
 }
 
 // === Bevy Conversion Helpers ===
-// Convert nalgebra types to Bevy types for rendering
+// Convert nalgebra types to Bevy types for rendering. Bevy is f32 throughout,
+// so with the `f64` feature these round.
+
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)] // only needed with `f64`
+pub fn to_bevy_scalar(value: Real) -> f32 {
+    value as f32
+}
 
 #[inline(always)]
+#[allow(clippy::unnecessary_cast)] // only needed with `f64`
 pub fn to_bevy_vec2(v: &Vector) -> bevy::prelude::Vec2 {
-    bevy::prelude::Vec2::new(v.x, v.y)
+    bevy::prelude::Vec2::new(v.x as f32, v.y as f32)
 }
 
 #[inline(always)]
 pub fn from_bevy_vec2(v: bevy::prelude::Vec2) -> Vector {
-    Vector::new(v.x, v.y)
+    Vector::new(v.x as Real, v.y as Real)
 }
 
 #[inline(always)]
+#[allow(clippy::unnecessary_cast)] // only needed with `f64`
 pub fn to_bevy_mat2(m: &Matrix) -> bevy::prelude::Mat2 {
     bevy::prelude::Mat2::from_cols_array(&[
-        m[(0, 0)], m[(1, 0)],
-        m[(0, 1)], m[(1, 1)],
+        m[(0, 0)] as f32,
+        m[(1, 0)] as f32,
+        m[(0, 1)] as f32,
+        m[(1, 1)] as f32,
     ])
 }
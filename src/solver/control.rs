@@ -0,0 +1,142 @@
+//! Pausing and stepping
+//!
+//! `SimControl` gates every solver system `MpmPlugin` adds. While `paused`,
+//! frames go by without the simulation moving; setting `single_step`
+//! simulates exactly one more frame and clears it again.
+
+use bevy::prelude::*;
+
+/// Pause, single-step and slow-motion controls for the solver.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct SimControl {
+    pub paused: bool,
+    /// Simulate the next frame even while paused; cleared once it has run.
+    pub single_step: bool,
+    /// Scales the timestep the substeps advance by (0.5 is half speed).
+    /// Emitters, inflows and other per-frame systems keep the frame's.
+    pub speed: f32,
+    /// Frames simulated so far.
+    frame: u64,
+    /// Whether the current frame simulates.
+    running: bool,
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            single_step: false,
+            speed: 1.0,
+            frame: 0,
+            running: true,
+        }
+    }
+}
+
+impl SimControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Simulate one frame, then stay paused.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.single_step = true;
+    }
+
+    /// Frames simulated so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Whether this frame simulates, as decided by `advance_sim_control`.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Decides whether the coming frame simulates and counts it if so.
+    pub fn advance(&mut self) {
+        self.running = !self.paused || self.single_step;
+        if self.running {
+            self.frame += 1;
+            self.single_step = false;
+        }
+    }
+}
+
+/// Starts a frame: works out whether it simulates. Runs before every other
+/// solver system.
+pub fn advance_sim_control(control: Option<ResMut<SimControl>>) {
+    if let Some(mut control) = control {
+        control.advance();
+    }
+}
+
+/// Run condition for the solver systems: true unless `SimControl` holds the
+/// frame back.
+pub fn sim_running(control: Option<Res<SimControl>>) -> bool {
+    control.is_none_or(|control| control.is_running())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::core::{MpmState, Particle};
+    use crate::materials::MaterialType;
+    use crate::math::Vector;
+    use crate::test_support::{frame_world, solver_stages};
+
+    #[test]
+    fn a_single_step_while_paused_simulates_exactly_one_frame() {
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        state.add_particle(Particle::new(
+            Vector::new(64.0, 64.0),
+            MaterialType::water(),
+        ));
+
+        let mut world = frame_world(state);
+        world.insert_resource(SimControl::new());
+        let mut schedule = Schedule::default();
+        schedule.add_systems((
+            advance_sim_control,
+            solver_stages()
+                .run_if(sim_running)
+                .after(advance_sim_control),
+        ));
+        let height = |world: &World| world.resource::<MpmState>().particles()[0].position.y;
+
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<SimControl>().frame(), 1);
+
+        world.resource_mut::<SimControl>().pause();
+        let paused_at = height(&world);
+        for _ in 0..5 {
+            schedule.run(&mut world);
+        }
+        assert_eq!(world.resource::<SimControl>().frame(), 1);
+        assert_eq!(height(&world), paused_at);
+
+        world.resource_mut::<SimControl>().single_step = true;
+        for _ in 0..5 {
+            schedule.run(&mut world);
+        }
+        let control = world.resource::<SimControl>();
+        assert_eq!(control.frame(), 2);
+        assert!(control.paused && !control.single_step);
+        assert!(height(&world) < paused_at);
+    }
+}
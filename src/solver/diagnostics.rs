@@ -0,0 +1,197 @@
+//! Conservation diagnostics
+//!
+//! Insert `SimDiagnostics` (or enable `MpmPlugin::with_diagnostics`) and the
+//! totals a blow-up shows first in are recorded every frame, along with
+//! their range over the last `SimDiagnostics::WINDOW` frames.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::core::{MpmState, Particle};
+use crate::math::{Real, Vector, zero_vector};
+
+#[derive(Resource, Clone, Debug)]
+pub struct SimDiagnostics {
+    pub kinetic_energy: Real,
+    pub momentum: Vector,
+    /// About the origin, counting the spin the particles' affine velocity
+    /// fields carry as well as their motion; see `angular_momentum_of`.
+    pub angular_momentum: Real,
+    pub mass: Real,
+    pub active_cells: usize,
+    pub particle_count: usize,
+    /// `(kinetic_energy, mass)` of the most recent frames, oldest first.
+    history: VecDeque<(Real, Real)>,
+}
+
+impl Default for SimDiagnostics {
+    fn default() -> Self {
+        Self {
+            kinetic_energy: 0.0,
+            momentum: zero_vector(),
+            angular_momentum: 0.0,
+            mass: 0.0,
+            active_cells: 0,
+            particle_count: 0,
+            history: VecDeque::with_capacity(Self::WINDOW),
+        }
+    }
+}
+
+impl SimDiagnostics {
+    /// Frames the rolling ranges span.
+    pub const WINDOW: usize = 120;
+
+    /// Totals over the particles and the grid as they are now.
+    pub fn record(&mut self, state: &MpmState) {
+        let mut kinetic_energy = 0.0;
+        let mut momentum = zero_vector();
+        let mut angular_momentum = 0.0;
+        let mut mass = 0.0;
+        let inertia = 1.0 / state.transfer_inv_d();
+        for particle in state.particles() {
+            kinetic_energy += 0.5 * particle.mass * particle.velocity.norm_squared();
+            momentum += particle.velocity * particle.mass;
+            angular_momentum += angular_momentum_of(particle, inertia);
+            mass += particle.mass;
+        }
+        self.kinetic_energy = kinetic_energy;
+        self.momentum = momentum;
+        self.angular_momentum = angular_momentum;
+        self.mass = mass;
+        self.active_cells = state.grid().active_cell_count();
+        self.particle_count = state.particles().len();
+
+        if self.history.len() == Self::WINDOW {
+            self.history.pop_front();
+        }
+        self.history.push_back((kinetic_energy, mass));
+    }
+
+    /// Smallest and largest kinetic energy over the window.
+    pub fn kinetic_energy_range(&self) -> (Real, Real) {
+        range(self.history.iter().map(|&(energy, _)| energy))
+    }
+
+    /// Smallest and largest total mass over the window.
+    pub fn mass_range(&self) -> (Real, Real) {
+        range(self.history.iter().map(|&(_, mass)| mass))
+    }
+}
+
+/// Angular momentum about the origin `particle` hands the grid in P2G.
+///
+/// Scattered with weights `w_i` to nodes at `x_p + d_i`, the particle puts
+/// `sum_i w_i (x_p + d_i) x m (v + C d_i)` on the grid. The weights sum to
+/// one, `sum_i w_i d_i` is zero and `sum_i w_i d_i d_i^T` is `inertia` times
+/// the identity (the APIC `D`), which leaves `x_p x m v` plus
+/// `m D (C_yx - C_xy)`: the affine field's spin, which APIC keeps and PIC
+/// would lose.
+pub fn angular_momentum_of(particle: &Particle, inertia: Real) -> Real {
+    let (position, velocity) = (particle.position, particle.velocity);
+    let gradient = &particle.velocity_gradient;
+    let orbital = position.x * velocity.y - position.y * velocity.x;
+    let spin = inertia * (gradient[(1, 0)] - gradient[(0, 1)]);
+    particle.mass * (orbital + spin)
+}
+
+/// `(min, max)` of `values`, `(0, 0)` when there are none.
+fn range(values: impl Iterator<Item = Real>) -> (Real, Real) {
+    values
+        .fold(None, |range: Option<(Real, Real)>, value| match range {
+            Some((min, max)) => Some((min.min(value), max.max(value))),
+            None => Some((value, value)),
+        })
+        .unwrap_or((0.0, 0.0))
+}
+
+/// Records the frame's totals when `SimDiagnostics` is present.
+pub fn update_sim_diagnostics(state: Res<MpmState>, diagnostics: Option<ResMut<SimDiagnostics>>) {
+    if let Some(mut diagnostics) = diagnostics {
+        diagnostics.record(&state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::{BoundaryHandling, zero_grid};
+    use crate::materials::MaterialType;
+    use crate::math::Matrix;
+    use crate::solver::grid_to_particle;
+    use crate::test_support::{frame_world, solver_schedule};
+
+    #[test]
+    fn a_spinning_patch_in_a_closed_box_keeps_zero_momentum() {
+        let center = Vector::new(64.0, 64.0);
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        state.set_boundary_mode(BoundaryHandling::Stick);
+        let mut speed_sum = 0.0;
+        for j in 0..20 {
+            for i in 0..20 {
+                let offset = Vector::new(i as Real - 9.5, j as Real - 9.5) * 0.5;
+                let velocity = Vector::new(-offset.y, offset.x) * 2.0;
+                speed_sum += velocity.norm();
+                let particle = Particle::new(center + offset, MaterialType::water());
+                state.add_particle(particle.with_velocity(velocity));
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.init_resource::<SimDiagnostics>();
+        let mut schedule = solver_schedule();
+        schedule.add_systems(update_sim_diagnostics.after(grid_to_particle));
+
+        let mut largest: Real = 0.0;
+        for _ in 0..100 {
+            schedule.run(&mut world);
+            largest = largest.max(world.resource::<SimDiagnostics>().momentum.norm());
+        }
+
+        let diagnostics = world.resource::<SimDiagnostics>();
+        assert!(largest < 1e-3 * speed_sum, "momentum reached {largest}");
+        assert_eq!(diagnostics.particle_count, 400);
+        assert_eq!(diagnostics.mass_range(), (400.0, 400.0));
+        let (low, high) = diagnostics.kinetic_energy_range();
+        assert!(low <= diagnostics.kinetic_energy && diagnostics.kinetic_energy <= high);
+        assert!(low > 0.0);
+    }
+
+    #[test]
+    fn a_rigidly_rotating_blob_in_a_closed_box_keeps_its_angular_momentum() {
+        let center = Vector::new(64.0, 64.0);
+        let spin = Matrix::new(0.0, -1.5, 1.5, 0.0);
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        state.set_boundary_mode(BoundaryHandling::Stick);
+        for j in 0..16 {
+            for i in 0..16 {
+                let offset = Vector::new(i as Real - 7.5, j as Real - 7.5) * 0.5;
+                let mut particle = Particle::new(center + offset, MaterialType::water())
+                    .with_velocity(spin * offset);
+                particle.velocity_gradient = spin;
+                particle.affine_momentum_matrix = spin;
+                state.add_particle(particle);
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.init_resource::<SimDiagnostics>();
+        let mut schedule = solver_schedule();
+        schedule.add_systems(update_sim_diagnostics.before(zero_grid));
+
+        schedule.run(&mut world);
+        let initial = world.resource::<SimDiagnostics>().angular_momentum;
+        assert!(initial > 0.0);
+        for _ in 0..120 {
+            schedule.run(&mut world);
+            let angular_momentum = world.resource::<SimDiagnostics>().angular_momentum;
+            let drift = (angular_momentum - initial).abs() / initial;
+            assert!(
+                drift < 1e-2,
+                "angular momentum {angular_momentum} from {initial}"
+            );
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! User-defined accelerations applied to the grid.
+//!
+//! Fields in the `ForceFields` resource are sampled at every node carrying
+//! mass during `grid_update`, alongside gravity and before the domain walls
+//! and colliders, so wind, whirlpools and attractors need no solver changes.
+
+use bevy::prelude::*;
+
+use crate::core::{Grid, node_center};
+use crate::math::{Real, Vector, zero_vector};
+
+/// An acceleration field over the simulation domain.
+pub trait ForceField: Send + Sync + 'static {
+    /// Acceleration at `p` (simulation units) at simulation time `t`.
+    fn accel(&self, p: Vector, t: Real) -> Vector;
+}
+
+/// The same acceleration everywhere, such as a steady wind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniformWindField {
+    pub acceleration: Vector,
+}
+
+impl UniformWindField {
+    pub fn new(acceleration: Vector) -> Self {
+        Self { acceleration }
+    }
+}
+
+impl ForceField for UniformWindField {
+    fn accel(&self, _p: Vector, _t: Real) -> Vector {
+        self.acceleration
+    }
+}
+
+/// Counter-clockwise swirl around `center` (clockwise for a negative
+/// `strength`), growing linearly with the distance out to `radius` and
+/// vanishing beyond it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VortexField {
+    pub center: Vector,
+    pub strength: Real,
+    pub radius: Real,
+}
+
+impl VortexField {
+    pub fn new(center: Vector, strength: Real, radius: Real) -> Self {
+        Self {
+            center,
+            strength,
+            radius,
+        }
+    }
+}
+
+impl ForceField for VortexField {
+    fn accel(&self, p: Vector, _t: Real) -> Vector {
+        let offset = p - self.center;
+        if offset.norm_squared() > self.radius * self.radius {
+            return zero_vector();
+        }
+        Vector::new(-offset.y, offset.x) * self.strength
+    }
+}
+
+/// Constant pull towards `center` within `radius` (a push for a negative
+/// `strength`), like an attractor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RadialField {
+    pub center: Vector,
+    pub strength: Real,
+    pub radius: Real,
+}
+
+impl RadialField {
+    pub fn new(center: Vector, strength: Real, radius: Real) -> Self {
+        Self {
+            center,
+            strength,
+            radius,
+        }
+    }
+}
+
+impl ForceField for RadialField {
+    fn accel(&self, p: Vector, _t: Real) -> Vector {
+        let inward = self.center - p;
+        if inward.norm_squared() > self.radius * self.radius {
+            return zero_vector();
+        }
+        inward
+            .try_normalize(Real::EPSILON)
+            .map_or(zero_vector(), |direction| direction * self.strength)
+    }
+}
+
+/// Force fields `grid_update` applies to the grid.
+#[derive(Resource, Default)]
+pub struct ForceFields {
+    fields: Vec<Box<dyn ForceField>>,
+    /// Simulation time handed to the fields, advanced by every application
+    /// so substeps see it move too.
+    elapsed: Real,
+}
+
+impl ForceFields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: impl ForceField) {
+        self.fields.push(Box::new(field));
+    }
+
+    pub fn with(mut self, field: impl ForceField) -> Self {
+        self.add(field);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn elapsed(&self) -> Real {
+        self.elapsed
+    }
+
+    /// Kicks every node with mass by the summed field accelerations over
+    /// `dt`, then advances the field clock.
+    pub fn apply(&mut self, grid: &mut Grid, dt: Real) {
+        if !self.fields.is_empty() {
+            let cell_width = grid.cell_width();
+            grid.for_each_node_mut(|coord, node| {
+                if node.mass <= 0.0 {
+                    return;
+                }
+                let position = node_center(coord, cell_width);
+                let accel = self.fields.iter().fold(zero_vector(), |sum, field| {
+                    sum + field.accel(position, self.elapsed)
+                });
+                node.velocity += accel * dt;
+            });
+        }
+        self.elapsed += dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::{Grid, MpmState, Particle};
+    use crate::materials::MaterialType;
+    use crate::test_support::run_frames_with;
+
+    /// Accelerates each node by its own position, so the kick shows where
+    /// the field sampled it.
+    struct PositionField;
+
+    impl ForceField for PositionField {
+        fn accel(&self, p: Vector, _t: Real) -> Vector {
+            p
+        }
+    }
+
+    #[test]
+    fn fields_are_sampled_at_node_centres_in_simulation_units() {
+        let mut grid = Grid::with_cell_width(0.5);
+        let coord = IVec2::new(10, 4);
+        grid.get_cell_coord_mut(coord).mass = 1.0;
+
+        ForceFields::new().with(PositionField).apply(&mut grid, 1.0);
+
+        let velocity = grid.get_cell_coord(coord).unwrap().velocity;
+        assert!(
+            (velocity - Vector::new(5.25, 2.25)).norm() < 1e-5,
+            "{velocity}"
+        );
+    }
+
+    #[test]
+    fn a_vortex_sets_a_resting_patch_spinning() {
+        let center = Vector::new(64.0, 64.0);
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        for j in 0..12 {
+            for i in 0..12 {
+                let lattice = Vector::new(i as Real, j as Real) * 0.5;
+                let position = center - Vector::new(2.75, 2.75) + lattice;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        let world = run_frames_with(state, 10, |world, _| {
+            world.insert_resource(ForceFields::new().with(VortexField::new(center, 4.0, 10.0)));
+        });
+
+        let state = world.resource::<MpmState>();
+        let angular_momentum: Real = state
+            .particles()
+            .iter()
+            .map(|particle| {
+                let offset = particle.position - center;
+                particle.mass * (offset.x * particle.velocity.y - offset.y * particle.velocity.x)
+            })
+            .sum();
+        assert!(angular_momentum > 1e-3, "{angular_momentum}");
+        assert!((world.resource::<ForceFields>().elapsed() - 10.0 / 60.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,109 @@
+//! Brittle fracture
+//!
+//! After G2P, particles carrying a `ParticleFracture` accumulate damage while
+//! their deviatoric stress exceeds `crack_threshold`. Once the damage reaches
+//! 1 the particle cracks: its phase drops to 0, which stops its phase-field
+//! (`psi`) scatter and lets the solid models fade out its tension.
+
+use bevy::prelude::*;
+
+use crate::core::MpmState;
+use crate::materials::MaterialModel;
+use crate::materials::utils::physics;
+use crate::math::Real;
+
+/// Fracture system, enabled with `MpmPlugin::with_fracture`.
+pub fn update_fracture(time: Res<Time>, mut state: ResMut<MpmState>) {
+    accumulate_fracture_damage(&mut state, time.delta_secs() as Real);
+}
+
+/// Grows each fracturing particle's damage by
+/// `crack_propagation_factor * (|dev(stress)| / crack_threshold - 1) * dt`
+/// and cracks it once the damage reaches 1.
+pub fn accumulate_fracture_damage(state: &mut MpmState, dt: Real) {
+    let (particles, params) = state.particles_mut_and_params();
+    for particle in particles {
+        let Some(fracture) = particle.fracture else {
+            continue;
+        };
+        if particle.phase <= 0.0 || particle.failed {
+            continue;
+        }
+
+        // The density P2G estimated, as the stress there used; rest density
+        // before the first step
+        let density = if particle.density > 0.0 {
+            particle.density
+        } else {
+            particle.rest_density()
+        };
+        let params = params.for_material(&particle.material_type);
+        let stress = particle
+            .material_type
+            .compute_stress(particle, density, params);
+        let overload = physics::deviatoric_part(&stress).norm() / fracture.crack_threshold - 1.0;
+        if overload <= 0.0 {
+            continue;
+        }
+
+        let damage = fracture.damage + fracture.crack_propagation_factor * overload * dt;
+        if let Some(fracture) = particle.fracture.as_mut() {
+            fracture.damage = damage;
+        }
+        if damage >= 1.0 {
+            particle.phase = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::{Particle, ParticleFracture};
+    use crate::materials::{ElasticParams, MaterialType};
+    use crate::math::{Vector, zero_vector};
+    use crate::solver::grid_to_particle;
+    use crate::test_support::run_frames_with;
+
+    #[test]
+    fn stretched_brittle_bar_splits_in_two() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let material = MaterialType::elastic(ElasticParams::jelly());
+        for j in 0..8 {
+            for i in 0..40 {
+                let position = Vector::new(54.25, 62.25) + Vector::new(i as Real, j as Real) * 0.5;
+                // Both halves are pulled apart
+                let pull = if i < 20 { -5.0 } else { 5.0 };
+                let mut particle = Particle::new(position, material.clone())
+                    .with_mass(0.25)
+                    .with_velocity(Vector::new(pull, 0.0))
+                    .with_fracture(ParticleFracture::new(20.0, 5.0));
+                particle.volume0 = 0.25;
+                state.add_particle(particle);
+            }
+        }
+
+        let world = run_frames_with(state, 60, |_, schedule| {
+            schedule.add_systems(update_fracture.after(grid_to_particle));
+        });
+
+        let state = world.resource::<MpmState>();
+        assert!(
+            state
+                .particles()
+                .iter()
+                .any(|particle| particle.phase == 0.0)
+        );
+        let mut xs: Vec<Real> = state.particles().iter().map(|p| p.position.x).collect();
+        xs.sort_by(Real::total_cmp);
+        let widest_gap = xs
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .fold(0.0, Real::max);
+        assert!(
+            widest_gap > 2.0,
+            "bar held together, widest gap {widest_gap}"
+        );
+    }
+}
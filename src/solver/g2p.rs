@@ -3,55 +3,816 @@
 //! Transfers velocities and velocity gradients from grid nodes back to particles.
 //! Updates particle positions and deformation state.
 
+use std::time::Instant;
+
 use bevy::prelude::*;
+use rayon::prelude::*;
 
-use crate::core::{GRID_RESOLUTION, MpmState, kernel::inv_d};
+use crate::config::{Integrator, StaticParticleHandling, TransferMode};
+use crate::core::{
+    BoundaryConfig, BoundaryHandling, FailureReason, Grid, KernelKind, MpmState, Particle,
+    ParticleTransferCache, add_rebound, project_friction, project_slip, project_stick,
+};
 use crate::materials::MaterialModel;
-use crate::math::{zero_vector, zero_matrix, identity_matrix, outer_product, from_bevy_vec2};
+use crate::math::{
+    Real, Vector, clamp_singular_values, identity_matrix, outer_product, repeat_vector,
+    zero_matrix, zero_vector,
+};
+
+use super::timings::SolverTimings;
+
+/// Verlet half-drift system, run before P2G (see `drift_particles_half_step`).
+pub fn drift_half_step(time: Res<Time>, mut state: ResMut<MpmState>) {
+    drift_particles_half_step(&mut state, time.delta_secs() as Real);
+}
+
+/// G2P system; records its duration when `SolverTimings` is present.
+pub fn grid_to_particle(
+    time: Res<Time>,
+    mut state: ResMut<MpmState>,
+    timings: Option<ResMut<SolverTimings>>,
+) {
+    let start = Instant::now();
+    transfer_grid_to_particles(&mut state, time.delta_secs() as Real);
+    if let Some(mut timings) = timings {
+        timings.g2p = start.elapsed();
+    }
+}
+
+/// First half of the `Integrator::VelocityVerlet` drift: moves every particle
+/// half a step along its current velocity before P2G, so the grid sees
+/// mid-step positions. Does nothing for `Integrator::ExplicitEuler`.
+pub fn drift_particles_half_step(state: &mut MpmState, dt: Real) {
+    if state.solver_params().integrator != Integrator::VelocityVerlet {
+        return;
+    }
+    let boundary = state.boundary_mode();
+    let resolution = state.grid().resolution();
+    let cell_width = state.grid().cell_width();
+    let kernel = state.solver_params().kernel;
+    for particle in state.particles_mut() {
+        if particle.is_static || particle.sleeping {
+            continue;
+        }
+        let velocity = particle.kinematic_velocity.unwrap_or(particle.velocity);
+        advect(
+            particle,
+            velocity,
+            dt * 0.5,
+            &boundary,
+            resolution,
+            cell_width,
+            kernel,
+        );
+    }
+}
 
 /// Native coordinate-based G2P transfer (eliminates linear index conversions)
-pub fn grid_to_particle(time: Res<Time>, mut state: ResMut<MpmState>) {
-    let (grid, particles, transfer_cache) = state.grid_and_particles_mut_cache();
-    let cell_width = grid.cell_width();
-    let inv_d = inv_d(cell_width);
+///
+/// Particles only read the grid, so they are updated in parallel. With a
+/// double-buffered particle set they read their previous velocity from the
+/// back buffer and write the new one to the front, and the two swap after.
+pub fn transfer_grid_to_particles(state: &mut MpmState, dt: Real) {
+    let step = G2pStep::new(state, dt);
+    state.particle_set_mut().sync_velocity_buffers();
+    let (grid, particles, transfer_cache, velocities) = state.grid_and_particles_mut_velocities();
+    let particles = particles.par_iter_mut().zip(transfer_cache.par_iter());
+    match velocities {
+        Some((previous, current)) => particles
+            .zip(previous.par_iter().zip(current.par_iter_mut()))
+            .for_each(|((particle, transfer), (&velocity, written))| {
+                step.update(particle, transfer, velocity, grid);
+                *written = particle.velocity;
+            }),
+        None => particles.for_each(|(particle, transfer)| {
+            let velocity = particle.velocity;
+            step.update(particle, transfer, velocity, grid);
+        }),
+    }
+    state.particle_set_mut().swap_velocity_buffers();
+}
 
-    // Simple single-threaded G2P (ready for parallelization later)
-    for (idx, particle) in particles.iter_mut().enumerate() {
-        let transfer = &transfer_cache[idx];
+/// Single-threaded `transfer_grid_to_particles`, for comparison and profiling.
+pub fn transfer_grid_to_particles_serial(state: &mut MpmState, dt: Real) {
+    let step = G2pStep::new(state, dt);
+    state.particle_set_mut().sync_velocity_buffers();
+    let (grid, particles, transfer_cache, mut velocities) =
+        state.grid_and_particles_mut_velocities();
+    for (index, (particle, transfer)) in particles.iter_mut().zip(transfer_cache).enumerate() {
+        let velocity = velocities
+            .as_ref()
+            .map_or(particle.velocity, |(previous, _)| previous[index]);
+        step.update(particle, transfer, velocity, grid);
+        if let Some((_, current)) = &mut velocities {
+            current[index] = particle.velocity;
+        }
+    }
+    state.particle_set_mut().swap_velocity_buffers();
+}
+
+/// Everything a particle's G2P update needs besides the grid.
+#[derive(Clone, Copy)]
+struct G2pStep {
+    dt: Real,
+    drift_dt: Real,
+    inv_d: Real,
+    flip_ratio: Real,
+    transfer_mode: TransferMode,
+    singular_value_range: Option<(Real, Real)>,
+    sleep_threshold: Real,
+    sleep_steps: u32,
+    wake_threshold: Real,
+    heat: bool,
+    colour_diffusion: Option<Real>,
+    static_boundary: bool,
+    boundary: BoundaryConfig,
+    resolution: usize,
+    cell_width: Real,
+    kernel: KernelKind,
+}
+
+impl G2pStep {
+    fn new(state: &MpmState, dt: Real) -> Self {
+        let params = state.solver_params();
+        Self {
+            dt,
+            // Verlet advects over the second half of the step, Euler over all of it
+            drift_dt: match params.integrator {
+                Integrator::ExplicitEuler => dt,
+                Integrator::VelocityVerlet => dt * 0.5,
+            },
+            inv_d: state.transfer_inv_d(),
+            flip_ratio: params.flip_ratio,
+            transfer_mode: params.transfer_mode,
+            singular_value_range: params.singular_value_range,
+            sleep_threshold: params.sleep_threshold,
+            sleep_steps: params.sleep_steps,
+            wake_threshold: params.wake_threshold,
+            heat: params.transfers_heat(),
+            colour_diffusion: params.colour_diffusion,
+            static_boundary: params.static_particles == StaticParticleHandling::Boundary,
+            boundary: state.boundary_mode(),
+            resolution: state.grid().resolution(),
+            cell_width: state.grid().cell_width(),
+            kernel: params.kernel,
+        }
+    }
+
+    /// `previous_velocity` is the velocity the particle started the step
+    /// with, which FLIP adds the grid's change to.
+    fn update(
+        &self,
+        particle: &mut Particle,
+        transfer: &ParticleTransferCache,
+        previous_velocity: Vector,
+        grid: &Grid,
+    ) {
+        particle.age += self.dt;
+
+        // Obstacles never move; blended into the transfers they hand the
+        // grid a zero velocity
+        if particle.is_static {
+            if !self.static_boundary {
+                particle.velocity = zero_vector();
+            }
+            return;
+        }
+
+        // Scripted particles ignore the grid and keep their set velocity,
+        // which P2G still scatters so they push the material around them
+        if let Some(velocity) = particle.kinematic_velocity {
+            particle.affine_momentum_matrix = zero_matrix();
+            particle.velocity_gradient = zero_matrix();
+            advect(
+                particle,
+                velocity,
+                self.drift_dt,
+                &self.boundary,
+                self.resolution,
+                self.cell_width,
+                self.kernel,
+            );
+            particle.velocity = velocity;
+            return;
+        }
+
+        if particle.sleeping {
+            let stirred = transfer.neighbors().iter().any(|&(coord, _, _)| {
+                grid.get_cell_coord(coord)
+                    .is_some_and(|cell| cell.velocity.norm() > self.wake_threshold)
+            });
+            if !stirred {
+                return;
+            }
+            particle.sleeping = false;
+            particle.idle_steps = 0;
+        }
 
         particle.velocity = zero_vector();
         let mut velocity_gradient = zero_matrix();
+        let mut velocity_change = zero_vector();
+        let mut temperature_change = 0.0;
+        let mut colour = [0.0; 4];
 
-        for &(coord, weight, cell_distance) in &transfer.neighbors {
+        for &(coord, weight, cell_distance) in transfer.neighbors() {
             if let Some(cell) = grid.get_cell_coord(coord) {
-                let weighted_velocity = cell.velocity * weight;  // nalgebra Vector
-                let cell_dist_na = from_bevy_vec2(cell_distance);
-                let outer = outer_product(weighted_velocity, cell_dist_na);
+                let weighted_velocity = cell.velocity * weight; // nalgebra Vector
+                let outer = outer_product(weighted_velocity, cell_distance);
 
                 particle.velocity += weighted_velocity;
-                velocity_gradient += outer * (weight * inv_d);
+                velocity_gradient += outer * (weight * self.inv_d);
+                velocity_change += (cell.velocity - cell.transferred_velocity) * weight;
+                temperature_change += (cell.temperature - cell.transferred_temperature) * weight;
+                for (channel, value) in colour.iter_mut().zip(cell.colour) {
+                    *channel += value * weight;
+                }
+            }
+        }
+        // Only the change diffusion made is taken up, as in FLIP, so the
+        // transfers themselves don't blur temperatures
+        if self.heat {
+            particle.temperature += temperature_change;
+        }
+        if let Some(diffusion) = self.colour_diffusion {
+            for (own, grid) in particle.colour.iter_mut().zip(colour) {
+                *own += (grid - *own) * diffusion;
             }
         }
 
-        particle.affine_momentum_matrix = velocity_gradient;
+        if self.flip_ratio > 0.0 {
+            // FLIP keeps the particle's own velocity and only adds what the
+            // grid changed, so less of the motion is smoothed away
+            let flip_velocity = previous_velocity + velocity_change;
+            particle.velocity =
+                particle.velocity * (1.0 - self.flip_ratio) + flip_velocity * self.flip_ratio;
+        }
+
+        // PIC particles carry no affine field, but the gradient still
+        // deforms them and drives their stress
+        particle.affine_momentum_matrix = match self.transfer_mode {
+            TransferMode::Apic => velocity_gradient,
+            TransferMode::Pic => zero_matrix(),
+        };
         particle.velocity_gradient = velocity_gradient;
 
         // Update deformation gradient: F_new = (I + dt * C) * F_old
-        let dt = time.delta_secs();
-        let deformation_update = identity_matrix() + velocity_gradient * dt;
+        let deformation_update = identity_matrix() + velocity_gradient * self.dt;
         particle.deformation_gradient = deformation_update * particle.deformation_gradient;
 
         let material = particle.material_type.clone();
         material.project_deformation(particle);
+        if let Some((min, max)) = self.singular_value_range
+            && !material.is_fluid()
+        {
+            particle.deformation_gradient =
+                clamp_singular_values(&particle.deformation_gradient, min, max);
+        }
+
+        let velocity = particle.velocity;
+        advect(
+            particle,
+            velocity,
+            self.drift_dt,
+            &self.boundary,
+            self.resolution,
+            self.cell_width,
+            self.kernel,
+        );
+        self.update_sleep(particle);
+    }
+
+    /// Counts the steps a particle spends idle and puts it to sleep, at rest,
+    /// after `sleep_steps` of them.
+    fn update_sleep(&self, particle: &mut Particle) {
+        if self.sleep_threshold <= 0.0 {
+            return;
+        }
+        let idle = particle.velocity.norm() < self.sleep_threshold
+            && particle.velocity_gradient.norm() < self.sleep_threshold;
+        if !idle {
+            particle.idle_steps = 0;
+            return;
+        }
+        particle.idle_steps += 1;
+        if particle.idle_steps >= self.sleep_steps {
+            particle.sleeping = true;
+            particle.velocity = zero_vector();
+            particle.affine_momentum_matrix = zero_matrix();
+            particle.velocity_gradient = zero_matrix();
+        }
+    }
+}
+
+/// Moves a particle by `velocity * dt`. A particle that would end up past a
+/// wall is stopped at it instead, losing its velocity into the wall the way
+/// that edge's `BoundaryHandling` says, less what bounces back off it; past
+/// an outflow edge it is failed straight away, and past an open edge it
+/// leaves the grid and is failed when the bins are rebuilt.
+fn advect(
+    particle: &mut Particle,
+    velocity: Vector,
+    dt: Real,
+    boundary: &BoundaryConfig,
+    resolution: usize,
+    cell_width: Real,
+    kernel: KernelKind,
+) {
+    let mut position = particle.position + velocity * dt;
+    let mut velocity = velocity;
+
+    let periodic = boundary.periodic_axes();
+    let size = resolution as Real * cell_width;
+    if periodic.x {
+        position.x = position.x.rem_euclid(size);
+    }
+    if periodic.y {
+        position.y = position.y.rem_euclid(size);
+    }
+
+    // Walls sit where the kernel stencil just touches the grid edge
+    let margin = kernel.min_position() * cell_width;
+    let min = repeat_vector(margin);
+    let max = repeat_vector(size - cell_width - margin);
+    let walls = [
+        (boundary.left, Vector::new(1.0, 0.0), min),
+        (boundary.right, Vector::new(-1.0, 0.0), max),
+        (boundary.bottom, Vector::new(0.0, 1.0), min),
+        (boundary.top, Vector::new(0.0, -1.0), max),
+    ];
+    for (mode, normal, limit) in walls {
+        let depth = (limit - position).dot(&normal);
+        if depth <= 0.0 {
+            continue;
+        }
+        if mode == BoundaryHandling::Outflow {
+            particle.position = position;
+            particle.velocity = velocity;
+            particle.fail(FailureReason::Outflow);
+            return;
+        }
+        let closing_speed = velocity.dot(&normal);
+        let velocity_into_wall = closing_speed < 0.0;
+        velocity = match mode {
+            BoundaryHandling::None | BoundaryHandling::Periodic | BoundaryHandling::Outflow => {
+                continue;
+            }
+            _ if !velocity_into_wall => velocity,
+            BoundaryHandling::Stick => project_stick(velocity, normal),
+            BoundaryHandling::Slip => project_slip(velocity, normal),
+            BoundaryHandling::Friction(friction) => project_friction(velocity, normal, friction),
+        };
+        if mode != BoundaryHandling::Stick {
+            velocity = add_rebound(velocity, normal, closing_speed, boundary.restitution);
+        }
+        position += normal * depth;
+    }
+
+    particle.position = position;
+    particle.velocity = velocity;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::{GRID_RESOLUTION, ParticleRemap, remove_failed_particles_system, zero_grid};
+    use crate::materials::MaterialType;
+    use crate::solver::{grid_update, particle_to_grid};
+    use crate::test_support::{frame_world, run_frames, solver_schedule, step_serial};
+
+    const CENTER: Vector = Vector::new(64.0, 64.0);
+    const STIFFNESS: Real = 4.0;
+
+    /// Pulls every node towards `CENTER` in proportion to the distance: a
+    /// harmonic oscillator the grid reproduces exactly.
+    fn spring(time: Res<Time>, mut state: ResMut<MpmState>) {
+        let dt = time.delta_secs() as Real;
+        for ((x, y), node) in state.grid_mut().iter_active_cells_mut() {
+            let position = Vector::new(x as Real, y as Real) + Vector::repeat(0.5);
+            node.velocity += (CENTER - position) * (STIFFNESS * dt);
+        }
+    }
+
+    /// Largest relative energy error of one particle orbiting in a spring
+    /// field over 1000 steps.
+    fn orbit_energy_drift(integrator: Integrator) -> Real {
+        let params = SolverParams::default().with_integrator(integrator);
+        let mut state = MpmState::new(params, zero_vector());
+        let start = CENTER + Vector::new(10.0, 0.0);
+        state.add_particle(
+            Particle::new(start, MaterialType::water()).with_velocity(Vector::new(0.0, 5.0)),
+        );
+        let energy = |state: &MpmState| {
+            let particle = &state.particles()[0];
+            let offset = particle.position - CENTER;
+            0.5 * particle.velocity.norm_squared() + 0.5 * STIFFNESS * offset.norm_squared()
+        };
+
+        let initial = energy(&state);
+        let mut world = frame_world(state);
+        let mut schedule = solver_schedule();
+        schedule.add_systems((
+            drift_half_step.after(zero_grid).before(particle_to_grid),
+            spring.after(grid_update).before(grid_to_particle),
+        ));
+
+        let mut drift: Real = 0.0;
+        for _ in 0..1000 {
+            schedule.run(&mut world);
+            let energy = energy(world.resource::<MpmState>());
+            drift = drift.max((energy / initial - 1.0).abs());
+        }
+        drift
+    }
+
+    #[test]
+    fn periodic_boundary_carries_particles_across_the_seam() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        state.set_boundary_mode(BoundaryHandling::Periodic);
+        let velocity = Vector::new(30.0, 0.0);
+        for x in [120.0, 127.75] {
+            let particle = Particle::new(Vector::new(x, 64.0), MaterialType::water());
+            state.add_particle(particle.with_velocity(velocity));
+        }
 
-        let particle_velocity = particle.velocity;
+        let mut world = frame_world(state);
+        let mut schedule = solver_schedule();
 
-        particle.position += particle_velocity * time.delta_secs();
+        for _ in 0..30 {
+            schedule.run(&mut world);
+            // Stencils straddling the seam land on wrapped nodes
+            let state = world.resource::<MpmState>();
+            let size = GRID_RESOLUTION as i32;
+            for ((x, y), _) in state.grid().iter_active_cells() {
+                assert!((0..size).contains(&x) && (0..size).contains(&y));
+            }
+        }
+
+        let state = world.resource::<MpmState>();
+        for particle in state.particles() {
+            assert!(!particle.failed);
+            assert!(
+                particle.position.x < 20.0,
+                "stuck at {}",
+                particle.position.x
+            );
+            assert!((particle.velocity - velocity).norm() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn conveyor_wraps_sideways_over_a_solid_floor() {
+        let conveyor = BoundaryConfig {
+            left: BoundaryHandling::Periodic,
+            right: BoundaryHandling::Periodic,
+            ..BoundaryConfig::uniform(BoundaryHandling::Slip)
+        };
+        let mut state = MpmState::new(SolverParams::default(), crate::config::GRAVITY);
+        state.set_boundary_mode(conveyor);
+        let velocity = Vector::new(30.0, 0.0);
+        let particle = Particle::new(Vector::new(126.5, 1.5), MaterialType::water());
+        state.add_particle(particle.with_velocity(velocity));
+
+        let world = run_frames(state, 30);
+
+        // Across the seam at full speed, still resting on the floor
+        let particle = &world.resource::<MpmState>().particles()[0];
+        assert!(!particle.failed);
+        assert!(
+            particle.position.x < 20.0,
+            "stuck at {}",
+            particle.position.x
+        );
+        assert!((particle.velocity.x - velocity.x).abs() < 1e-2);
+        assert!(particle.position.y >= 1.0);
+    }
+
+    #[test]
+    fn particle_thrown_at_a_wall_comes_to_rest_against_it() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let particle = Particle::new(Vector::new(100.0, 64.0), MaterialType::water());
+        state.add_particle(particle.with_velocity(Vector::new(80.0, 5.0)));
+
+        let world = run_frames(state, 60);
+
+        let particle = &world.resource::<MpmState>().particles()[0];
+        assert!(particle.position.iter().all(|v| v.is_finite()));
+        assert!(particle.velocity.iter().all(|v| v.is_finite()));
+        let wall = GRID_RESOLUTION as Real - 2.0;
+        assert!(particle.position.x > wall - 0.5 && particle.position.x <= wall);
+        assert!(
+            particle.velocity.x.abs() < 0.05,
+            "still moving at {}",
+            particle.velocity.x
+        );
+        // Slip walls keep the sliding part
+        assert!(particle.velocity.y > 4.0);
+    }
+
+    #[test]
+    fn particles_leave_through_an_outflow_edge_but_not_the_opposite_wall() {
+        let river = BoundaryConfig {
+            right: BoundaryHandling::Outflow,
+            ..BoundaryConfig::uniform(BoundaryHandling::Slip)
+        };
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        state.set_boundary_mode(river);
+        // Drops heading right tagged 0, heading left tagged 1
+        for j in 0..4 {
+            let y = 40.0 + j as Real * 10.0;
+            for (tag, x, speed) in [(0, 100.0, 40.0), (1, 28.0, -40.0)] {
+                let mut particle = Particle::new(Vector::new(x, y), MaterialType::water())
+                    .with_velocity(Vector::new(speed, 0.0));
+                particle.user_data = tag;
+                state.add_particle(particle);
+            }
+        }
+
+        let mut world = frame_world(state);
+        world.init_resource::<ParticleRemap>();
+        let mut schedule = solver_schedule();
+        schedule.add_systems(remove_failed_particles_system.after(grid_to_particle));
+        for _ in 0..60 {
+            schedule.run(&mut world);
+        }
+
+        // The right-movers are gone, the left-movers rest against the wall
+        let particles = world.resource::<MpmState>().particles();
+        assert_eq!(particles.len(), 4);
+        for particle in particles {
+            assert_eq!(particle.user_data, 1);
+            assert!(
+                particle.position.x < 3.0,
+                "stuck at {}",
+                particle.position.x
+            );
+        }
+    }
+
+    /// Kinetic energy left a second after a block of water hits the floor.
+    fn energy_after_impact(flip_ratio: Real) -> Real {
+        let params = SolverParams::default().with_flip_ratio(flip_ratio);
+        let mut state = MpmState::new(params, crate::config::GRAVITY);
+        for j in 0..20 {
+            for i in 0..20 {
+                let position = Vector::new(59.25, 30.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        let world = run_frames(state, 110);
+
+        let particles = world.resource::<MpmState>().particles();
+        particles
+            .iter()
+            .map(|particle| 0.5 * particle.mass * particle.velocity.norm_squared())
+            .sum()
+    }
+
+    #[test]
+    fn flip_keeps_more_energy_after_an_impact_than_pic() {
+        let pic = energy_after_impact(0.0);
+        let flip = energy_after_impact(0.95);
+        assert!(flip.is_finite());
+        assert!(flip > pic, "FLIP {flip} vs PIC {pic}");
+    }
+
+    #[test]
+    fn parallel_g2p_matches_the_serial_loop() {
+        let dt = 1.0 / 60.0;
+        let transferred = || {
+            let mut state = MpmState::new(SolverParams::default(), crate::config::GRAVITY);
+            for j in 0..40 {
+                for i in 0..40 {
+                    let lattice = Vector::new(i as Real, j as Real) * 0.5;
+                    let velocity = Vector::new(j as Real * 0.1, -(i as Real) * 0.2);
+                    let position = Vector::new(44.25, 30.25) + lattice;
+                    let particle = Particle::new(position, MaterialType::water());
+                    state.add_particle(particle.with_velocity(velocity));
+                }
+            }
+            crate::solver::transfer_particles_to_grid_serial(&mut state, dt);
+            state.integrate_grid_velocities(dt);
+            state
+        };
+        let (mut parallel, mut serial) = (transferred(), transferred());
+
+        transfer_grid_to_particles(&mut parallel, dt);
+        transfer_grid_to_particles_serial(&mut serial, dt);
+        for (parallel, serial) in parallel.particles().iter().zip(serial.particles()) {
+            assert_eq!(parallel.position, serial.position);
+            assert_eq!(parallel.velocity, serial.velocity);
+            assert_eq!(parallel.deformation_gradient, serial.deformation_gradient);
+        }
+    }
+
+    #[test]
+    fn double_buffered_velocities_match_the_single_buffer_bit_for_bit() {
+        let dt = 1.0 / 60.0;
+        // FLIP reads the back buffer, and a removal halfway through has to
+        // shift it along with the particles
+        let simulate = |double_buffer: bool| {
+            let params = SolverParams::default().with_flip_ratio(0.95);
+            let mut state = MpmState::new(params, crate::config::GRAVITY);
+            state
+                .particle_set_mut()
+                .set_velocity_double_buffer(double_buffer);
+            for j in 0..24 {
+                for i in 0..24 {
+                    let lattice = Vector::new(i as Real, j as Real) * 0.5;
+                    let velocity = Vector::new(6.0 - j as Real * 0.5, i as Real * 0.25);
+                    let position = Vector::new(50.25, 40.25) + lattice;
+                    let particle = Particle::new(position, MaterialType::water());
+                    state.add_particle(particle.with_velocity(velocity));
+                }
+            }
+            for step in 0..40 {
+                if step == 20 {
+                    state.particle_set_mut().remove_many(&[0, 100, 300]);
+                }
+                state.zero_grid();
+                crate::solver::transfer_particles_to_grid_serial(&mut state, dt);
+                state.cleanup_grid();
+                state.integrate_grid_velocities(dt);
+                transfer_grid_to_particles(&mut state, dt);
+            }
+            state
+        };
+
+        let (single, double) = (simulate(false), simulate(true));
+        assert_eq!(double.particle_count(), 573);
+        let previous = double.particle_set().previous_velocities().unwrap();
+        let velocities: Vec<Vector> = double.particles().iter().map(|p| p.velocity).collect();
+        assert_eq!(previous, velocities.as_slice());
+        assert!(single.particle_set().previous_velocities().is_none());
+        for (single, double) in single.particles().iter().zip(double.particles()) {
+            assert_eq!(single.position, double.position);
+            assert_eq!(single.velocity, double.velocity);
+            assert_eq!(single.affine_momentum_matrix, double.affine_momentum_matrix);
+            assert_eq!(single.deformation_gradient, double.deformation_gradient);
+        }
+    }
+
+    #[test]
+    fn a_kinematic_row_drags_the_fluid_above_it_along() {
+        let dt = 1.0 / 60.0;
+        let push = Vector::new(10.0, 0.0);
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        for j in 0..6 {
+            for i in 0..40 {
+                let lattice = Vector::new(i as Real, j as Real) * 0.5;
+                let mut particle =
+                    Particle::new(Vector::new(40.25, 60.25) + lattice, MaterialType::water());
+                if j < 2 {
+                    particle.kinematic_velocity = Some(push);
+                }
+                state.add_particle(particle);
+            }
+        }
+
+        for _ in 0..20 {
+            step_serial(&mut state, dt);
+        }
+
+        let (scripted, free): (Vec<&Particle>, Vec<&Particle>) = state
+            .particles()
+            .iter()
+            .partition(|particle| particle.kinematic_velocity.is_some());
+        for particle in &scripted {
+            assert_eq!(particle.velocity, push);
+        }
+        let first = scripted[0].position.x;
+        assert!((first - (40.25 + 20.0 * dt * 10.0)).abs() < 1e-3, "{first}");
+        let drift = free
+            .iter()
+            .map(|particle| particle.velocity.x)
+            .sum::<Real>()
+            / 160.0;
+        assert!(drift > 0.5, "free fluid only reached {drift}");
+    }
+
+    #[test]
+    fn a_settled_pool_sleeps_until_it_is_stirred() {
+        let dt = 1.0 / 60.0;
+        let params = SolverParams::default().with_sleeping(3.0, 20, 6.0);
+        let mut state = MpmState::new(params, crate::config::GRAVITY);
+        for j in 0..16 {
+            for i in 0..64 {
+                let lattice = Vector::new(i as Real, j as Real) * 0.5;
+                let position = Vector::new(48.25, 2.25) + lattice;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+
+        // Dropped onto the floor, the pool sloshes and then settles
+        for _ in 0..360 {
+            step_serial(&mut state, dt);
+        }
+        let particles = state.particles();
+        assert!(particles.iter().all(|particle| !particle.failed));
+        let sleepers: Vec<(usize, Vector)> = particles
+            .iter()
+            .enumerate()
+            .filter(|(_, particle)| particle.sleeping)
+            .map(|(index, particle)| (index, particle.position))
+            .collect();
+        assert!(
+            sleepers.len() * 2 > particles.len(),
+            "{} of {} asleep",
+            sleepers.len(),
+            particles.len()
+        );
+
+        // Sleepers hold still while the grid around them stays quiet
+        step_serial(&mut state, dt);
+        for &(index, position) in &sleepers {
+            let particle = &state.particles()[index];
+            if particle.sleeping {
+                assert_eq!(particle.position, position);
+            }
+        }
+
+        // A fast particle scatters enough momentum to wake its stencil
+        let (poked, at) = sleepers[sleepers.len() / 2];
+        state.particles_mut()[poked].velocity = Vector::new(40.0, 0.0);
+        step_serial(&mut state, dt);
+        let near = |p: &&Particle| (p.position - at).norm() < 1.0;
+        assert!(
+            state
+                .particles()
+                .iter()
+                .filter(near)
+                .any(|particle| !particle.sleeping)
+        );
+    }
+
+    #[test]
+    fn velocity_verlet_holds_an_orbit_energy_closer_than_euler() {
+        let euler = orbit_energy_drift(Integrator::ExplicitEuler);
+        let verlet = orbit_energy_drift(Integrator::VelocityVerlet);
+        assert!(verlet < 0.002, "Verlet drifted {verlet}");
+        assert!(verlet * 10.0 < euler, "Verlet {verlet} vs Euler {euler}");
+    }
+
+    #[test]
+    fn a_rigid_motion_round_trips_exactly_with_half_width_cells() {
+        // APIC carries any affine velocity field through the transfers, so a
+        // translating, spinning patch must come back with the same velocity
+        // and spin, and no strain rate, whatever the cell width
+        let spin = crate::math::Matrix::new(0.0, -0.8, 0.8, 0.0);
+        let drift = Vector::new(1.5, -0.5);
+        let center = Vector::new(20.0, 20.0);
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        *state.grid_mut() = Grid::with_cell_width(0.5);
+        for j in 0..12 {
+            for i in 0..12 {
+                let position = center + Vector::new(i as Real - 5.5, j as Real - 5.5) * 0.25;
+                let velocity = drift + spin * (position - center);
+                let mut particle =
+                    Particle::new(position, MaterialType::water()).with_velocity(velocity);
+                particle.velocity_gradient = spin;
+                particle.affine_momentum_matrix = spin;
+                state.add_particle(particle);
+            }
+        }
+
+        // A zero step leaves just the transfers: no stress, no advection
+        crate::solver::transfer_particles_to_grid_serial(&mut state, 0.0);
+        transfer_grid_to_particles_serial(&mut state, 0.0);
+        for particle in state.particles() {
+            let velocity = drift + spin * (particle.position - center);
+            assert!(
+                (particle.velocity - velocity).norm() < 1e-4,
+                "{}",
+                particle.velocity
+            );
+            let gradient = particle.velocity_gradient;
+            assert!((gradient - spin).norm() < 1e-4, "{gradient}");
+            assert_eq!(particle.deformation_gradient, identity_matrix());
+        }
+    }
+
+    #[test]
+    fn pic_keeps_the_velocity_gradient_but_no_affine_field() {
+        let spin = crate::math::Matrix::new(0.0, -0.8, 0.8, 0.0);
+        let center = Vector::new(20.0, 20.0);
+        let params = SolverParams::default().with_transfer_mode(TransferMode::Pic);
+        let mut state = MpmState::new(params, zero_vector());
+        for j in 0..12 {
+            for i in 0..12 {
+                let position = center + Vector::new(i as Real - 5.5, j as Real - 5.5) * 0.5;
+                let velocity = spin * (position - center);
+                let particle = Particle::new(position, MaterialType::water());
+                state.add_particle(particle.with_velocity(velocity));
+            }
+        }
 
-        // Prevent particles from going out of bounds
-        let min = 1.0;
-        let max = GRID_RESOLUTION as f32 - 2.0;
-        particle.position.x = particle.position.x.clamp(min, max);
-        particle.position.y = particle.position.y.clamp(min, max);
+        crate::solver::transfer_particles_to_grid_serial(&mut state, 0.0);
+        transfer_grid_to_particles_serial(&mut state, 0.0);
+        let particle = &state.particles()[6 * 12 + 6];
+        assert_eq!(particle.affine_momentum_matrix, zero_matrix());
+        // Away from the edges the grid still resolves the rotation
+        let gradient = particle.velocity_gradient;
+        assert!((gradient - spin).norm() < 0.2, "{gradient}");
     }
 }
@@ -1,9 +1,83 @@
+use std::time::Instant;
+
 use bevy::prelude::*;
 
 use crate::core::MpmState;
+use crate::geometry::Colliders;
+use crate::math::Real;
+
+use super::force_field::ForceFields;
+use super::timings::SolverTimings;
 
-/// Grid update stage (divides momentum by mass, applies gravity, clamps boundaries).
-pub fn grid_update(time: Res<Time>, mut state: ResMut<MpmState>) {
-    let dt = time.delta_secs();
+/// Grid update stage (eases gravity towards any target, divides momentum by
+/// mass, applies gravity and any `ForceFields`, clamps boundaries and keeps
+/// material out of any `Colliders`).
+pub fn grid_update(
+    time: Res<Time>,
+    mut state: ResMut<MpmState>,
+    force_fields: Option<ResMut<ForceFields>>,
+    colliders: Option<ResMut<Colliders>>,
+    timings: Option<ResMut<SolverTimings>>,
+) {
+    let start = Instant::now();
+    let dt = time.delta_secs() as Real;
+    state.ease_gravity(dt);
+    if let Some(mut force_fields) = force_fields {
+        force_fields.apply(state.grid_mut(), dt);
+    }
     state.integrate_grid_velocities(dt);
+    if let Some(mut colliders) = colliders {
+        colliders.advance(dt);
+        colliders.project_grid(state.grid_mut());
+    }
+    if let Some(mut timings) = timings {
+        timings.grid_update = start.elapsed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::math::consts::FRAC_PI_2;
+    use crate::test_support::frame_world;
+
+    #[test]
+    fn gravity_eases_onto_its_target_without_overshooting() {
+        let params = SolverParams::default().with_gravity_slew_rate(120.0);
+        let mut state = MpmState::new(params, GRAVITY);
+        // A quarter turn, from straight down to pointing along +x
+        state.set_gravity_from_angle(0.0, 80.0);
+        let target = state.gravity_target().unwrap();
+
+        let mut world = frame_world(state);
+        let mut schedule = Schedule::default();
+        schedule.add_systems(grid_update);
+
+        let mut distance = (target - GRAVITY).norm();
+        let mut steps = 0;
+        while world.resource::<MpmState>().gravity_target().is_some() {
+            schedule.run(&mut world);
+            let gravity = world.resource::<MpmState>().gravity();
+            let remaining = (target - gravity).norm();
+            assert!(remaining < distance, "stalled at {gravity}");
+            assert!(
+                gravity.x <= target.x && gravity.y <= 0.0,
+                "overshot to {gravity}"
+            );
+            distance = remaining;
+            steps += 1;
+            assert!(steps <= 60, "still {distance} away");
+        }
+        // 80 * sqrt(2) at 2 per step
+        assert_eq!(steps, 57);
+        assert_eq!(world.resource::<MpmState>().gravity(), target);
+
+        // Angles are measured from +x, so a quarter turn back is straight down
+        let mut state = world.resource_mut::<MpmState>();
+        state.solver_params_mut().gravity_slew_rate = 0.0;
+        state.set_gravity_from_angle(-FRAC_PI_2, 80.0);
+        schedule.run(&mut world);
+        assert!((world.resource::<MpmState>().gravity() - GRAVITY).norm() < 1e-4);
+    }
 }
@@ -1,7 +1,25 @@
+pub mod control;
+pub mod diagnostics;
+pub mod force_field;
+pub mod fracture;
 pub mod g2p;
 pub mod grid_update;
 pub mod p2g;
+pub mod pbmpm;
+pub mod phase_transition;
+pub mod resampling;
+pub mod substep;
+pub mod timings;
 
+pub use control::*;
+pub use diagnostics::*;
+pub use force_field::*;
+pub use fracture::*;
 pub use g2p::*;
 pub use grid_update::*;
 pub use p2g::*;
+pub use pbmpm::*;
+pub use phase_transition::*;
+pub use resampling::*;
+pub use substep::*;
+pub use timings::*;
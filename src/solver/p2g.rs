@@ -3,100 +3,612 @@
 //! Transfers mass, momentum, and forces from particles to grid nodes.
 //! Includes stress calculation and MLS affine momentum transfer.
 
+use std::collections::HashMap;
+use std::time::Instant;
+
 use bevy::prelude::*;
+use rayon::prelude::*;
 
-use crate::core::{MpmState, kernel::inv_d};
+use crate::config::{SolverParams, StaticParticleHandling, TransferMode};
+use crate::core::{
+    Grid, GridNode, MAX_NEIGHBOR_COUNT, MpmState, Particle, ParticleTransferCache, cell_colour,
+};
+use crate::geometry::unpack_to_ivec;
 use crate::materials::MaterialModel;
 use crate::materials::utils;
-use crate::math::from_bevy_vec2;
+use crate::math::{Matrix, Real, Vector};
+
+use super::timings::SolverTimings;
+
+/// P2G system; records its duration when `SolverTimings` is present.
+pub fn particle_to_grid(
+    time: Res<Time>,
+    mut state: ResMut<MpmState>,
+    timings: Option<ResMut<SolverTimings>>,
+) {
+    let start = Instant::now();
+    transfer_particles_to_grid(&mut state, time.delta_secs() as Real);
+    if let Some(mut timings) = timings {
+        timings.p2g = start.elapsed();
+    }
+}
+
+/// Relative gap between particle and grid mass `check_mass_conservation`
+/// lets pass.
+pub const MASS_TOLERANCE: Real = 1.0e-3;
+
+/// Debug check run after P2G: warns when the grid holds less (or more) mass
+/// than the particles, usually particles whose stencil left the grid and
+/// were failed without scattering anything.
+pub fn check_mass_conservation(state: Res<MpmState>) {
+    let particle_mass = state.total_particle_mass();
+    let grid_mass = state.grid().total_cell_mass();
+    if (particle_mass - grid_mass).abs() > MASS_TOLERANCE * particle_mass {
+        warn!("P2G lost mass: particles carry {particle_mass}, the grid received {grid_mass}");
+    }
+}
+
+/// Side, in cells, of the square tiles ("bukkits") the momentum scatter is
+/// split into for parallel processing.
+const BUKKIT_SIZE: i32 = 4;
+
+/// Buffers P2G keeps on `MpmState` from one substep to the next, so a steady
+/// scene transfers without allocating.
+#[derive(Default)]
+pub struct P2gScratch {
+    contributions: Vec<Option<MomentumContribution>>,
+    nodes: Vec<NodePtr>,
+    /// Particle indices by bukkit, one map per colour.
+    bukkits: [HashMap<IVec2, Vec<usize>>; 4],
+}
 
 /// Two-pass P2G: accumulate mass/momentum, then apply stress forces
 /// Identical behavior to the previous split functions, just consolidated
-pub fn particle_to_grid(time: Res<Time>, mut state: ResMut<MpmState>) {
+///
+/// The momentum pass runs in parallel: see `scatter_momentum_coloured`.
+pub fn transfer_particles_to_grid(state: &mut MpmState, dt: Real) {
+    transfer(state, dt, true);
+}
+
+/// Single-threaded `transfer_particles_to_grid`, for comparison and profiling.
+pub fn transfer_particles_to_grid_serial(state: &mut MpmState, dt: Real) {
+    transfer(state, dt, false);
+}
+
+fn transfer(state: &mut MpmState, dt: Real, parallel: bool) {
     state.rebuild_particle_bins();
-    let solver_params = state.solver_params().clone();
-    let dt = time.delta_secs();
+    let mut scratch = std::mem::take(state.p2g_scratch_mut());
+    if parallel {
+        colour_bukkits(state, &mut scratch.bukkits);
+    }
+
+    let inv_d = state.transfer_inv_d();
 
-    let (grid, particles, cache) = state.grid_mut_and_particles_cache();
-    let cell_width = grid.cell_width();
-    let inv_d = inv_d(cell_width);
+    let (grid, particles, cache, params) = state.grid_mut_particles_cache_and_params();
+    let solver_params = params.solver;
+    let static_boundary = solver_params.static_particles == StaticParticleHandling::Boundary;
+    let cohesion = solver_params.cohesion_strength > 0.0;
+    let heat = solver_params.transfers_heat();
+    let colour = solver_params.colour_diffusion.is_some();
 
     // Pass 1: accumulate mass
     for (idx, particle) in particles.iter().enumerate() {
         let transfer = &cache[idx];
-        for &(coord, weight, _) in &transfer.neighbors {
+
+        // Static obstacles only mark the nodes they cover
+        if static_boundary && particle.is_static {
+            for &(coord, weight, cell_distance) in transfer.neighbors() {
+                let cell = grid.get_cell_coord_mut(coord);
+                let mass_delta = weight * particle.mass;
+                cell.static_mass += mass_delta;
+                cell.static_normal += cell_distance * mass_delta;
+                cell.static_collision_mask |= particle.collision_mask;
+            }
+            continue;
+        }
+
+        let rest_volume = particle.mass * utils::inv_exact(particle.material_rest_density());
+        for &(coord, weight, _) in transfer.neighbors() {
             let cell = grid.get_cell_coord_mut(coord);
             let mass_delta = weight * particle.mass;
             cell.mass += mass_delta;
             cell.fluids.mass += mass_delta;
+            cell.rest_volume += weight * rest_volume;
+            cell.collision_mask |= particle.collision_mask;
+            if heat {
+                cell.temperature += mass_delta * particle.temperature;
+            }
+            if colour {
+                for (channel, value) in cell.colour.iter_mut().zip(particle.colour) {
+                    *channel += mass_delta * value;
+                }
+            }
+            if cohesion && particle.cohesion_energy > 0.0 {
+                cell.cohesion += mass_delta * particle.cohesion_energy;
+                cell.cohesion_mass = cell.cohesion_mass.min(particle.cohesion_mass);
+            }
         }
     }
 
-    // Pass 2: scatter momentum with stress contribution
-    // OPTIMIZATION: Batch neighbor cell lookups to avoid double HashMap access
-    for (idx, particle) in particles.iter().enumerate() {
-        let transfer = &cache[idx];
+    // Pass 2: scatter momentum with stress contribution. Working out a
+    // particle's contribution only reads the grid, so it runs in parallel.
+    let contribution_of = |(particle, transfer): (&Particle, &ParticleTransferCache)| {
+        if static_boundary && particle.is_static {
+            return None;
+        }
+        Some(momentum_contribution(
+            particle,
+            transfer,
+            grid,
+            solver_params,
+            params.for_material(&particle.material_type),
+            inv_d,
+            dt,
+        ))
+    };
+    let contributions = &mut scratch.contributions;
+    if parallel {
+        particles
+            .par_iter()
+            .zip(cache.par_iter())
+            .map(contribution_of)
+            .collect_into_vec(contributions);
+    } else {
+        contributions.clear();
+        contributions.extend(particles.iter().zip(cache).map(contribution_of));
+    }
 
-        // Fetch all 9 neighbor cells ONCE and cache them
-        // This avoids 18 HashMap lookups (9 for density + 9 for momentum scatter)
-        let mut neighbor_cells: [Option<(f32, Vec2)>; 9] = [None; 9];
-        let mut density = 0.0;
+    scratch.nodes.clear();
+    scratch
+        .nodes
+        .extend(grid.node_slots_mut().map(|node| NodePtr(node)));
+    if parallel {
+        scatter_momentum_coloured(&scratch.nodes, &scratch.contributions, &scratch.bukkits);
+    } else {
+        for contribution in scratch.contributions.iter().flatten() {
+            // SAFETY: the pointers come from distinct live nodes of the grid,
+            // which nothing else touches until the scatter is done
+            contribution.scatter(|index| unsafe { &mut *scratch.nodes[index].0 });
+        }
+    }
+    scratch.nodes.clear();
 
-        for (i, &(coord, weight, cell_distance)) in transfer.neighbors.iter().enumerate() {
-            if let Some(cell) = grid.get_cell_coord(coord) {
-                density += cell.mass * weight;
-                neighbor_cells[i] = Some((weight, cell_distance));
+    // Pass 3: Convert momentum to velocity immediately after accumulation
+    // This must happen in P2G for correct force computation timing
+    for (_, cell) in grid.iter_active_cells_mut() {
+        if cell.mass > 0.0 {
+            let inv_mass = utils::inv_exact(cell.mass);
+            cell.velocity = cell.momentum * inv_mass;
+            cell.transferred_velocity = cell.velocity;
+            cell.temperature *= inv_mass;
+            cell.transferred_temperature = cell.temperature;
+            for channel in &mut cell.colour {
+                *channel *= inv_mass;
             }
         }
+    }
+
+    // Keep each particle's density estimate for queries and constraints
+    for (particle, contribution) in state.particles_mut().iter_mut().zip(&scratch.contributions) {
+        if let Some(contribution) = contribution {
+            particle.density = contribution.density;
+        }
+    }
+    *state.p2g_scratch_mut() = scratch;
+}
 
-        // Calculate stress based on material type
-        let stress = particle.material_type.compute_stress(particle, density, &solver_params);
+/// What one particle adds to the momentum of its stencil nodes.
+struct MomentumContribution {
+    /// Index of each stencil node in the grid's node order, with its weight
+    /// and offset from the particle.
+    neighbors: [Option<(usize, Real, Vector)>; MAX_NEIGHBOR_COUNT],
+    affine: Matrix,
+    momentum: Vector,
+    psi_mass: Real,
+    psi_momentum: Real,
+    /// The particle's density estimate, kept as `Particle::density`.
+    density: Real,
+}
+
+impl MomentumContribution {
+    /// Adds this contribution to the nodes `node_at` hands out by index.
+    fn scatter<'a>(&self, mut node_at: impl FnMut(usize) -> &'a mut GridNode) {
+        for &(index, weight, cell_distance) in self.neighbors.iter().flatten() {
+            let cell = node_at(index);
+            let momentum_delta = weight * (self.affine * cell_distance + self.momentum);
+            cell.momentum += momentum_delta;
+            cell.fluids.momentum += momentum_delta;
 
-        let psi_mass = if particle.phase > 0.0
-            && particle.crack_propagation_factor != 0.0
-            && !particle.failed
-        {
+            if self.psi_mass > 0.0 {
+                let psi_mass_delta = weight * self.psi_mass;
+                let psi_momentum_delta = weight * self.psi_momentum;
+                cell.psi_mass += psi_mass_delta;
+                cell.psi_momentum += psi_momentum_delta;
+                cell.fluids.psi_mass += psi_mass_delta;
+                cell.fluids.psi_momentum += psi_momentum_delta;
+            }
+        }
+    }
+}
+
+fn momentum_contribution(
+    particle: &Particle,
+    transfer: &ParticleTransferCache,
+    grid: &Grid,
+    solver_params: &SolverParams,
+    stress_params: &SolverParams,
+    inv_d: Real,
+    dt: Real,
+) -> MomentumContribution {
+    // Fetch every stencil cell ONCE and cache them
+    // This halves the HashMap lookups (one pass for density, one for momentum scatter)
+    let mut neighbors = [None; MAX_NEIGHBOR_COUNT];
+    let mut density = 0.0;
+    // Density as seen by this particle's own material: the filled rest
+    // volume times its rest density. For one fluid this is exactly the
+    // node mass; where fluids of different rest densities share a node
+    // every particle sees the same compression, so the blended pressure
+    // stays balanced and the denser fluid sinks under its extra weight.
+    let rest_density = particle.material_rest_density();
+
+    for (i, &(coord, weight, cell_distance)) in transfer.neighbors().iter().enumerate() {
+        if let Some((index, cell)) = grid.get_cell_coord_full(coord) {
+            // Obstacles on other collision layers don't push back
+            let static_mass = if particle.collision_mask & cell.static_collision_mask != 0 {
+                cell.static_mass
+            } else {
+                0.0
+            };
+            density += (rest_density * cell.rest_volume + static_mass) * weight;
+            neighbors[i] = Some((index, weight, cell_distance));
+        }
+    }
+
+    // Calculate stress based on material type, with any per-material params
+    let stress = particle
+        .material_type
+        .compute_stress(particle, density, stress_params);
+
+    let psi_mass =
+        if particle.phase > 0.0 && particle.crack_propagation_factor != 0.0 && !particle.failed {
             particle.mass
         } else {
             0.0
         };
-        let psi_momentum = psi_mass * particle.psi_pos;
-
-        // Affine term (APIC) incorporating stress (Jiang et al. 2015)
-        // CRITICAL: Use volume0 (rest volume) not current volume
-        let affine = particle.mass * particle.velocity_gradient
-            - (particle.volume0 * inv_d * dt) * stress;
-        let momentum = particle.mass * particle.velocity;
-
-        // Now scatter momentum using the SAME neighbor iteration
-        // We only do ONE HashMap lookup per neighbor instead of two
-        for (i, &(coord, _weight, _cell_distance)) in transfer.neighbors.iter().enumerate() {
-            if let Some((weight, cell_distance)) = neighbor_cells[i] {
-                let cell = grid.get_cell_coord_mut(coord);
-                let cell_dist_na = from_bevy_vec2(cell_distance);
-                let contribution_na = affine * cell_dist_na + momentum;  // momentum is already nalgebra Vector
-                let momentum_delta = weight * contribution_na;  // keep as nalgebra
-                cell.momentum += momentum_delta;
-                cell.fluids.momentum += momentum_delta;
-
-                if psi_mass > 0.0 {
-                    let psi_mass_delta = weight * psi_mass;
-                    let psi_momentum_delta = weight * psi_momentum;
-                    cell.psi_mass += psi_mass_delta;
-                    cell.psi_momentum += psi_momentum_delta;
-                    cell.fluids.psi_mass += psi_mass_delta;
-                    cell.fluids.psi_momentum += psi_momentum_delta;
-                }
+    let psi_momentum = psi_mass * particle.psi_pos;
+
+    // Affine term (APIC) incorporating stress (Jiang et al. 2015)
+    // CRITICAL: Use volume0 (rest volume) not current volume
+    // PIC keeps only the stress part, scattering plain m*v momentum
+    let stress_term = (particle.volume0 * inv_d * dt) * stress;
+    let affine = match solver_params.transfer_mode {
+        TransferMode::Apic => particle.mass * particle.velocity_gradient - stress_term,
+        TransferMode::Pic => -stress_term,
+    };
+
+    MomentumContribution {
+        neighbors,
+        affine,
+        momentum: particle.mass * particle.kinematic_velocity.unwrap_or(particle.velocity),
+        psi_mass,
+        psi_momentum,
+        density,
+    }
+}
+
+/// Groups particle indices by bukkit into `bukkits`, one map per colour.
+///
+/// Bukkits of one colour sit a whole bukkit apart, so the kernel stencils of
+/// their particles never reach a common node and can be scattered
+/// concurrently. `MpmConfig::validate` keeps the resolution a multiple of
+/// twice `BUKKIT_SIZE`, so the colouring also holds across a periodic seam.
+fn colour_bukkits(state: &MpmState, bukkits: &mut [HashMap<IVec2, Vec<usize>>; 4]) {
+    // Bukkits occupied last substep keep their emptied lists, the rest go
+    for colour in bukkits.iter_mut() {
+        colour.retain(|_, indices| {
+            let occupied = !indices.is_empty();
+            indices.clear();
+            occupied
+        });
+    }
+    let particles = state.particles();
+    // Walk the regions rather than the bare order: their ranges are already
+    // sorted by cell, but failed particles still sit inside them, so each
+    // particle is binned by its own cell
+    for (_, range) in state.particle_regions() {
+        for &idx in &state.particle_order()[range.clone()] {
+            let cell = particles[idx].grid_index;
+            if cell == u64::MAX {
+                continue;
             }
+            let bukkit = unpack_to_ivec(cell).div_euclid(IVec2::splat(BUKKIT_SIZE));
+            bukkits[cell_colour(bukkit) as usize]
+                .entry(bukkit)
+                .or_default()
+                .push(idx);
         }
     }
+}
 
-    // Pass 3: Convert momentum to velocity immediately after accumulation
-    // This must happen in P2G for correct force computation timing
-    for (_, cell) in grid.iter_active_cells_mut() {
-        if cell.mass > 0.0 {
-            let inv_mass = utils::inv_exact(cell.mass);
-            cell.velocity = cell.momentum * inv_mass;
+/// Raw node handle shared between the threads of one colour.
+#[derive(Clone, Copy)]
+struct NodePtr(*mut GridNode);
+
+// SAFETY: only dereferenced while P2G scatters, by one thread or, in
+// `scatter_momentum_coloured`, by threads that never share a node.
+unsafe impl Send for NodePtr {}
+unsafe impl Sync for NodePtr {}
+
+/// Scatters the contributions colour by colour, the bukkits of each colour
+/// in parallel.
+fn scatter_momentum_coloured(
+    nodes: &[NodePtr],
+    contributions: &[Option<MomentumContribution>],
+    bukkits: &[HashMap<IVec2, Vec<usize>>; 4],
+) {
+    for colour in bukkits {
+        colour.par_iter().for_each(|(_, indices)| {
+            for contribution in indices
+                .iter()
+                .filter_map(|&idx| contributions[idx].as_ref())
+            {
+                // SAFETY: every pointer is to a distinct live node lent to this
+                // transfer, and bukkits of one colour touch disjoint nodes
+                contribution.scatter(|index| unsafe { &mut *nodes[index].0 });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::core::Particle;
+    use crate::materials::MaterialType;
+    use crate::math::{Real, Vector, zero_vector};
+    use crate::test_support::{frame_world, run_frames, solver_schedule};
+
+    const WALL_LAYER: u32 = 0b01;
+
+    /// Throws a block of water on `water_layers` at a one-cell-thick column
+    /// of static particles at x = 64 and returns the furthest any water
+    /// particle got.
+    fn furthest_past_static_wall(handling: StaticParticleHandling, water_layers: u32) -> Real {
+        let params = SolverParams::default().with_static_particles(handling);
+        let mut state = MpmState::new(params, zero_vector());
+        let lattice = |i: usize, j: usize| Vector::new(i as Real, j as Real) * 0.5;
+        for j in 0..160 {
+            for i in 0..2 {
+                let position = Vector::new(64.25, 24.25) + lattice(i, j);
+                let mut wall = Particle::new(position, MaterialType::water())
+                    .with_mass(0.25)
+                    .with_collision_mask(WALL_LAYER);
+                wall.is_static = true;
+                state.add_particle(wall);
+            }
+        }
+        for j in 0..40 {
+            for i in 0..20 {
+                let position = Vector::new(40.25, 54.25) + lattice(i, j);
+                let water = Particle::new(position, MaterialType::water())
+                    .with_mass(0.25)
+                    .with_velocity(Vector::new(20.0, 0.0))
+                    .with_collision_mask(water_layers);
+                state.add_particle(water);
+            }
+        }
+
+        let mut world = frame_world(state);
+        let mut schedule = solver_schedule();
+
+        let mut furthest: Real = 0.0;
+        for _ in 0..120 {
+            schedule.run(&mut world);
+            let state = world.resource::<MpmState>();
+            let water = state
+                .particles()
+                .iter()
+                .filter(|particle| !particle.is_static);
+            furthest = water.fold(furthest, |furthest, particle| {
+                furthest.max(particle.position.x)
+            });
+        }
+        furthest
+    }
+
+    #[test]
+    fn fluid_cannot_cross_a_one_cell_wall_of_static_particles() {
+        assert!(furthest_past_static_wall(StaticParticleHandling::Boundary, u32::MAX) < 64.0);
+        // Blending lets the water push straight through the same wall
+        assert!(furthest_past_static_wall(StaticParticleHandling::Blend, u32::MAX) > 70.0);
+    }
+
+    #[test]
+    fn static_wall_only_blocks_water_on_a_shared_collision_layer() {
+        let boundary = StaticParticleHandling::Boundary;
+        assert!(furthest_past_static_wall(boundary, WALL_LAYER) < 64.0);
+        assert!(furthest_past_static_wall(boundary, 0b10) > 70.0);
+    }
+
+    /// Angular momentum about the centre left in a spinning 10x10 cell patch
+    /// of water after half a second, as a share of what it started with.
+    fn spin_left_after_half_a_second(transfer_mode: TransferMode) -> Real {
+        let params = SolverParams::default().with_transfer_mode(transfer_mode);
+        let mut state = MpmState::new(params, zero_vector());
+        let center = Vector::new(64.0, 64.0);
+        for j in 0..20 {
+            for i in 0..20 {
+                let position = Vector::new(59.25, 59.25) + Vector::new(i as Real, j as Real) * 0.5;
+                let offset = position - center;
+                let velocity = Vector::new(-offset.y, offset.x) * 2.0;
+                let particle = Particle::new(position, MaterialType::water());
+                state.add_particle(particle.with_velocity(velocity));
+            }
+        }
+        let spin = |state: &MpmState| -> Real {
+            state
+                .particles()
+                .iter()
+                .map(|particle| {
+                    let offset = particle.position - center;
+                    particle.mass * offset.perp(&particle.velocity)
+                })
+                .sum()
+        };
+        let initial = spin(&state);
+
+        let world = run_frames(state, 30);
+        spin(world.resource::<MpmState>()) / initial
+    }
+
+    #[test]
+    fn pic_damps_a_spinning_patch_faster_than_apic() {
+        let apic = spin_left_after_half_a_second(TransferMode::Apic);
+        let pic = spin_left_after_half_a_second(TransferMode::Pic);
+        assert!(pic.is_finite());
+        assert!(pic < apic - 0.05, "PIC kept {pic}, APIC kept {apic}");
+    }
+
+    #[test]
+    fn an_interior_cluster_hands_all_its_mass_to_the_grid() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        for j in 0..30 {
+            for i in 0..30 {
+                let position = Vector::new(50.25, 40.25) + Vector::new(i as Real, j as Real) * 0.5;
+                let particle = Particle::new(position, MaterialType::water());
+                state.add_particle(particle.with_mass(0.25 + (i % 3) as Real * 0.5));
+            }
+        }
+        transfer_particles_to_grid_serial(&mut state, 1.0 / 60.0);
+
+        let particle_mass = state.total_particle_mass();
+        let grid_mass = state.grid().total_cell_mass();
+        assert!(
+            (particle_mass - grid_mass).abs() < 1e-4 * particle_mass,
+            "{grid_mass}"
+        );
+    }
+
+    /// Round-off rather than a fixed tolerance bounds the error, so the
+    /// `f64` feature has to hold mass some nine orders of magnitude tighter.
+    #[test]
+    fn scattered_mass_matches_to_round_off_at_either_precision() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let count = 60 * 60;
+        for index in 0..count {
+            let (i, j) = ((index % 60) as Real, (index / 60) as Real);
+            // Off-lattice positions and masses, so no weight is exact
+            let jitter = Vector::new((i * 0.37).sin(), (j * 0.53).cos()) * 0.2;
+            let position = Vector::new(30.1, 30.3) + Vector::new(i, j) * 0.45 + jitter;
+            let particle = Particle::new(position, MaterialType::water());
+            state.add_particle(particle.with_mass(0.3 + (i * j * 0.01).sin().abs()));
         }
+        transfer_particles_to_grid_serial(&mut state, 1.0 / 60.0);
+
+        let particle_mass = state.total_particle_mass();
+        let error = (particle_mass - state.grid().total_cell_mass()).abs() / particle_mass;
+        let bound = (count as Real).sqrt() * 64.0 * Real::EPSILON;
+        assert!(error < bound, "relative error {error} above {bound}");
+        #[cfg(feature = "f64")]
+        assert!(error < 1e-12, "{error}");
+    }
+
+    #[test]
+    fn parallel_p2g_matches_the_serial_totals() {
+        let dt = 1.0 / 60.0;
+        let transferred = |transfer: fn(&mut MpmState, Real)| {
+            let mut state = MpmState::new(SolverParams::default(), zero_vector());
+            for j in 0..60 {
+                for i in 0..60 {
+                    let lattice = Vector::new(i as Real, j as Real) * 0.5;
+                    let velocity = Vector::new(j as Real * 0.1, -(i as Real) * 0.2);
+                    let position = Vector::new(40.25, 30.25) + lattice;
+                    let particle = Particle::new(position, MaterialType::water());
+                    state.add_particle(particle.with_velocity(velocity));
+                }
+            }
+            transfer(&mut state, dt);
+            state
+                .grid()
+                .iter_active_cells()
+                .fold((0.0, zero_vector()), |(mass, momentum), (_, node)| {
+                    (mass + node.mass, momentum + node.momentum)
+                })
+        };
+
+        let (parallel_mass, parallel_momentum) = transferred(transfer_particles_to_grid);
+        let (serial_mass, serial_momentum) = transferred(transfer_particles_to_grid_serial);
+        assert!((parallel_mass - serial_mass).abs() < 1e-3 * serial_mass);
+        let drift = (parallel_momentum - serial_momentum).norm();
+        assert!(
+            drift < 1e-3 * serial_momentum.norm(),
+            "momentum drifted by {drift}"
+        );
+    }
+
+    #[test]
+    fn density_reads_above_rest_when_squeezed_and_below_when_stretched() {
+        // Four half-mass particles a cell are water at its rest density
+        let rest = MaterialType::water().rest_density().unwrap();
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let mut block = |corner: Vector, spacing: Real| -> usize {
+            let first = state.particle_count();
+            for j in 0..16 {
+                for i in 0..16 {
+                    let position = corner + Vector::new(i as Real, j as Real) * spacing;
+                    let particle = Particle::new(position, MaterialType::water());
+                    state.add_particle(particle.with_mass(0.5));
+                }
+            }
+            // A particle in the middle of the block
+            first + 8 * 16 + 8
+        };
+        let squeezed = block(Vector::new(20.25, 20.25), 0.35);
+        let resting = block(Vector::new(50.25, 20.25), 0.5);
+        let stretched = block(Vector::new(80.25, 20.25), 0.7);
+
+        state.zero_grid();
+        transfer_particles_to_grid_serial(&mut state, 1.0 / 60.0);
+
+        let density = |index: usize| state.particles()[index].density;
+        assert!(
+            (density(resting) - rest).abs() < 0.05 * rest,
+            "{}",
+            density(resting)
+        );
+        assert!(density(squeezed) > 1.5 * rest, "{}", density(squeezed));
+        assert!(density(stretched) < 0.75 * rest, "{}", density(stretched));
+    }
+
+    #[test]
+    fn a_steady_scene_reuses_the_p2g_buffers() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        for j in 0..20 {
+            for i in 0..20 {
+                let position = Vector::new(54.25, 54.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+        // Where each buffer lives, bukkit lists sorted by bukkit
+        let buffers = |state: &mut MpmState| {
+            let scratch = state.p2g_scratch_mut();
+            let mut lists: Vec<(IVec2, *const usize)> = scratch
+                .bukkits
+                .iter()
+                .flatten()
+                .map(|(&bukkit, list)| (bukkit, list.as_ptr()))
+                .collect();
+            lists.sort_by_key(|&(bukkit, _)| (bukkit.x, bukkit.y));
+            (scratch.contributions.as_ptr(), lists)
+        };
+
+        state.zero_grid();
+        transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+        let first = buffers(&mut state);
+        assert_eq!(first.1.len(), 9);
+        state.zero_grid();
+        transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+        assert_eq!(buffers(&mut state), first);
     }
 }
@@ -0,0 +1,228 @@
+//! Melting and freezing
+//!
+//! Each `PhaseTransition` pairs a solid and a fluid material by name. A solid
+//! particle hotter than the melt point soaks up the excess as latent heat,
+//! holding at the melt point until it has taken `latent_heat` and turns
+//! fluid; a fluid particle colder than the freeze point gives latent heat up
+//! the same way before it solidifies. Keeping the freeze point below the melt
+//! point leaves a band where neither happens, so particles sitting near one
+//! threshold don't flip back and forth.
+
+use bevy::prelude::*;
+
+use crate::core::{MpmState, Particle, ParticlePlasticityState};
+use crate::materials::{MaterialModel, MaterialType};
+use crate::math::{Real, identity_matrix};
+
+/// A solid and the fluid it melts into.
+#[derive(Clone, Debug)]
+pub struct PhaseTransition {
+    pub solid: MaterialType,
+    pub fluid: MaterialType,
+    pub melt_point: Real,
+    /// At most `melt_point`.
+    pub freeze_point: Real,
+    /// Temperature-equivalent heat a particle takes in to melt and gives up
+    /// to freeze; 0 switches the moment a threshold is crossed.
+    pub latent_heat: Real,
+}
+
+impl PhaseTransition {
+    pub fn new(
+        solid: MaterialType,
+        fluid: MaterialType,
+        melt_point: Real,
+        freeze_point: Real,
+    ) -> Self {
+        Self {
+            solid,
+            fluid,
+            melt_point,
+            freeze_point: freeze_point.min(melt_point),
+            latent_heat: 0.0,
+        }
+    }
+
+    pub fn with_latent_heat(mut self, latent_heat: Real) -> Self {
+        self.latent_heat = latent_heat.max(0.0);
+        self
+    }
+
+    /// Moves `particle` along its phase change, switching its material once
+    /// the latent heat is in (or out).
+    pub fn apply(&self, particle: &mut Particle) {
+        let name = particle.material_type.material_name();
+        if name == self.solid.material_name() {
+            // Heat past the melt point goes into the latent store first
+            let heat = particle.temperature + particle.stored_latent_heat;
+            if heat <= self.melt_point {
+                particle.temperature = heat;
+                particle.stored_latent_heat = 0.0;
+            } else if heat < self.melt_point + self.latent_heat {
+                particle.temperature = self.melt_point;
+                particle.stored_latent_heat = heat - self.melt_point;
+            } else {
+                particle.temperature = heat - self.latent_heat;
+                particle.stored_latent_heat = 0.0;
+                self.melt(particle);
+            }
+        } else if name == self.fluid.material_name() {
+            let heat = particle.temperature - particle.stored_latent_heat;
+            if heat >= self.freeze_point {
+                particle.temperature = heat;
+                particle.stored_latent_heat = 0.0;
+            } else if heat > self.freeze_point - self.latent_heat {
+                particle.temperature = self.freeze_point;
+                particle.stored_latent_heat = self.freeze_point - heat;
+            } else {
+                particle.temperature = heat + self.latent_heat;
+                particle.stored_latent_heat = 0.0;
+                self.freeze(particle);
+            }
+        }
+    }
+
+    /// The fluid keeps the solid's volume change and drops its shear.
+    fn melt(&self, particle: &mut Particle) {
+        particle.material_type = self.fluid.clone();
+        particle.plasticity = ParticlePlasticityState::default();
+        self.fluid.project_deformation(particle);
+    }
+
+    /// The solid starts out unstrained in whatever shape the fluid had.
+    fn freeze(&self, particle: &mut Particle) {
+        particle.material_type = self.solid.clone();
+        particle.plasticity = ParticlePlasticityState::default();
+        particle.deformation_gradient = identity_matrix();
+    }
+}
+
+/// Phase transitions `update_phase_transitions` applies after every frame.
+#[derive(Resource, Default)]
+pub struct PhaseTransitions {
+    transitions: Vec<PhaseTransition>,
+}
+
+impl PhaseTransitions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, transition: PhaseTransition) {
+        self.transitions.push(transition);
+    }
+
+    pub fn with(mut self, transition: PhaseTransition) -> Self {
+        self.add(transition);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    /// Runs every transition over every particle.
+    pub fn apply(&self, state: &mut MpmState) {
+        for particle in state.particles_mut() {
+            if particle.failed {
+                continue;
+            }
+            for transition in &self.transitions {
+                transition.apply(particle);
+            }
+        }
+    }
+}
+
+/// Melts and freezes particles when `PhaseTransitions` is present.
+pub fn update_phase_transitions(
+    mut state: ResMut<MpmState>,
+    transitions: Option<Res<PhaseTransitions>>,
+) {
+    if let Some(transitions) = transitions
+        && !transitions.is_empty()
+    {
+        transitions.apply(&mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, SolverParams};
+    use crate::materials::ElasticParams;
+    use crate::math::Vector;
+    use crate::solver::grid_to_particle;
+    use crate::test_support::{horizontal_extent, run_frames_with};
+
+    /// Width of an 8x8 cell block of ice at `temperature` after it has sat
+    /// on the floor for a second, and the materials it ended up as.
+    fn block_after_a_second(temperature: Real) -> (Real, Vec<&'static str>) {
+        let ice = MaterialType::elastic(ElasticParams::new("ice", 200.0, 0.3));
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+        for j in 0..16 {
+            for i in 0..16 {
+                let position = Vector::new(60.25, 3.25) + Vector::new(i as Real, j as Real) * 0.5;
+                let particle = Particle::new(position, ice.clone()).with_temperature(temperature);
+                state.add_particle(particle);
+            }
+        }
+
+        let transition = PhaseTransition::new(ice, MaterialType::water(), 1.0, -1.0);
+        let world = run_frames_with(state, 60, |world, schedule| {
+            world.insert_resource(PhaseTransitions::new().with(transition.with_latent_heat(2.0)));
+            schedule.add_systems(update_phase_transitions.after(grid_to_particle));
+        });
+
+        let particles = world.resource::<MpmState>().particles();
+        let width = horizontal_extent(particles);
+        let materials = particles
+            .iter()
+            .map(|p| p.material_type.material_name())
+            .collect();
+        (width, materials)
+    }
+
+    #[test]
+    fn ice_heated_past_its_melt_point_turns_to_water_and_spreads() {
+        let (frozen_width, frozen) = block_after_a_second(0.0);
+        assert!(frozen.iter().all(|&name| name == "ice"));
+
+        let (melted_width, melted) = block_after_a_second(5.0);
+        assert_eq!(melted.len(), 256);
+        assert!(melted.iter().all(|&name| name == "water"));
+        assert!(
+            melted_width > frozen_width * 2.0,
+            "water spread to {melted_width}, ice to {frozen_width}"
+        );
+    }
+
+    #[test]
+    fn latent_heat_holds_a_melting_particle_at_the_melt_point() {
+        let ice = MaterialType::elastic(ElasticParams::new("ice", 200.0, 0.3));
+        let transition = PhaseTransition::new(ice.clone(), MaterialType::water(), 1.0, -1.0)
+            .with_latent_heat(2.0);
+        let mut particle = Particle::new(Vector::new(10.0, 10.0), ice).with_temperature(2.0);
+
+        transition.apply(&mut particle);
+        assert_eq!(particle.temperature, 1.0);
+        assert_eq!(particle.stored_latent_heat, 1.0);
+        assert_eq!(particle.material_type.material_name(), "ice");
+
+        // The rest of the latent heat melts it, and the leftover warms it
+        particle.temperature += 1.5;
+        transition.apply(&mut particle);
+        assert_eq!(particle.material_type.material_name(), "water");
+        assert_eq!(particle.temperature, 1.5);
+
+        // Below the melt point but above the freeze point it stays water
+        particle.temperature = -0.9;
+        transition.apply(&mut particle);
+        assert_eq!(particle.material_type.material_name(), "water");
+        assert_eq!(particle.stored_latent_heat, 0.0);
+    }
+}
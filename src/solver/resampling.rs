@@ -0,0 +1,312 @@
+//! Adaptive particle resampling
+//!
+//! Stretched material leaves cells with too few particles to resolve it,
+//! and holes open up. With a `ResamplingConfig` present, every frame each
+//! occupied cell below `min_particles_per_cell` has its heaviest particles
+//! split in two along the direction the material is stretching. The halves
+//! share the mass, volume and velocity, so mass and momentum are unchanged.
+//! Cells holding more than `max_particles_per_cell` go the other way: their
+//! lightest pairs of the same material are merged, so converging flow doesn't
+//! pile up particles without bound.
+
+use bevy::prelude::*;
+
+use crate::core::{MpmState, Particle, ParticleRemap};
+use crate::materials::utils::physics;
+use crate::math::{Real, Vector, consts};
+
+/// Thresholds for `resample_particles`.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct ResamplingConfig {
+    pub enabled: bool,
+    /// Occupied cells holding fewer particles than this get particles split
+    /// until they reach it.
+    pub min_particles_per_cell: usize,
+    /// Particles lighter than this are never split further.
+    pub min_split_mass: Real,
+    /// Cells holding more particles than this get pairs merged until they
+    /// are back down to it.
+    pub max_particles_per_cell: usize,
+}
+
+impl Default for ResamplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_particles_per_cell: 2,
+            min_split_mass: 0.1,
+            max_particles_per_cell: 8,
+        }
+    }
+}
+
+impl ResamplingConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_particles_per_cell(mut self, count: usize) -> Self {
+        self.min_particles_per_cell = count;
+        self
+    }
+
+    pub fn with_min_split_mass(mut self, mass: Real) -> Self {
+        self.min_split_mass = mass;
+        self
+    }
+
+    pub fn with_max_particles_per_cell(mut self, count: usize) -> Self {
+        self.max_particles_per_cell = count;
+        self
+    }
+
+    /// Splits particles in under-sampled cells and returns how many splits
+    /// were made. Stops at the particle cap; splitting never recycles old
+    /// particles.
+    pub fn split_under_sampled(&self, state: &mut MpmState) -> usize {
+        state.rebuild_particle_bins();
+        let order = state.particle_order();
+        let sparse_cells: Vec<Vec<usize>> = state
+            .particle_regions()
+            .iter()
+            .filter(|(_, range)| range.len() < self.min_particles_per_cell)
+            .map(|(_, range)| order[range.clone()].to_vec())
+            .collect();
+        let offset = 0.25 * state.grid().cell_width();
+
+        let mut splits = 0;
+        for mut cell in sparse_cells {
+            while cell.len() < self.min_particles_per_cell {
+                let particles = state.particles();
+                let Some(heaviest) = cell
+                    .iter()
+                    .copied()
+                    .filter(|&index| {
+                        let particle = &particles[index];
+                        let splittable = !particle.failed && !particle.is_static;
+                        splittable && particle.mass >= self.min_split_mass
+                    })
+                    .max_by(|&a, &b| particles[a].mass.total_cmp(&particles[b].mass))
+                else {
+                    break;
+                };
+
+                if state.at_capacity() {
+                    return splits;
+                }
+                let twin = split(&mut state.particles_mut()[heaviest], offset);
+                let added = state.add_particles(vec![twin]);
+                cell.push(added.appended.start);
+                splits += 1;
+            }
+        }
+        splits
+    }
+
+    /// Merges pairs of particles in over-packed cells and returns the
+    /// mapping to compose into `ParticleRemap` (empty when nothing merged).
+    /// Only particles of the same material are merged, so a cell packed
+    /// with several may stay above the maximum.
+    pub fn merge_over_packed(&self, state: &mut MpmState) -> Vec<Option<usize>> {
+        state.rebuild_particle_bins();
+        let order = state.particle_order();
+        let crowded_cells: Vec<Vec<usize>> = state
+            .particle_regions()
+            .iter()
+            .filter(|(_, range)| range.len() > self.max_particles_per_cell)
+            .map(|(_, range)| order[range.clone()].to_vec())
+            .collect();
+
+        let mut absorbed = Vec::new();
+        for mut cell in crowded_cells {
+            let mut excess = cell.len() - self.max_particles_per_cell;
+            let particles = state.particles();
+            cell.retain(|&index| {
+                let particle = &particles[index];
+                !particle.failed && !particle.is_static && particle.mass > 0.0
+            });
+
+            while excess > 0 {
+                // The lightest particle with a partner of its material, into
+                // the lightest such partner
+                let particles = state.particles();
+                cell.sort_by(|&a, &b| particles[a].mass.total_cmp(&particles[b].mass));
+                let pair = cell.iter().enumerate().find_map(|(slot, &light)| {
+                    let name = particles[light].material_type.material_name();
+                    cell[slot + 1..]
+                        .iter()
+                        .find(|&&other| particles[other].material_type.material_name() == name)
+                        .map(|&heavy| (slot, light, heavy))
+                });
+                let Some((slot, light, heavy)) = pair else {
+                    break;
+                };
+
+                let light_particle = particles[light].clone();
+                merge(&mut state.particles_mut()[heavy], &light_particle);
+                state.particle_set_mut().refresh_previous_velocity(heavy);
+                cell.remove(slot);
+                absorbed.push(light);
+                excess -= 1;
+            }
+        }
+        state.remove_particles(&absorbed)
+    }
+}
+
+/// Halves `particle` in place and returns its twin, the two `offset` either
+/// side of where it was along its direction of greatest stretch.
+fn split(particle: &mut Particle, offset: Real) -> Particle {
+    let direction = stretch_direction(particle) * offset;
+    particle.mass *= 0.5;
+    particle.volume0 *= 0.5;
+    particle.radius0 *= consts::FRAC_1_SQRT_2;
+    let mut twin = particle.clone();
+    particle.position -= direction;
+    twin.position += direction;
+    twin
+}
+
+/// Folds `absorbed` into `kept`. Mass and volume add up, and position,
+/// velocity and the affine field are mass-weighted, so mass and momentum
+/// are unchanged; the rest of the state stays `kept`'s.
+fn merge(kept: &mut Particle, absorbed: &Particle) {
+    let mass = kept.mass + absorbed.mass;
+    let (kept_share, absorbed_share) = (kept.mass / mass, absorbed.mass / mass);
+    kept.position = kept.position * kept_share + absorbed.position * absorbed_share;
+    kept.velocity = kept.velocity * kept_share + absorbed.velocity * absorbed_share;
+    kept.affine_momentum_matrix =
+        kept.affine_momentum_matrix * kept_share + absorbed.affine_momentum_matrix * absorbed_share;
+    kept.velocity_gradient =
+        kept.velocity_gradient * kept_share + absorbed.velocity_gradient * absorbed_share;
+    kept.mass = mass;
+    kept.volume0 += absorbed.volume0;
+    kept.radius0 = kept.radius0.hypot(absorbed.radius0);
+}
+
+/// Principal axis of the strain rate with the largest extension, or x for
+/// material at rest.
+fn stretch_direction(particle: &Particle) -> Vector {
+    let strain_rate = physics::strain_rate(&particle.velocity_gradient);
+    let eigen = strain_rate.symmetric_eigen();
+    let (first, second) = (eigen.eigenvalues.x, eigen.eigenvalues.y);
+    if (first - second).abs() <= Real::EPSILON {
+        return Vector::x();
+    }
+    let stretched = if first > second { 0 } else { 1 };
+    eigen.eigenvectors.column(stretched).into_owned()
+}
+
+/// Splits particles in under-sampled cells and merges them in over-packed
+/// ones when `ResamplingConfig` is present and enabled, composing the merges
+/// into `ParticleRemap`.
+pub fn resample_particles(
+    mut state: ResMut<MpmState>,
+    mut remap: ResMut<ParticleRemap>,
+    config: Option<Res<ResamplingConfig>>,
+) {
+    if let Some(config) = config
+        && config.enabled
+    {
+        config.split_under_sampled(&mut state);
+        remap.compose(config.merge_over_packed(&mut state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolverParams;
+    use crate::materials::{MaterialType, SandParams};
+    use crate::math::{Matrix, zero_vector};
+
+    #[test]
+    fn a_stretched_sheet_is_split_back_up_to_the_minimum_density() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        // One particle a cell, the sheet pulled apart along x
+        for j in 0..2 {
+            for i in 0..20 {
+                let position = Vector::new(40.5 + i as Real, 60.5 + j as Real);
+                let mut particle = Particle::new(position, MaterialType::water())
+                    .with_velocity(Vector::new((i as Real - 9.5) * 0.5, 0.0));
+                particle.velocity_gradient = Matrix::new(0.5, 0.0, 0.0, 0.0);
+                state.add_particle(particle);
+            }
+        }
+        let mass = |state: &MpmState| -> Real { state.particles().iter().map(|p| p.mass).sum() };
+        let momentum = |state: &MpmState| -> Vector {
+            state
+                .particles()
+                .iter()
+                .fold(zero_vector(), |sum, p| sum + p.velocity * p.mass)
+        };
+        let (mass_before, momentum_before) = (mass(&state), momentum(&state));
+
+        let config = ResamplingConfig::new().with_min_particles_per_cell(2);
+        assert_eq!(config.split_under_sampled(&mut state), 40);
+
+        assert_eq!(state.particle_count(), 80);
+        state.rebuild_particle_bins();
+        assert!(
+            state
+                .particle_regions()
+                .iter()
+                .all(|(_, range)| range.len() >= 2)
+        );
+        assert!((mass(&state) - mass_before).abs() < 1e-5);
+        assert!((momentum(&state) - momentum_before).norm() < 1e-4);
+        // Twins sit either side along the stretch
+        let (first, twin) = (&state.particles()[0], &state.particles()[40]);
+        let gap = twin.position - first.position;
+        assert!(
+            (gap.x.abs() - 0.5).abs() < 1e-5 && gap.y.abs() < 1e-5,
+            "{gap}"
+        );
+        assert_eq!(first.position + twin.position, Vector::new(81.0, 121.0));
+
+        // Nothing left to split, and halves too light stay as they are
+        assert_eq!(config.split_under_sampled(&mut state), 0);
+        let light = config
+            .with_min_particles_per_cell(4)
+            .with_min_split_mass(0.6);
+        assert_eq!(light.split_under_sampled(&mut state), 0);
+    }
+
+    #[test]
+    fn an_over_packed_cell_is_merged_down_without_losing_mass() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        for i in 0..15 {
+            let offset = Vector::new(i as Real % 4.0, (i / 4) as Real) * 0.2;
+            let particle = Particle::new(Vector::new(64.1, 64.1) + offset, MaterialType::water())
+                .with_velocity(Vector::new(i as Real, -1.0));
+            state.add_particle(particle.with_mass(1.0 + i as Real * 0.1));
+        }
+        let sand = MaterialType::sand(SandParams::sand());
+        state.add_particle(Particle::new(Vector::new(64.5, 64.5), sand));
+        let mass: Real = state.particles().iter().map(|p| p.mass).sum();
+        let momentum = state
+            .particles()
+            .iter()
+            .fold(zero_vector(), |sum, p| sum + p.velocity * p.mass);
+
+        let config = ResamplingConfig::new().with_max_particles_per_cell(8);
+        let mapping = config.merge_over_packed(&mut state);
+
+        assert_eq!(state.particle_count(), 8);
+        assert_eq!(mapping.len(), 16);
+        assert_eq!(mapping.iter().filter(|entry| entry.is_none()).count(), 8);
+        // The sand had no partner and is left alone
+        let sand = mapping[15].map(|index| &state.particles()[index]).unwrap();
+        assert_eq!(sand.material_type.material_name(), "sand");
+        assert_eq!(sand.mass, 1.0);
+
+        let particles = state.particles();
+        let merged_mass: Real = particles.iter().map(|p| p.mass).sum();
+        let merged_momentum = particles
+            .iter()
+            .fold(zero_vector(), |sum, p| sum + p.velocity * p.mass);
+        assert!((merged_mass - mass).abs() < 1e-4);
+        assert!((merged_momentum - momentum).norm() < 1e-3);
+        assert!(config.merge_over_packed(&mut state).is_empty());
+    }
+}
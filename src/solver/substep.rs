@@ -0,0 +1,134 @@
+//! Fixed-count substepping: the transfer stages run as their own schedule,
+//! driven several times per tick by one exclusive system.
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use crate::core::MpmState;
+
+use super::control::SimControl;
+
+/// Schedule holding one solver pass (zero grid through G2P). Runs
+/// `SolverParams::substeps` times per tick, each seeing `Time` advance by its
+/// share of the tick; P2G re-bins the particles at the start of every pass.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MpmSubstep;
+
+/// Runs `MpmSubstep` `SolverParams::substeps` times with the frame delta,
+/// scaled by `SimControl::speed` when present, split evenly between them.
+pub fn run_substeps(world: &mut World) {
+    let substeps = world.resource::<MpmState>().solver_params().substeps.max(1);
+    let speed = world
+        .get_resource::<SimControl>()
+        .map_or(1.0, |control| control.speed);
+    if substeps == 1 && speed == 1.0 {
+        world.run_schedule(MpmSubstep);
+        return;
+    }
+
+    let frame_time = world.resource::<Time>().clone();
+    let mut substep_time = Time::<()>::default();
+    substep_time.advance_by(frame_time.delta().mul_f32(speed.max(0.0)) / substeps);
+    world.insert_resource(substep_time);
+    for _ in 0..substeps {
+        world.run_schedule(MpmSubstep);
+    }
+    world.insert_resource(frame_time);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::{SolverParams, StaticParticleHandling};
+    use crate::core::Particle;
+    use crate::materials::MaterialType;
+    use crate::math::{Real, Vector, zero_vector};
+    use crate::test_support::{frame_world, solver_stages};
+
+    /// Furthest a fast block of water gets into a thin wall of static
+    /// particles at x = 64.
+    fn penetration_with(substeps: u32) -> Real {
+        let params = SolverParams::default()
+            .with_static_particles(StaticParticleHandling::Boundary)
+            .with_substeps(substeps);
+        let mut state = MpmState::new(params, zero_vector());
+        let lattice = |i: usize, j: usize| Vector::new(i as Real, j as Real) * 0.5;
+        for j in 0..160 {
+            for i in 0..2 {
+                let position = Vector::new(64.25, 24.25) + lattice(i, j);
+                let mut wall = Particle::new(position, MaterialType::water()).with_mass(0.25);
+                wall.is_static = true;
+                state.add_particle(wall);
+            }
+        }
+        for j in 0..10 {
+            for i in 0..10 {
+                let position = Vector::new(40.25, 60.25) + lattice(i, j);
+                let water = Particle::new(position, MaterialType::water())
+                    .with_mass(0.25)
+                    .with_velocity(Vector::new(300.0, 0.0));
+                state.add_particle(water);
+            }
+        }
+
+        let mut world = frame_world(state);
+        let mut substep = Schedule::new(MpmSubstep);
+        substep.add_systems(solver_stages());
+        world.add_schedule(substep);
+        let mut schedule = Schedule::default();
+        schedule.add_systems(run_substeps);
+
+        let mut furthest: Real = 0.0;
+        for _ in 0..30 {
+            schedule.run(&mut world);
+            let state = world.resource::<MpmState>();
+            let water = state
+                .particles()
+                .iter()
+                .filter(|particle| !particle.is_static);
+            furthest = water.fold(furthest, |furthest, particle| {
+                furthest.max(particle.position.x)
+            });
+        }
+        furthest
+    }
+
+    #[test]
+    fn substeps_cut_how_far_a_fast_particle_tunnels() {
+        let single = penetration_with(1);
+        let split = penetration_with(4);
+        assert!(split.is_finite());
+        assert!(
+            split + 0.5 < single,
+            "4 substeps reached {split}, 1 reached {single}"
+        );
+    }
+
+    #[test]
+    fn substeps_share_the_frame_time_and_restore_it() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(20));
+        world.insert_resource(time);
+        let params = SolverParams::default().with_substeps(4);
+        world.insert_resource(MpmState::new(params, zero_vector()));
+        world.init_resource::<Deltas>();
+        let mut substep = Schedule::new(MpmSubstep);
+        substep.add_systems(|time: Res<Time>, mut deltas: ResMut<Deltas>| {
+            deltas.0.push(time.delta());
+        });
+        world.add_schedule(substep);
+
+        run_substeps(&mut world);
+        assert_eq!(
+            world.resource::<Deltas>().0,
+            vec![Duration::from_millis(5); 4]
+        );
+        assert_eq!(world.resource::<Time>().delta(), Duration::from_millis(20));
+    }
+
+    #[derive(Resource, Default)]
+    struct Deltas(Vec<Duration>);
+}
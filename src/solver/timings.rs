@@ -0,0 +1,33 @@
+//! Per-stage solver timings
+//!
+//! Insert `SolverTimings` (or enable `MpmPlugin::with_profiling`) and the
+//! solver systems record how long each stage took on the last frame.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct SolverTimings {
+    pub p2g: Duration,
+    pub grid_update: Duration,
+    pub g2p: Duration,
+}
+
+impl SolverTimings {
+    pub fn p2g_ms(&self) -> f32 {
+        self.p2g.as_secs_f32() * 1000.0
+    }
+
+    pub fn grid_update_ms(&self) -> f32 {
+        self.grid_update.as_secs_f32() * 1000.0
+    }
+
+    pub fn g2p_ms(&self) -> f32 {
+        self.g2p.as_secs_f32() * 1000.0
+    }
+
+    pub fn total(&self) -> Duration {
+        self.p2g + self.grid_update + self.g2p
+    }
+}
@@ -0,0 +1,418 @@
+//! Particle sources and sinks for continuously fed scenes.
+//!
+//! An `Emitter` entity spawns particles at a steady rate, like a faucet, a
+//! `BoundaryInflow` entity feeds a stream in through a domain edge, like a
+//! river entering the scene, and a `Sink` entity drains whatever flows into
+//! it. New particles are appended to the state and drained ones go through
+//! the usual failed particle removal, so visuals following `ParticleRemap`
+//! (see `visuals::ParticleVisualPlugin`) stay in step with both.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::config::SimRng;
+use crate::core::{FailureReason, MpmState, Particle, ParticleBuilder};
+use crate::geometry::Region;
+use crate::materials::MaterialType;
+use crate::math::{Real, Vector};
+
+/// Spawns `rate_per_sec` particles of `material` per second at `position`,
+/// moving at `velocity`. Each one is offset by up to `jitter` along each
+/// axis of both its position and its velocity, so the stream doesn't come
+/// out as a single file.
+#[derive(Component, Clone, Debug)]
+pub struct Emitter {
+    pub position: Vector,
+    pub velocity: Vector,
+    pub rate_per_sec: Real,
+    pub material: MaterialType,
+    pub jitter: Real,
+    /// Particles owed but not yet spawned, always below one.
+    accumulated: Real,
+}
+
+impl Emitter {
+    pub fn new(
+        position: Vector,
+        velocity: Vector,
+        rate_per_sec: Real,
+        material: MaterialType,
+    ) -> Self {
+        Self {
+            position,
+            velocity,
+            rate_per_sec,
+            material,
+            jitter: 0.0,
+            accumulated: 0.0,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: Real) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Advances the emitter by `dt` and inserts the particles that fell due.
+    /// Returns how many were inserted; once the particle cap refuses one the
+    /// rest of this step's share is dropped.
+    pub fn emit(&mut self, state: &mut MpmState, dt: Real, rng: &mut impl Rng) -> usize {
+        self.accumulated += self.rate_per_sec.max(0.0) * dt;
+        let due = self.accumulated.floor();
+        self.accumulated -= due;
+
+        let mut inserted = 0;
+        for _ in 0..due as usize {
+            let position = jittered(self.position, self.jitter, rng);
+            let velocity = jittered(self.velocity, self.jitter, rng);
+            let particle = Particle::new(position, self.material.clone()).with_velocity(velocity);
+            if state.add_particle(particle).is_none() {
+                break;
+            }
+            inserted += 1;
+        }
+        inserted
+    }
+}
+
+/// `value` offset by up to `jitter` along each axis.
+fn jittered(value: Vector, jitter: Real, rng: &mut impl Rng) -> Vector {
+    if jitter <= 0.0 {
+        return value;
+    }
+    value
+        + Vector::new(
+            rng.random_range(-jitter..=jitter),
+            rng.random_range(-jitter..=jitter),
+        )
+}
+
+/// Runs every `Emitter` for this frame, drawing their jitter from `SimRng`.
+pub fn emit_particles(
+    time: Res<Time>,
+    mut state: ResMut<MpmState>,
+    mut rng: ResMut<SimRng>,
+    mut emitters: Query<&mut Emitter>,
+) {
+    let dt = time.delta_secs() as Real;
+    for mut emitter in emitters.iter_mut() {
+        emitter.emit(&mut state, dt, &mut **rng);
+    }
+}
+
+/// Edge of the simulation domain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DomainEdge {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl DomainEdge {
+    /// Unit normal pointing from the edge into the domain.
+    pub fn inward_normal(self) -> Vector {
+        match self {
+            Self::Left => Vector::new(1.0, 0.0),
+            Self::Right => Vector::new(-1.0, 0.0),
+            Self::Bottom => Vector::new(0.0, 1.0),
+            Self::Top => Vector::new(0.0, -1.0),
+        }
+    }
+}
+
+/// Cells between a domain edge and the first row a `BoundaryInflow` seeds,
+/// clear of the wall nodes and with room for the widest kernel.
+const INFLOW_INSET_CELLS: Real = 3.0;
+
+/// Feeds `material` in through `edge` at `velocity`, across the stretch
+/// from `span.0` to `span.1` along the edge. Particles sit `spacing` apart
+/// and each carries `density` times its share of the area, so the stream
+/// comes in as a continuous block; a new row is seeded each time the last
+/// one has moved `spacing` into the domain. Only the inward part of
+/// `velocity` paces the rows, so a velocity leaving the domain seeds
+/// nothing.
+#[derive(Component, Clone, Debug)]
+pub struct BoundaryInflow {
+    pub edge: DomainEdge,
+    pub span: (Real, Real),
+    pub velocity: Vector,
+    pub material: MaterialType,
+    pub density: Real,
+    pub spacing: Real,
+    /// How far the stream has moved in since the last row was seeded.
+    travelled: Real,
+}
+
+impl BoundaryInflow {
+    pub fn new(
+        edge: DomainEdge,
+        span: (Real, Real),
+        velocity: Vector,
+        material: MaterialType,
+        density: Real,
+    ) -> Self {
+        Self {
+            edge,
+            span,
+            velocity,
+            material,
+            density,
+            spacing: 0.5,
+            travelled: 0.0,
+        }
+    }
+
+    pub fn with_spacing(mut self, spacing: Real) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Advances the stream by `dt` and seeds the rows that fell due. Returns
+    /// how many particles were inserted; once the particle cap refuses one
+    /// the rest of this step's rows are dropped.
+    pub fn feed(&mut self, state: &mut MpmState, dt: Real) -> usize {
+        if self.spacing <= 0.0 {
+            return 0;
+        }
+        let normal = self.edge.inward_normal();
+        let tangent = Vector::new(normal.y.abs(), normal.x.abs());
+        self.travelled += self.velocity.dot(&normal).max(0.0) * dt;
+
+        let cell_width = state.grid().cell_width();
+        let extent = state.grid().resolution() as Real * cell_width;
+        let volume = self.spacing * self.spacing;
+        let (start, end) = (self.span.0.min(self.span.1), self.span.0.max(self.span.1));
+        let per_row = ((end - start) / self.spacing).floor() as usize;
+
+        let mut inserted = 0;
+        while self.travelled >= self.spacing {
+            self.travelled -= self.spacing;
+            // The row fell due `travelled` ago, so it has moved that far in
+            let depth = INFLOW_INSET_CELLS * cell_width + self.travelled;
+            let row = match self.edge {
+                DomainEdge::Left => Vector::new(depth, 0.0),
+                DomainEdge::Right => Vector::new(extent - depth, 0.0),
+                DomainEdge::Bottom => Vector::new(0.0, depth),
+                DomainEdge::Top => Vector::new(0.0, extent - depth),
+            };
+            for i in 0..per_row {
+                let along = start + (i as Real + 0.5) * self.spacing;
+                let particle = ParticleBuilder::new(self.material.clone())
+                    .with_position(row + tangent * along)
+                    .with_velocity(self.velocity)
+                    .with_mass_and_volume(self.density * volume, volume)
+                    .build();
+                if state.add_particle(particle).is_none() {
+                    self.travelled = 0.0;
+                    return inserted;
+                }
+                inserted += 1;
+            }
+        }
+        inserted
+    }
+}
+
+/// Runs every `BoundaryInflow` for this frame.
+pub fn feed_inflows(
+    time: Res<Time>,
+    mut state: ResMut<MpmState>,
+    mut inflows: Query<&mut BoundaryInflow>,
+) {
+    let dt = time.delta_secs() as Real;
+    for mut inflow in inflows.iter_mut() {
+        inflow.feed(&mut state, dt);
+    }
+}
+
+/// Removes every particle inside `region`. Particles are only marked
+/// failed, so `remove_failed_particles_system` takes them out with the rest
+/// of the frame's failures and records them in `ParticleRemap`.
+#[derive(Component)]
+pub struct Sink {
+    pub region: Box<dyn Region>,
+}
+
+impl Sink {
+    pub fn new(region: impl Region) -> Self {
+        Self {
+            region: Box::new(region),
+        }
+    }
+}
+
+/// Marks the particles inside any `Sink` as failed.
+pub fn drain_sinks(mut state: ResMut<MpmState>, sinks: Query<&Sink>) {
+    if sinks.is_empty() {
+        return;
+    }
+    for particle in state.particles_mut() {
+        if sinks
+            .iter()
+            .any(|sink| sink.region.contains(particle.position))
+        {
+            particle.fail(FailureReason::Sink);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GRAVITY, REST_DENSITY, SolverParams};
+    use crate::core::{
+        ParticleRemap, clear_particle_remap_system, remove_failed_particles_system, zero_grid,
+    };
+    use crate::geometry::RectRegion;
+    use crate::math::zero_vector;
+    use crate::test_support::{frame_world, solver_schedule};
+
+    #[test]
+    fn an_emitter_at_100_per_second_adds_100_particles_a_second() {
+        let mut world = frame_world(MpmState::new(SolverParams::default(), GRAVITY));
+        world.init_resource::<SimRng>();
+        let faucet = Emitter::new(
+            Vector::new(64.0, 100.0),
+            Vector::new(0.0, -20.0),
+            100.0,
+            MaterialType::water(),
+        );
+        world.spawn(faucet.with_jitter(0.5));
+        let mut schedule = Schedule::default();
+        schedule.add_systems(emit_particles);
+
+        for _ in 0..60 {
+            schedule.run(&mut world);
+        }
+
+        let state = world.resource::<MpmState>();
+        assert!(
+            (99..=100).contains(&state.particle_count()),
+            "{}",
+            state.particle_count()
+        );
+        for particle in state.particles() {
+            assert!((particle.position - Vector::new(64.0, 100.0)).amax() <= 0.5);
+            assert!((particle.velocity - Vector::new(0.0, -20.0)).amax() <= 0.5);
+        }
+    }
+
+    #[test]
+    fn a_left_edge_inflow_fills_the_domain_from_the_left() {
+        let mut world = frame_world(MpmState::new(SolverParams::default(), zero_vector()));
+        let river = BoundaryInflow::new(
+            DomainEdge::Left,
+            (40.0, 60.0),
+            Vector::new(10.0, 0.0),
+            MaterialType::water(),
+            REST_DENSITY,
+        );
+        world.spawn(river);
+        let mut schedule = solver_schedule();
+        schedule.add_systems(feed_inflows.before(zero_grid));
+
+        // Leading edge and particle count after each second
+        let mut progress = Vec::new();
+        for _ in 0..2 {
+            for _ in 0..60 {
+                schedule.run(&mut world);
+            }
+            let state = world.resource::<MpmState>();
+            let front = state
+                .particles()
+                .iter()
+                .map(|p| p.position.x)
+                .fold(0.0, Real::max);
+            progress.push((front, state.particle_count()));
+        }
+
+        // 20 rows of 40 a second at 10 cells/s and half a cell apart
+        let (first_front, first_count) = progress[0];
+        let (second_front, second_count) = progress[1];
+        assert!((760..=800).contains(&first_count), "{first_count}");
+        assert!((1560..=1600).contains(&second_count), "{second_count}");
+        assert!(first_front > 10.0, "front at {first_front}");
+        assert!(second_front > first_front + 5.0, "front at {second_front}");
+        let state = world.resource::<MpmState>();
+        assert!(
+            state
+                .particles()
+                .iter()
+                .all(|p| p.position.x > 2.0 && p.position.x < 40.0)
+        );
+    }
+
+    #[test]
+    fn a_sink_swallows_what_flows_in_and_leaves_the_rest() {
+        let mut world = World::new();
+        world.insert_resource(MpmState::new(SolverParams::default(), GRAVITY));
+        world.init_resource::<ParticleRemap>();
+        world.spawn(Sink::new(RectRegion::new(
+            Vector::new(80.0, 0.0),
+            Vector::new(128.0, 40.0),
+        )));
+        let mut schedule = Schedule::default();
+        schedule.add_systems(
+            (
+                drain_sinks,
+                remove_failed_particles_system,
+                check_remap,
+                clear_particle_remap_system,
+            )
+                .chain(),
+        );
+
+        /// Where each tagged particle should sit, kept up to date from the remap.
+        #[derive(Resource)]
+        struct Tracked(Vec<Option<usize>>);
+        fn check_remap(remap: Res<ParticleRemap>, mut tracked: ResMut<Tracked>) {
+            if remap.map.is_empty() {
+                return;
+            }
+            for slot in tracked.0.iter_mut() {
+                *slot = slot.and_then(|index| remap.map[index]);
+            }
+        }
+
+        // A stream of 40 particles flowing right at y = 20, interleaved with
+        // 40 resting ones at y = 60, tagged with their insertion order
+        let mut state = world.resource_mut::<MpmState>();
+        for i in 0..80 {
+            let y = if i % 2 == 0 { 20.0 } else { 60.0 };
+            let x = 50.0 + (i / 2) as Real * 0.5;
+            let mut particle = Particle::new(Vector::new(x, y), MaterialType::water());
+            particle.user_data = i;
+            state.add_particle(particle);
+        }
+        world.insert_resource(Tracked((0..80).map(Some).collect()));
+
+        for _ in 0..20 {
+            for particle in world.resource_mut::<MpmState>().particles_mut() {
+                if particle.position.y < 40.0 {
+                    particle.position.x += 1.0;
+                }
+            }
+            schedule.run(&mut world);
+        }
+
+        let state = world.resource::<MpmState>();
+        let tracked = &world.resource::<Tracked>().0;
+        for (tag, slot) in tracked.iter().enumerate() {
+            if tag % 2 == 0 {
+                let position = Vector::new(50.0 + (tag / 2) as Real * 0.5 + 20.0, 20.0);
+                // Swallowed once it reaches x = 80, every frame taking several
+                assert_eq!(slot.is_none(), position.x >= 80.0, "particle {tag}");
+            } else {
+                let index = slot.expect("resting particles are never drained");
+                assert_eq!(state.particles()[index].user_data, tag as u64);
+            }
+        }
+        assert!(
+            state
+                .particles()
+                .iter()
+                .all(|particle| particle.position.x < 80.0)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! World and schedule scaffolding shared by the solver tests.
+
+use std::time::Duration;
+
+use bevy::ecs::schedule::ScheduleConfigs;
+use bevy::ecs::system::ScheduleSystem;
+use bevy::prelude::*;
+
+use crate::config::{GRAVITY, SolverParams};
+use crate::core::{MpmState, Particle, cleanup_grid_cells, zero_grid};
+use crate::materials::MaterialType;
+use crate::math::{Real, Vector};
+use crate::solver::{
+    grid_to_particle, grid_update, particle_to_grid, transfer_grid_to_particles_serial,
+    transfer_particles_to_grid_serial,
+};
+
+/// A world holding `state`, with `Time` advanced by one 60 Hz frame so every
+/// schedule run steps 1/60 s.
+pub(crate) fn frame_world(state: MpmState) -> World {
+    let mut world = World::new();
+    let mut time = Time::<()>::default();
+    time.advance_by(Duration::from_secs_f32(1.0 / 60.0));
+    world.insert_resource(time);
+    world.insert_resource(state);
+    world
+}
+
+/// The solver stages of one step, chained.
+pub(crate) fn solver_stages() -> ScheduleConfigs<ScheduleSystem> {
+    (
+        zero_grid,
+        particle_to_grid,
+        cleanup_grid_cells,
+        grid_update,
+        grid_to_particle,
+    )
+        .chain()
+}
+
+/// A schedule stepping the solver once per run. Tests slot their own
+/// systems in around the stages, e.g. `.after(grid_to_particle)`.
+pub(crate) fn solver_schedule() -> Schedule {
+    let mut schedule = Schedule::default();
+    schedule.add_systems(solver_stages());
+    schedule
+}
+
+/// Steps `state` through `frames` frames and hands back the world.
+pub(crate) fn run_frames(state: MpmState, frames: usize) -> World {
+    run_frames_with(state, frames, |_, _| {})
+}
+
+/// `run_frames`, with `setup` adding resources and systems first.
+pub(crate) fn run_frames_with(
+    state: MpmState,
+    frames: usize,
+    setup: impl FnOnce(&mut World, &mut Schedule),
+) -> World {
+    let mut world = frame_world(state);
+    let mut schedule = solver_schedule();
+    setup(&mut world, &mut schedule);
+    for _ in 0..frames {
+        schedule.run(&mut world);
+    }
+    world
+}
+
+/// Distance between the leftmost and rightmost particle.
+pub(crate) fn horizontal_extent(particles: &[Particle]) -> Real {
+    let xs = particles.iter().map(|particle| particle.position.x);
+    let min_x = xs.clone().fold(Real::MAX, Real::min);
+    let max_x = xs.fold(Real::MIN, Real::max);
+    max_x - min_x
+}
+
+/// Horizontal spread of a 10x10 cell block of `material` after 60 steps
+/// under gravity.
+pub(crate) fn spread_after_one_second(material: MaterialType) -> Real {
+    let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+    for j in 0..20 {
+        for i in 0..20 {
+            let position = Vector::new(59.25, 4.25) + Vector::new(i as Real, j as Real) * 0.5;
+            state.add_particle(Particle::new(position, material.clone()));
+        }
+    }
+
+    let world = run_frames(state, 60);
+    horizontal_extent(world.resource::<MpmState>().particles())
+}
+
+/// Mean height of each of `layers` after `frames` frames under gravity, in
+/// layer order. The layers start as 10x6 cell blocks stacked bottom first,
+/// each particle filling a quarter cell at its material's rest density; the
+/// materials need distinct names.
+pub(crate) fn mean_layer_heights_after(layers: &[MaterialType], frames: usize) -> Vec<Real> {
+    let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+    for (layer, material) in layers.iter().enumerate() {
+        let base = 2.25 + 6.0 * layer as Real;
+        let mass = material.rest_density().unwrap_or(1.0) * 0.25;
+        for j in 0..12 {
+            for i in 0..20 {
+                let position = Vector::new(59.25, base) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, material.clone()).with_mass(mass));
+            }
+        }
+    }
+
+    let world = run_frames(state, frames);
+    let particles = world.resource::<MpmState>().particles();
+    layers
+        .iter()
+        .map(|material| {
+            let name = material.material_name();
+            let heights: Vec<Real> = particles
+                .iter()
+                .filter(|p| p.material_type.material_name() == name)
+                .map(|p| p.position.y)
+                .collect();
+            heights.iter().sum::<Real>() / heights.len() as Real
+        })
+        .collect()
+}
+
+/// One serial solver step, for tests that need the sums in a fixed order.
+pub(crate) fn step_serial(state: &mut MpmState, dt: Real) {
+    state.zero_grid();
+    transfer_particles_to_grid_serial(state, dt);
+    state.cleanup_grid();
+    state.integrate_grid_velocities(dt);
+    transfer_grid_to_particles_serial(state, dt);
+}
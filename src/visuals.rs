@@ -0,0 +1,400 @@
+//! Bevy-side helpers for rendering particles
+//!
+//! Links a render entity to a particle index so the two can be spawned in a
+//! single call, and `ParticleVisualPlugin` keeps such entities in step with
+//! the simulation without any per-app glue.
+
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
+use bevy::prelude::*;
+
+use crate::core::{
+    MpmState, Particle, ParticleRemap, clear_particle_remap_system, remove_failed_particles_system,
+};
+use crate::math::to_bevy_vec2;
+
+/// Marks an entity as the visual for the particle at `index`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParticleVisual {
+    pub index: usize,
+}
+
+/// Inserts `particle` into the simulation and spawns its 2D visual.
+///
+/// Returns `None`, spawning nothing, when the particle cap rejected the
+/// insertion. When the cap recycled an old particle instead, its index is
+/// returned and `ParticleVisualPlugin` despawns the old particle's visual.
+///
+/// The entity is placed at the particle position in simulation space; apps
+/// with their own world mapping should overwrite the `Transform`.
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use mpm2d::math::Vector;
+/// use mpm2d::visuals::spawn_visual_particle;
+/// use mpm2d::{MaterialType, MpmState, Particle};
+///
+/// fn spawn(
+///     mut commands: Commands,
+///     mut meshes: ResMut<Assets<Mesh>>,
+///     mut materials: ResMut<Assets<ColorMaterial>>,
+///     mut state: ResMut<MpmState>,
+/// ) {
+///     let particle = Particle::new(Vector::new(64.0, 64.0), MaterialType::water());
+///     let _spawned = spawn_visual_particle(
+///         &mut commands,
+///         &mut meshes,
+///         &mut materials,
+///         &mut state,
+///         particle,
+///         Circle::new(1.0),
+///         Color::WHITE,
+///     );
+/// }
+/// ```
+pub fn spawn_visual_particle(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    state: &mut MpmState,
+    particle: Particle,
+    mesh: impl Into<Mesh>,
+    color: Color,
+) -> Option<(usize, Entity)> {
+    let position = to_bevy_vec2(&particle.position);
+    let index = state.add_particle(particle)?;
+    let entity = commands
+        .spawn((
+            ParticleVisual { index },
+            Mesh2d(meshes.add(mesh)),
+            MeshMaterial2d(materials.add(color)),
+            Transform::from_translation(position.extend(0.0)),
+        ))
+        .id();
+    Some((index, entity))
+}
+
+/// Gives every particle a visual and keeps it in step with the simulation:
+/// visuals of removed particles are despawned and the rest re-indexed from
+/// `ParticleRemap`, recycled particles get a fresh entity in place of the old
+/// one, particles without one get a new entity with the plugin's mesh and
+/// colour, and every `Transform` follows its particle into world space.
+///
+/// The systems run between `remove_failed_particles_system` and
+/// `clear_particle_remap_system`, so they must share `MpmPlugin`'s schedule:
+/// `Update` by default, `FixedUpdate` when it was given a fixed timestep.
+/// Visuals spawned with `spawn_visual_particle` are adopted as they are.
+pub struct ParticleVisualPlugin {
+    pub mesh: Mesh,
+    pub color: Color,
+    pub schedule: InternedScheduleLabel,
+}
+
+impl ParticleVisualPlugin {
+    pub fn new(mesh: impl Into<Mesh>, color: Color) -> Self {
+        Self {
+            mesh: mesh.into(),
+            color,
+            schedule: Update.intern(),
+        }
+    }
+
+    /// Runs the visual systems in `schedule` instead of `Update`.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+/// Mesh and material of the visuals `ParticleVisualPlugin` spawns.
+#[derive(Resource, Clone, Default)]
+pub struct ParticleVisualStyle {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<ColorMaterial>,
+}
+
+impl Plugin for ParticleVisualPlugin {
+    fn build(&self, app: &mut App) {
+        // Headless apps have no asset storage; their visuals get default handles
+        let world = app.world_mut();
+        let mesh = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .map(|mut meshes| meshes.add(self.mesh.clone()))
+            .unwrap_or_default();
+        let material = world
+            .get_resource_mut::<Assets<ColorMaterial>>()
+            .map(|mut materials| materials.add(self.color))
+            .unwrap_or_default();
+        app.insert_resource(ParticleVisualStyle { mesh, material });
+
+        app.add_systems(
+            self.schedule,
+            (
+                apply_particle_remap,
+                spawn_particle_visuals,
+                refresh_recycled_visuals,
+                sync_particle_transforms,
+            )
+                .chain()
+                .after(remove_failed_particles_system)
+                .before(clear_particle_remap_system),
+        );
+    }
+}
+
+/// Despawns the visuals of removed particles and re-indexes the rest.
+pub fn apply_particle_remap(
+    mut commands: Commands,
+    remap: Res<ParticleRemap>,
+    mut visuals: Query<(Entity, &mut ParticleVisual)>,
+) {
+    if remap.map.is_empty() {
+        return;
+    }
+    for (entity, mut visual) in visuals.iter_mut() {
+        // Particles added after the removals have no entry and keep their index
+        let Some(&entry) = remap.map.get(visual.index) else {
+            continue;
+        };
+        match entry {
+            Some(index) => visual.index = index,
+            None => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+/// Swaps the visual of each recycled particle for a fresh entity, so
+/// whatever the app attached to the old particle's visual goes with it.
+///
+/// A visual linked since the last run, as `spawn_visual_particle` does when
+/// the cap recycles, is already the new particle's and is kept. Runs after
+/// `spawn_particle_visuals`, so the visuals that spawned last frame count as
+/// old.
+pub fn refresh_recycled_visuals(
+    mut commands: Commands,
+    state: Res<MpmState>,
+    style: Res<ParticleVisualStyle>,
+    visuals: Query<(Entity, Ref<ParticleVisual>)>,
+) {
+    if state.recycled_particles().is_empty() {
+        return;
+    }
+    let mut recycled = state.recycled_particles().to_vec();
+    recycled.sort_unstable();
+    recycled.dedup();
+
+    let mut linked = Vec::new();
+    for (entity, visual) in visuals.iter() {
+        if recycled.binary_search(&visual.index).is_err() {
+            continue;
+        }
+        if visual.is_added() {
+            linked.push(visual.index);
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    for index in recycled {
+        if !linked.contains(&index) {
+            spawn_styled_visual(&mut commands, &state, &style, index);
+        }
+    }
+}
+
+/// Spawns visuals for the particles appended since the last run.
+///
+/// Particles are otherwise only removed or recycled in place, so once the
+/// remap is applied the indices without a visual are those past the count
+/// of visuals still alive. A recycled particle that `spawn_visual_particle`
+/// already linked counts twice until `refresh_recycled_visuals` runs, which
+/// can hold a particle appended in the same frame back to the next one.
+pub fn spawn_particle_visuals(
+    mut commands: Commands,
+    state: Res<MpmState>,
+    style: Res<ParticleVisualStyle>,
+    visuals: Query<&ParticleVisual>,
+) {
+    for index in visuals.iter().len()..state.particle_count() {
+        spawn_styled_visual(&mut commands, &state, &style, index);
+    }
+}
+
+fn spawn_styled_visual(
+    commands: &mut Commands,
+    state: &MpmState,
+    style: &ParticleVisualStyle,
+    index: usize,
+) {
+    let position = state.sim_to_world(state.particles()[index].position);
+    commands.spawn((
+        ParticleVisual { index },
+        Mesh2d(style.mesh.clone()),
+        MeshMaterial2d(style.material.clone()),
+        Transform::from_translation(position.extend(0.0)),
+    ));
+}
+
+/// Moves every visual to its particle's world-space position.
+pub fn sync_particle_transforms(
+    state: Res<MpmState>,
+    mut visuals: Query<(&ParticleVisual, &mut Transform)>,
+) {
+    let particles = state.particles();
+    for (visual, mut transform) in visuals.iter_mut() {
+        if let Some(particle) = particles.get(visual.index) {
+            transform.translation = state.sim_to_world(particle.position).extend(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CapacityHandling, GRAVITY, SolverParams};
+    use crate::materials::MaterialType;
+    use crate::math::{Real, Vector};
+
+    /// Runs `spawn_visual_particle` once against a bare world.
+    fn spawn_once(world: &mut World, state: &mut MpmState) -> (usize, Entity) {
+        let mut meshes = Assets::<Mesh>::default();
+        let mut materials = Assets::<ColorMaterial>::default();
+        let particle = Particle::new(Vector::new(12.0, 34.0), MaterialType::water());
+        let mut commands = world.commands();
+        let spawned = spawn_visual_particle(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            state,
+            particle,
+            Circle::new(1.0),
+            Color::WHITE,
+        );
+        world.flush();
+        spawned.unwrap()
+    }
+
+    #[test]
+    fn one_call_inserts_the_particle_and_links_its_visual() {
+        let mut world = World::new();
+        let mut state = MpmState::new(SolverParams::default(), GRAVITY);
+
+        for expected in 0..2 {
+            let (index, entity) = spawn_once(&mut world, &mut state);
+            assert_eq!(index, expected);
+            assert_eq!(
+                world.get::<ParticleVisual>(entity),
+                Some(&ParticleVisual { index })
+            );
+            let translation = world.get::<Transform>(entity).unwrap().translation;
+            assert_eq!(translation, Vec3::new(12.0, 34.0, 0.0));
+        }
+        assert_eq!(state.particles().len(), 2);
+        let visuals = world.query::<&ParticleVisual>().iter(&world).count();
+        assert_eq!(visuals, 2);
+    }
+
+    #[test]
+    fn visuals_follow_their_particles_and_vanish_with_them() {
+        let mut app = App::new();
+        app.insert_resource(MpmState::new(SolverParams::default(), GRAVITY));
+        app.init_resource::<ParticleRemap>();
+        app.add_systems(
+            Update,
+            (remove_failed_particles_system, clear_particle_remap_system).chain(),
+        );
+        app.add_plugins(ParticleVisualPlugin::new(Circle::new(1.0), Color::WHITE));
+
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        for i in 0..3 {
+            let position = Vector::new(10.0 * i as Real, 5.0);
+            state.add_particle(Particle::new(position, MaterialType::water()));
+        }
+        app.update();
+        let mut visuals = app.world_mut().query::<(Entity, &ParticleVisual)>();
+        let mut linked: Vec<(usize, Entity)> = visuals
+            .iter(app.world())
+            .map(|(entity, visual)| (visual.index, entity))
+            .collect();
+        linked.sort();
+        assert_eq!(
+            linked.iter().map(|&(index, _)| index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        // The middle particle fails and is removed; the last one moves down an index
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        state.particles_mut()[1].failed = true;
+        state.particles_mut()[2].position = Vector::new(40.0, 7.0);
+        app.update();
+
+        assert!(app.world().get_entity(linked[1].1).is_err());
+        assert_eq!(
+            app.world()
+                .get::<ParticleVisual>(linked[2].1)
+                .unwrap()
+                .index,
+            1
+        );
+        let translation = app
+            .world()
+            .get::<Transform>(linked[2].1)
+            .unwrap()
+            .translation;
+        assert_eq!(translation, Vec3::new(40.0, 7.0, 0.0));
+        assert_eq!(visuals.iter(app.world()).count(), 2);
+
+        // A particle added later gets its own visual on the next update
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        state.add_particle(Particle::new(Vector::new(60.0, 5.0), MaterialType::water()));
+        app.update();
+        assert_eq!(visuals.iter(app.world()).count(), 3);
+    }
+
+    #[test]
+    fn a_recycled_particle_gets_a_fresh_visual() {
+        let params = SolverParams::default().with_max_particles(2, CapacityHandling::RecycleOldest);
+        let mut app = App::new();
+        app.insert_resource(MpmState::new(params, GRAVITY));
+        app.init_resource::<ParticleRemap>();
+        app.add_systems(
+            Update,
+            (remove_failed_particles_system, clear_particle_remap_system).chain(),
+        );
+        app.add_plugins(ParticleVisualPlugin::new(Circle::new(1.0), Color::WHITE));
+
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        for (x, age) in [(10.0, 2.0), (20.0, 1.0)] {
+            let mut particle = Particle::new(Vector::new(x, 5.0), MaterialType::water());
+            particle.age = age;
+            state.add_particle(particle);
+        }
+        app.update();
+        let mut visuals = app.world_mut().query::<(Entity, &ParticleVisual)>();
+        let old = visuals
+            .iter(app.world())
+            .find(|(_, visual)| visual.index == 0)
+            .map(|(entity, _)| entity)
+            .unwrap();
+
+        let mut state = app.world_mut().resource_mut::<MpmState>();
+        let particle = Particle::new(Vector::new(30.0, 5.0), MaterialType::water());
+        assert_eq!(state.add_particle(particle), Some(0));
+        app.update();
+
+        assert!(app.world().get_entity(old).is_err());
+        let fresh: Vec<Entity> = visuals
+            .iter(app.world())
+            .filter(|(_, visual)| visual.index == 0)
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(fresh.len(), 1);
+        let translation = app.world().get::<Transform>(fresh[0]).unwrap().translation;
+        assert_eq!(translation, Vec3::new(30.0, 5.0, 0.0));
+        assert_eq!(visuals.iter(app.world()).count(), 2);
+        assert!(
+            app.world()
+                .resource::<MpmState>()
+                .recycled_particles()
+                .is_empty()
+        );
+    }
+}
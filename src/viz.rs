@@ -0,0 +1,190 @@
+//! Raster outputs of grid fields and debug overlays
+//!
+//! CPU-side images of the simulation for UI overlays and minimaps; wrap the
+//! returned buffers in a Bevy `Image` to display them. `draw_debug_gizmos`
+//! sketches the grid itself with Bevy gizmos.
+
+use bevy::math::bounding::Aabb2d;
+use bevy::prelude::*;
+
+use crate::core::{GridInterpolation, MpmState};
+use crate::math::{Real, repeat_vector, to_bevy_scalar, to_bevy_vec2};
+
+/// Seconds of travel each node's velocity arrow spans.
+const VELOCITY_ARROW_SECONDS: Real = 0.1;
+
+/// Samples the interpolated node mass on a `resolution` pixel grid covering
+/// `bounds` (world units).
+///
+/// Pixels are sampled at their centres, mapped into simulation space with
+/// `MpmState::world_to_sim`, and returned row-major, top row
+/// (largest y) first, as `Image` expects. Regions without active nodes read
+/// zero.
+pub fn density_texture(state: &MpmState, resolution: UVec2, bounds: Aabb2d) -> Vec<f32> {
+    let mut texture = Vec::with_capacity((resolution.x * resolution.y) as usize);
+    let pixel_size = (bounds.max - bounds.min) / resolution.as_vec2();
+    let grid = state.grid();
+    let cell_width = grid.cell_width();
+
+    for row in 0..resolution.y {
+        let y = bounds.max.y - (row as f32 + 0.5) * pixel_size.y;
+        for column in 0..resolution.x {
+            let x = bounds.min.x + (column as f32 + 0.5) * pixel_size.x;
+            let position = state.world_to_sim(Vec2::new(x, y));
+            let interpolation = GridInterpolation::compute_for_particle(position, cell_width);
+            let density: Real = interpolation
+                .iter_neighbors()
+                .filter_map(|(coord, weight, _)| {
+                    grid.get_cell_coord(coord).map(|node| node.mass * weight)
+                })
+                .sum();
+            texture.push(to_bevy_scalar(density));
+        }
+    }
+
+    texture
+}
+
+/// Draws the domain boundary, an outline around every node with mass
+/// (blue for the lightest through red for the heaviest) and each node's
+/// velocity, in world space per the grid's origin and scale.
+///
+/// Added by `MpmPlugin` in debug mode; needs Bevy's gizmo plugin, which
+/// `DefaultPlugins` brings.
+pub fn draw_debug_gizmos(state: Res<MpmState>, mut gizmos: Gizmos) {
+    let grid = state.grid();
+    let scale = grid.scale();
+    let cell_width = grid.cell_width();
+    let domain = grid.resolution() as Real * cell_width;
+    let domain_centre = state.sim_to_world(repeat_vector(domain * 0.5));
+    let domain_size = Vec2::splat(to_bevy_scalar(domain * scale));
+    gizmos.rect_2d(domain_centre, domain_size, Color::WHITE);
+
+    let heaviest = grid
+        .iter_active_cells()
+        .map(|(_, node)| node.mass)
+        .fold(0.0, Real::max);
+    if heaviest <= 0.0 {
+        return;
+    }
+    for ((x, y), node) in grid.iter_active_cells() {
+        if node.mass <= 0.0 {
+            continue;
+        }
+        let node_position = grid.node_world_center(IVec2::new(x, y));
+        let centre = to_bevy_vec2(&node_position);
+        let hue = to_bevy_scalar(240.0 * (1.0 - node.mass / heaviest));
+        let size = Vec2::splat(to_bevy_scalar(scale * cell_width));
+        gizmos.rect_2d(centre, size, Color::hsl(hue, 1.0, 0.5));
+        if node.velocity.norm_squared() == 0.0 {
+            continue;
+        }
+        let travel = node.velocity * (VELOCITY_ARROW_SECONDS * scale);
+        let tip = to_bevy_vec2(&(node_position + travel));
+        gizmos.arrow_2d(centre, tip, Color::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GridConfig, SolverParams};
+    use crate::core::{Grid, Particle};
+    use crate::materials::MaterialType;
+    use crate::math::{Real, Vector, zero_vector};
+    use crate::solver::transfer_particles_to_grid;
+
+    #[test]
+    fn density_texture_peaks_where_particles_are_densest() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        // Four particles per cell on the left, one per cell on the right
+        for j in 0..16 {
+            for i in 0..16 {
+                let position = Vector::new(20.25, 20.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+        for j in 0..8 {
+            for i in 0..8 {
+                let position = Vector::new(40.5 + i as Real, 20.5 + j as Real);
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+        transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+
+        let bounds = Aabb2d {
+            min: Vec2::new(0.0, 0.0),
+            max: Vec2::new(64.0, 64.0),
+        };
+        let texture = density_texture(&state, UVec2::new(64, 64), bounds);
+        assert_eq!(texture.len(), 64 * 64);
+        let pixel = |x: usize, y: usize| texture[(63 - y) * 64 + x];
+
+        let (brightest, _) = texture
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        let (x, y) = (brightest % 64, 63 - brightest / 64);
+        assert!(
+            (20..28).contains(&x) && (20..28).contains(&y),
+            "peak at {x}, {y}"
+        );
+        assert!(pixel(24, 24) > 2.0 * pixel(44, 24));
+        assert!(pixel(44, 24) > 0.0);
+        assert_eq!(pixel(10, 50), 0.0);
+    }
+
+    #[test]
+    fn density_texture_follows_the_grid_origin_and_scale() {
+        let mut state = MpmState::new(SolverParams::default(), zero_vector());
+        let config = GridConfig::default()
+            .with_origin(Vector::new(100.0, -40.0))
+            .with_scale(0.5);
+        *state.grid_mut() = Grid::from_config(&config);
+        for j in 0..16 {
+            for i in 0..16 {
+                let position = Vector::new(28.25, 28.25) + Vector::new(i as Real, j as Real) * 0.5;
+                state.add_particle(Particle::new(position, MaterialType::water()));
+            }
+        }
+        transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+
+        // Simulation space 0..64 on each axis, one pixel per cell
+        let bounds = Aabb2d {
+            min: Vec2::new(100.0, -40.0),
+            max: Vec2::new(132.0, -8.0),
+        };
+        let texture = density_texture(&state, UVec2::new(64, 64), bounds);
+        let pixel = |x: usize, y: usize| texture[(63 - y) * 64 + x];
+        assert!(pixel(32, 32) > 0.0);
+        assert_eq!(pixel(5, 5), 0.0);
+        assert_eq!(pixel(58, 58), 0.0);
+    }
+
+    #[test]
+    fn debug_gizmos_draw_empty_and_populated_grids() {
+        use bevy::gizmos::config::{DefaultGizmoConfigGroup, GizmoConfigStore};
+        use bevy::gizmos::gizmos::GizmoStorage;
+
+        let mut world = World::new();
+        let mut store = GizmoConfigStore::default();
+        store.register::<DefaultGizmoConfigGroup>();
+        world.insert_resource(store);
+        world.init_resource::<GizmoStorage<DefaultGizmoConfigGroup, ()>>();
+        world.insert_resource(MpmState::new(SolverParams::default(), zero_vector()));
+        let mut schedule = Schedule::default();
+        schedule.add_systems(draw_debug_gizmos);
+
+        schedule.run(&mut world);
+
+        let mut state = world.resource_mut::<MpmState>();
+        for i in 0..50 {
+            let position = Vector::new(30.0 + (i % 10) as Real * 0.5, 30.0 + (i / 10) as Real);
+            let particle = Particle::new(position, MaterialType::water());
+            state.add_particle(particle.with_velocity(Vector::new(2.0, -1.0)));
+        }
+        transfer_particles_to_grid(&mut state, 1.0 / 60.0);
+        schedule.run(&mut world);
+    }
+}